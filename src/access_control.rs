@@ -0,0 +1,236 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+//! Authorization of `send` and `register_listener`.
+//!
+//! Set the hook with `UPClientZenohBuilder::with_access_control`. Everything is allowed without it.
+//! [`RuleFileAccessControl`] is the default implementation reading the rules from a file, one rule per line:
+//!
+//! ```text
+//! # <allow|deny> <send|register|*> <publish|notification|request|response|*> <local authority|*> <source UUri> [sink UUri]
+//! allow send publish vehicle //vehicle/1/1/8000
+//! allow register request * //*/FFFF/FF/FFFF //vehicle/2/1/1
+//! deny * * * //*/FFFF/FF/FFFF
+//! ```
+//!
+//! The rules are checked in order and the first matching one decides. The request is denied if no rule matches.
+//! The `UUri` in the rule can contain wildcard, and the omitted sink matches any sink.
+use crate::{error::TransportError, key_mapping::uri_to_zenoh_key, UPClientZenoh};
+use std::{fs, path::Path, str::FromStr};
+use up_rust::{UCode, UMessageType, UStatus, UUri};
+use zenoh::key_expr::keyexpr;
+
+/// The operation to authorize.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    /// `UTransport::send`
+    Send,
+    /// `UTransport::register_listener`
+    RegisterListener,
+}
+
+/// The request to be authorized by `AccessControl`.
+#[derive(Clone, Copy, Debug)]
+pub struct AccessRequest<'a> {
+    /// The operation to do
+    pub operation: Operation,
+    /// The type of the message sent or listened to
+    pub message_type: UMessageType,
+    /// The authority name of the local `UPClientZenoh`
+    pub authority: &'a str,
+    /// The source of the message, or the source filter of the listener
+    pub source: &'a UUri,
+    /// The sink of the message, or the sink filter of the listener
+    pub sink: Option<&'a UUri>,
+}
+
+/// The pluggable authorization hook checked in `send` and `register_listener`.
+pub trait AccessControl: Send + Sync {
+    /// Allow or deny the request.
+    ///
+    /// # Errors
+    /// Will return `Err` with the reason if the request is denied
+    fn authorize(&self, request: &AccessRequest) -> Result<(), String>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Action {
+    Allow,
+    Deny,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    line: usize,
+    action: Action,
+    // None means any
+    operation: Option<Operation>,
+    message_type: Option<UMessageType>,
+    authority: Option<String>,
+    source: UUri,
+    sink: Option<UUri>,
+}
+
+// Whether the pattern with wildcard includes all the UUri matched by `uri`
+pub(crate) fn uri_includes(authority: &str, pattern: &UUri, uri: &UUri) -> bool {
+    let pattern = uri_to_zenoh_key(authority, pattern);
+    let uri = uri_to_zenoh_key(authority, uri);
+    match (keyexpr::new(&pattern), keyexpr::new(&uri)) {
+        (Ok(pattern), Ok(uri)) => pattern.includes(uri),
+        _ => false,
+    }
+}
+
+impl Rule {
+    fn parse(line: usize, text: &str) -> Result<Self, String> {
+        let fields = text.split_whitespace().collect::<Vec<_>>();
+        let (action, operation, message_type, authority, source, sink) = match fields[..] {
+            [action, operation, message_type, authority, source] => {
+                (action, operation, message_type, authority, source, None)
+            }
+            [action, operation, message_type, authority, source, sink] => (
+                action,
+                operation,
+                message_type,
+                authority,
+                source,
+                Some(sink),
+            ),
+            _ => return Err("expect 5 or 6 fields".to_string()),
+        };
+        let action = match action {
+            "allow" => Action::Allow,
+            "deny" => Action::Deny,
+            _ => return Err(format!("unknown action {action}")),
+        };
+        let operation = match operation {
+            "send" => Some(Operation::Send),
+            "register" => Some(Operation::RegisterListener),
+            "*" => None,
+            _ => return Err(format!("unknown operation {operation}")),
+        };
+        let message_type = match message_type {
+            "publish" => Some(UMessageType::UMESSAGE_TYPE_PUBLISH),
+            "notification" => Some(UMessageType::UMESSAGE_TYPE_NOTIFICATION),
+            "request" => Some(UMessageType::UMESSAGE_TYPE_REQUEST),
+            "response" => Some(UMessageType::UMESSAGE_TYPE_RESPONSE),
+            "*" => None,
+            _ => return Err(format!("unknown message type {message_type}")),
+        };
+        let authority = (authority != "*").then(|| authority.to_string());
+        let parse_uri =
+            |uri: &str| UUri::from_str(uri).map_err(|e| format!("invalid UUri {uri}: {e:?}"));
+        Ok(Rule {
+            line,
+            action,
+            operation,
+            message_type,
+            authority,
+            source: parse_uri(source)?,
+            sink: sink.map(parse_uri).transpose()?,
+        })
+    }
+
+    fn matches(&self, request: &AccessRequest) -> bool {
+        self.operation.map_or(true, |op| op == request.operation)
+            && self
+                .message_type
+                .map_or(true, |ty| ty == request.message_type)
+            && self
+                .authority
+                .as_ref()
+                .map_or(true, |authority| authority == request.authority)
+            && uri_includes(request.authority, &self.source, request.source)
+            && match (&self.sink, request.sink) {
+                (None, _) => true,
+                (Some(pattern), Some(sink)) => uri_includes(request.authority, pattern, sink),
+                (Some(_), None) => false,
+            }
+    }
+}
+
+/// `AccessControl` with the rules read from a file. See the [module documentation](self) for the format.
+#[derive(Clone, Debug)]
+pub struct RuleFileAccessControl {
+    rules: Vec<Rule>,
+}
+
+impl RuleFileAccessControl {
+    /// Read the rules from the file at `path`.
+    ///
+    /// # Errors
+    /// Will return `Err` if unable to read the file or the rules are invalid
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, UStatus> {
+        let content = fs::read_to_string(path).map_err(|e| {
+            let msg = format!("Unable to read the access control rule file: {e:?}");
+            tracing::error!(code = ?UCode::INVALID_ARGUMENT, "{msg}");
+            UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg)
+        })?;
+        Self::parse(&content)
+    }
+
+    /// Parse the rules, one rule per line. Empty lines and the lines starting with `#` are skipped.
+    ///
+    /// # Errors
+    /// Will return `Err` if any rule is invalid
+    pub fn parse(content: &str) -> Result<Self, UStatus> {
+        let rules = content
+            .lines()
+            .enumerate()
+            .map(|(index, text)| (index + 1, text.trim()))
+            .filter(|(_, text)| !text.is_empty() && !text.starts_with('#'))
+            .map(|(line, text)| {
+                Rule::parse(line, text).map_err(|e| {
+                    let msg = format!("Invalid access control rule at line {line}: {e}");
+                    tracing::error!(code = ?UCode::INVALID_ARGUMENT, "{msg}");
+                    UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg)
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(RuleFileAccessControl { rules })
+    }
+}
+
+impl AccessControl for RuleFileAccessControl {
+    fn authorize(&self, request: &AccessRequest) -> Result<(), String> {
+        match self.rules.iter().find(|rule| rule.matches(request)) {
+            Some(rule) if rule.action == Action::Allow => Ok(()),
+            Some(rule) => Err(format!("denied by the rule at line {}", rule.line)),
+            None => Err("no rule allows it".to_string()),
+        }
+    }
+}
+
+impl UPClientZenoh {
+    // Check the request with the access control hook if any
+    pub(crate) fn authorize(
+        &self,
+        operation: Operation,
+        message_type: UMessageType,
+        source: &UUri,
+        sink: Option<&UUri>,
+    ) -> Result<(), TransportError> {
+        let Some(access_control) = &self.access_control else {
+            return Ok(());
+        };
+        let request = AccessRequest {
+            operation,
+            message_type,
+            authority: &self.authority_name,
+            source,
+            sink,
+        };
+        access_control.authorize(&request).map_err(|reason| {
+            TransportError::PermissionDenied(format!("{operation:?} {message_type:?}: {reason}"))
+        })
+    }
+}
@@ -0,0 +1,214 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+//! Poke at a uProtocol-over-Zenoh system from the shell.
+//!
+//! ```shell
+//! up-cli --authority cli pub //vehicle1/1234/1/8000 "Hello World"
+//! up-cli --authority cli sub //vehicle1/1234/1/8000
+//! up-cli --authority server serve //server/4321/1/1 "Response"
+//! up-cli --authority cli call //server/4321/1/1 "Request"
+//! up-cli sniff
+//! ```
+use async_trait::async_trait;
+use clap::{Parser, Subcommand};
+use std::{path::PathBuf, str::FromStr, sync::Arc};
+use up_rust::{
+    RpcClient, UListener, UMessage, UMessageBuilder, UPayloadFormat, UStatus, UTransport, UUri,
+};
+use up_transport_zenoh::{Config, UPClientZenoh};
+
+#[derive(Parser)]
+#[command(version, about = "Command-line client of uProtocol over Zenoh")]
+struct Args {
+    /// The Zenoh configuration file
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+    /// The authority name of this client
+    #[arg(short, long, default_value = "up-cli")]
+    authority: String,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Publish the payload to the topic
+    Pub {
+        /// The topic UUri, e.g. //vehicle1/1234/1/8000
+        topic: String,
+        /// The payload in text
+        payload: String,
+    },
+    /// Print the messages matching the filters until Ctrl-C
+    Sub {
+        /// The source UUri filter, e.g. //vehicle1/FFFF/FF/FFFF
+        source: String,
+        /// The sink UUri filter, which is required for Notification
+        #[arg(long)]
+        sink: Option<String>,
+    },
+    /// Invoke the method and print the response
+    Call {
+        /// The method UUri, e.g. //server/4321/1/1
+        method: String,
+        /// The request payload in text
+        payload: String,
+        /// The UUri to receive the response. Default to //[authority]/1/1/0
+        #[arg(long)]
+        source: Option<String>,
+        /// The TTL of the request in milliseconds
+        #[arg(long, default_value_t = 1000)]
+        ttl: u32,
+    },
+    /// Answer the method with the fixed payload until Ctrl-C
+    Serve {
+        /// The method UUri, e.g. //server/4321/1/1
+        method: String,
+        /// The response payload in text
+        payload: String,
+    },
+    /// Print the decoded Publish and Notification messages in the key space until Ctrl-C
+    Sniff {
+        /// The Zenoh key expression to sniff
+        #[arg(default_value = "up/**")]
+        key_expr: String,
+    },
+}
+
+fn print_message(msg: &UMessage) {
+    println!("attributes: {:?}", msg.attributes);
+    if let Some(payload) = &msg.payload {
+        println!("payload: {}", String::from_utf8_lossy(payload));
+    }
+}
+
+struct PrintListener;
+#[async_trait]
+impl UListener for PrintListener {
+    async fn on_receive(&self, msg: UMessage) {
+        print_message(&msg);
+    }
+    async fn on_error(&self, err: UStatus) {
+        eprintln!("Error: {err:?}");
+    }
+}
+
+struct ServeListener {
+    upclient: Arc<UPClientZenoh>,
+    payload: String,
+}
+#[async_trait]
+impl UListener for ServeListener {
+    async fn on_receive(&self, msg: UMessage) {
+        print_message(&msg);
+        let response = UMessageBuilder::response_for_request(&msg.attributes)
+            .build_with_payload(self.payload.clone(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT);
+        let result = match response {
+            Ok(response) => self.upclient.send(response).await,
+            Err(e) => {
+                eprintln!("Unable to build the response: {e:?}");
+                return;
+            }
+        };
+        if let Err(e) = result {
+            eprintln!("Unable to send the response: {e:?}");
+        }
+    }
+    async fn on_error(&self, err: UStatus) {
+        eprintln!("Error: {err:?}");
+    }
+}
+
+async fn new_upclient(
+    config: Option<PathBuf>,
+    authority: String,
+) -> Result<UPClientZenoh, Box<dyn std::error::Error>> {
+    let config = if let Some(config) = config {
+        Config::from_file(config)?
+    } else {
+        Config::default()
+    };
+    Ok(UPClientZenoh::new(config, authority).await?)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+    let args = Args::parse();
+    let authority = args.authority.clone();
+    let upclient = Arc::new(new_upclient(args.config, args.authority).await?);
+
+    match args.command {
+        Command::Pub { topic, payload } => {
+            let umessage = UMessageBuilder::publish(UUri::from_str(&topic)?)
+                .build_with_payload(payload, UPayloadFormat::UPAYLOAD_FORMAT_TEXT)?;
+            upclient.send(umessage).await?;
+        }
+        Command::Sub { source, sink } => {
+            let source = UUri::from_str(&source)?;
+            let sink = sink.as_deref().map(UUri::from_str).transpose()?;
+            upclient
+                .register_listener(&source, sink.as_ref(), Arc::new(PrintListener))
+                .await?;
+            tokio::signal::ctrl_c().await?;
+        }
+        Command::Call {
+            method,
+            payload,
+            source,
+            ttl,
+        } => {
+            let method = UUri::from_str(&method)?;
+            let source = if let Some(source) = source {
+                UUri::from_str(&source)?
+            } else {
+                UUri {
+                    authority_name: authority,
+                    ue_id: 1,
+                    ue_version_major: 1,
+                    resource_id: 0,
+                    ..Default::default()
+                }
+            };
+            let request = UMessageBuilder::request(method.clone(), source, ttl)
+                .build_with_payload(payload, UPayloadFormat::UPAYLOAD_FORMAT_TEXT)?;
+            let response = upclient.invoke_method(method, request).await?;
+            print_message(&response);
+        }
+        Command::Serve { method, payload } => {
+            let method = UUri::from_str(&method)?;
+            let source_filter = UUri {
+                authority_name: "*".to_string(),
+                ue_id: 0xFFFF,
+                ue_version_major: 0xFF,
+                resource_id: 0xFFFF,
+                ..Default::default()
+            };
+            let listener = Arc::new(ServeListener {
+                upclient: upclient.clone(),
+                payload,
+            });
+            upclient
+                .register_listener(&source_filter, Some(&method), listener)
+                .await?;
+            tokio::signal::ctrl_c().await?;
+        }
+        Command::Sniff { key_expr } => {
+            let _sniffer = upclient
+                .start_sniffing(&key_expr, |msg| println!("{msg}"))
+                .await?;
+            tokio::signal::ctrl_c().await?;
+        }
+    }
+    Ok(())
+}
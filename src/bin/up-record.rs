@@ -0,0 +1,114 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+//! Record the uProtocol traffic over Zenoh into a log file and replay it later.
+//!
+//! ```shell
+//! # Record until Ctrl-C
+//! up-record record vehicle.uplog
+//! # Replay twice as fast, only the messages from uEntity 0x1234 of vehicle1
+//! up-record replay vehicle.uplog --speed 2 --source //vehicle1/1234/FF/FFFF
+//! ```
+use clap::{Parser, Subcommand};
+use std::{path::PathBuf, str::FromStr};
+use up_rust::UUri;
+use up_transport_zenoh::{
+    record::{read_record_file, ReplayOptions},
+    Config, UPClientZenoh,
+};
+
+#[derive(Parser)]
+#[command(version, about = "Record and replay uProtocol traffic over Zenoh")]
+struct Args {
+    /// The Zenoh configuration file
+    #[arg(short, long)]
+    config: Option<PathBuf>,
+    /// The authority name of this client
+    #[arg(short, long, default_value = "up-record")]
+    authority: String,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Record the Publish and Notification messages into the log file until Ctrl-C
+    Record {
+        /// The log file to write
+        file: PathBuf,
+    },
+    /// Re-publish the messages in the log file with the original timing
+    Replay {
+        /// The log file to read
+        file: PathBuf,
+        /// Replay SPEED times faster than the original timing
+        #[arg(short, long, default_value_t = 1.0)]
+        speed: f64,
+        /// Only replay the messages whose source matches the UUri, e.g. //vehicle1/1234/FF/FFFF
+        #[arg(long)]
+        source: Option<String>,
+    },
+    /// Print the messages in the log file
+    Dump {
+        /// The log file to read
+        file: PathBuf,
+    },
+}
+
+async fn new_upclient(
+    config: Option<PathBuf>,
+    authority: String,
+) -> Result<UPClientZenoh, Box<dyn std::error::Error>> {
+    let config = if let Some(config) = config {
+        Config::from_file(config)?
+    } else {
+        Config::default()
+    };
+    Ok(UPClientZenoh::new(config, authority).await?)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+    let args = Args::parse();
+
+    match args.command {
+        Command::Record { file } => {
+            let upclient = new_upclient(args.config, args.authority).await?;
+            let _recorder = upclient.start_recording(&file).await?;
+            println!("Recording into {}, press Ctrl-C to stop", file.display());
+            tokio::signal::ctrl_c().await?;
+        }
+        Command::Replay {
+            file,
+            speed,
+            source,
+        } => {
+            if speed <= 0.0 {
+                return Err("The replay speed should be positive".into());
+            }
+            let mut options = ReplayOptions::default().with_speed(speed);
+            if let Some(source) = source {
+                options = options.with_source_filter(UUri::from_str(&source)?);
+            }
+            let upclient = new_upclient(args.config, args.authority).await?;
+            let sent = upclient.replay(&file, &options).await?;
+            println!("Replayed {sent} messages from {}", file.display());
+        }
+        Command::Dump { file } => {
+            for (timestamp, msg) in read_record_file(&file)? {
+                println!("{timestamp} {:?}", msg.attributes);
+            }
+        }
+    }
+    Ok(())
+}
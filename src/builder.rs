@@ -0,0 +1,288 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+use crate::{
+    access_control::AccessControl,
+    connectivity::{ConnectivityMonitor, LocalListeners},
+    encryption::EncryptionConfig,
+    error::TransportError,
+    history::{History, HistoryConfig},
+    metrics::Metrics,
+    offline_queue::{OfflineQueue, OfflineQueueConfig},
+    rate_limit::{RateLimitConfig, RateLimiter},
+    signing::SigningConfig,
+    tls::TlsConfig,
+    Config, QueryingSubscriberMap, SubscriberMap, UPClientZenoh,
+};
+use std::{
+    collections::HashMap,
+    sync::{atomic::AtomicBool, Arc, Mutex},
+};
+use up_rust::{UCode, UStatus};
+use zenoh::{prelude::r#async::*, runtime::Runtime as ZRuntime};
+
+/// How `UPClientZenoh` handles the outgoing message whose source authority differs from its own.
+/// The omitted source authority is always filled with its own.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AuthorityPolicy {
+    /// Send the message as it is
+    #[default]
+    Trust,
+    /// Fail the message with `UCode::PERMISSION_DENIED`
+    Reject,
+    /// Replace the source authority with its own
+    Rewrite,
+}
+
+/// Builder of `UPClientZenoh`. Use `UPClientZenoh::builder` to create it.
+pub struct UPClientZenohBuilder {
+    authority_name: String,
+    config: Config,
+    runtime: Option<ZRuntime>,
+    rpc_fail_fast: bool,
+    send_fail_fast: bool,
+    history: Option<HistoryConfig>,
+    access_control: Option<Arc<dyn AccessControl>>,
+    authority_policy: AuthorityPolicy,
+    signing: Option<SigningConfig>,
+    encryption: Option<EncryptionConfig>,
+    tls: Option<TlsConfig>,
+    rate_limit: RateLimitConfig,
+    offline_queue: Option<OfflineQueueConfig>,
+}
+
+impl UPClientZenohBuilder {
+    pub(crate) fn new(authority_name: String) -> Self {
+        UPClientZenohBuilder {
+            authority_name,
+            config: Config::default(),
+            runtime: None,
+            rpc_fail_fast: false,
+            send_fail_fast: false,
+            history: None,
+            access_control: None,
+            authority_policy: AuthorityPolicy::Trust,
+            signing: None,
+            encryption: None,
+            tls: None,
+            rate_limit: RateLimitConfig::default(),
+            offline_queue: None,
+        }
+    }
+
+    /// Open the Zenoh session with the configuration. `Config::default()` is used if not set.
+    /// You can refer to [here](https://github.com/eclipse-zenoh/zenoh/blob/0.11.0-rc.3/DEFAULT_CONFIG.json5) for more configuration details.
+    #[must_use]
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Open the Zenoh session on an existing Zenoh Runtime instead of the configuration.
+    #[must_use]
+    pub fn with_runtime(mut self, runtime: ZRuntime) -> Self {
+        self.runtime = Some(runtime);
+        self
+    }
+
+    /// Fail the RPC request with `UCode::UNAVAILABLE` immediately if nobody serves the method,
+    /// instead of waiting until the TTL expires. Disabled by default.
+    ///
+    /// The method is known from the liveliness announced by `register_listener`,
+    /// so the server needs to register the request listener with the specific method `UUri`.
+    #[must_use]
+    pub fn with_rpc_fail_fast(mut self, enabled: bool) -> Self {
+        self.rpc_fail_fast = enabled;
+        self
+    }
+
+    /// Fail the Publish and Notification with `UCode::UNAVAILABLE` immediately while no router or peer
+    /// is connected and no local listener matches, instead of dropping them silently. The Request is failed
+    /// the same way unless the method is served locally, instead of waiting until the TTL. Disabled by default.
+    #[must_use]
+    pub fn with_send_fail_fast(mut self, enabled: bool) -> Self {
+        self.send_fail_fast = enabled;
+        self
+    }
+
+    /// Record the Publish and Notification messages reachable through Zenoh,
+    /// which can be retrieved by `UPClientZenoh::get_history` later. Disabled by default.
+    #[must_use]
+    pub fn with_history(mut self, config: HistoryConfig) -> Self {
+        self.history = Some(config);
+        self
+    }
+
+    /// Authorize `send` and `register_listener` with the hook, e.g. `RuleFileAccessControl`.
+    /// The denied operation fails with `UCode::PERMISSION_DENIED`. Everything is allowed by default.
+    #[must_use]
+    pub fn with_access_control(mut self, access_control: Arc<dyn AccessControl>) -> Self {
+        self.access_control = Some(access_control);
+        self
+    }
+
+    /// Enable the strict authority mode with `AuthorityPolicy::Reject` or `AuthorityPolicy::Rewrite`,
+    /// so the client can't impersonate another authority. `AuthorityPolicy::Trust` by default.
+    ///
+    /// In the strict mode, the inbound message is also rejected if the authority in its Zenoh key
+    /// differs from the source authority in its `UAttributes`, and the listener gets
+    /// `UCode::PERMISSION_DENIED` from `on_error`.
+    #[must_use]
+    pub fn with_authority_policy(mut self, policy: AuthorityPolicy) -> Self {
+        self.authority_policy = policy;
+        self
+    }
+
+    /// Sign the outgoing messages and verify the received ones with the keys in `config`.
+    /// The listener gets `UCode::UNAUTHENTICATED` from `on_error` for the rejected message. Disabled by default.
+    #[must_use]
+    pub fn with_signing(mut self, config: SigningConfig) -> Self {
+        self.signing = Some(config);
+        self
+    }
+
+    /// Encrypt the payload of the topics in `config` and decrypt the received ones with its keys.
+    /// The listener gets `UCode::UNAUTHENTICATED` from `on_error` if the payload can't be decrypted. Disabled by default.
+    #[must_use]
+    pub fn with_encryption(mut self, config: EncryptionConfig) -> Self {
+        self.encryption = Some(config);
+        self
+    }
+
+    /// Open the TLS or mTLS endpoints in `config` on top of the Zenoh configuration.
+    /// It can't be used with `with_runtime`, whose configuration is fixed already.
+    #[must_use]
+    pub fn with_tls(mut self, config: TlsConfig) -> Self {
+        self.tls = Some(config);
+        self
+    }
+
+    /// Limit the received messages delivered to the listeners of `register_listener`,
+    /// so a flooding source can't spawn unlimited callbacks. Nothing is limited by default.
+    #[must_use]
+    pub fn with_rate_limit(mut self, config: RateLimitConfig) -> Self {
+        self.rate_limit = config;
+        self
+    }
+
+    /// Queue the Publish and Notification messages while no router or peer is connected,
+    /// and flush them in order on reconnect. The expired ones are dropped. Disabled by default.
+    ///
+    /// The queued messages are not delivered to the local listeners until flushed either.
+    /// All the topics share one queue to keep the sending order, so the message nobody can receive
+    /// holds the ones behind it, even those a local listener matches, until a router or peer connects.
+    #[must_use]
+    pub fn with_offline_queue(mut self, config: OfflineQueueConfig) -> Self {
+        self.offline_queue = Some(config);
+        self
+    }
+
+    /// Create `UPClientZenoh`.
+    ///
+    /// # Errors
+    /// Will return `Err` if unable to create `UPClientZenoh`
+    pub async fn build(mut self) -> Result<UPClientZenoh, UStatus> {
+        // Apply TLS to the Zenoh config
+        if let Some(tls) = &self.tls {
+            if self.runtime.is_some() {
+                let msg = "TLS can't be applied to the existing Zenoh runtime".to_string();
+                tracing::error!(code = ?UCode::INVALID_ARGUMENT, "{msg}");
+                return Err(UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg));
+            }
+            tls.apply(&mut self.config, &self.authority_name)?;
+        }
+        let peer_certificates = match &self.tls {
+            Some(tls) => tls.peer_certificate_check()?,
+            None => None,
+        };
+        // Create Zenoh session
+        let shared_runtime = self.runtime.is_some();
+        let session = if let Some(runtime) = self.runtime {
+            zenoh::init(runtime).res().await
+        } else {
+            zenoh::open(self.config).res().await
+        };
+        let Ok(session) = session else {
+            let msg = "Unable to open Zenoh session".to_string();
+            tracing::error!(code = ?UCode::INTERNAL, "{msg}");
+            return Err(UStatus::fail_with_code(UCode::INTERNAL, msg));
+        };
+        let session = Arc::new(session);
+        // Announce the authority is alive
+        let authority_token = session
+            .liveliness()
+            .declare_token(UPClientZenoh::authority_liveliness_key(
+                &self.authority_name,
+            ))
+            .res()
+            .await
+            .map_err(|e| {
+                let msg = format!("Unable to declare Zenoh liveliness token: {e:?}");
+                tracing::error!(code = ?UCode::INTERNAL, "{msg}");
+                UStatus::fail_with_code(UCode::INTERNAL, msg)
+            })?;
+        let metrics = Arc::new(Metrics::new());
+        let rate_limiter = Arc::new(RateLimiter::new(self.rate_limit));
+        // Follow the connectivity with the transport events
+        let connectivity = ConnectivityMonitor::start(&session, rate_limiter.clone())
+            .await
+            .map_err(TransportError::into_status)?;
+        // The Publish listeners are the local routes of the offline queue as well
+        let subscriber_map: SubscriberMap = Arc::new(Mutex::new(HashMap::new()));
+        let querying_subscriber_map: QueryingSubscriberMap = Arc::new(Mutex::new(HashMap::new()));
+        // Start flushing the offline queue
+        let offline_queue = if let Some(config) = &self.offline_queue {
+            Some(OfflineQueue::start(
+                config,
+                Arc::downgrade(&session),
+                connectivity.subscribe(),
+                LocalListeners::new(subscriber_map.clone(), querying_subscriber_map.clone()),
+                metrics.clone(),
+            )?)
+        } else {
+            None
+        };
+        let mut upclient = UPClientZenoh {
+            session,
+            subscriber_map,
+            queryable_map: Arc::new(Mutex::new(HashMap::new())),
+            query_map: Arc::new(Mutex::new(HashMap::new())),
+            rpc_callback_map: Arc::new(Mutex::new(HashMap::new())),
+            liveliness_token_map: Arc::new(Mutex::new(HashMap::new())),
+            discovery_subscriber_map: Arc::new(Mutex::new(HashMap::new())),
+            publication_cache_map: Arc::new(Mutex::new(HashMap::new())),
+            querying_subscriber_map,
+            _authority_token: authority_token,
+            history: None,
+            metrics,
+            rpc_fail_fast: self.rpc_fail_fast,
+            access_control: self.access_control,
+            authority_policy: self.authority_policy,
+            signing: self.signing.map(Arc::new),
+            encryption: self.encryption.map(Arc::new),
+            peer_certificates,
+            rate_limiter,
+            connectivity,
+            send_fail_fast: self.send_fail_fast,
+            offline_queue,
+            authority_name: self.authority_name,
+            closed: AtomicBool::new(false),
+            shared_runtime,
+        };
+        // Start recording the history, which verifies the messages like the listeners
+        if let Some(config) = &self.history {
+            let history = History::new(&upclient.session, config, upclient.inbound_check()).await?;
+            upclient.history = Some(history);
+        }
+        Ok(upclient)
+    }
+}
@@ -0,0 +1,173 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+// Run the golden vectors in `conformance/` against the implementation.
+// The vectors are shared with other language bindings, see `conformance/README.md`.
+use crate::{
+    key_mapping::{get_listener_message_type, to_zenoh_key_string, MessageFlag},
+    UPClientZenoh, UATTRIBUTE_VERSION,
+};
+use protobuf::Enum;
+use serde_json::Value;
+use std::str::FromStr;
+use up_rust::{UAttributes, UMessageType, UPriority, UUri, UUID};
+use zenoh::sample::AttachmentBuilder;
+
+const KEY_EXPR_VECTORS: &str = include_str!("../conformance/key_expr.json");
+const ATTACHMENT_VECTORS: &str = include_str!("../conformance/attachment.json");
+const MESSAGE_TYPE_VECTORS: &str = include_str!("../conformance/message_type.json");
+
+fn load_vectors(json: &str) -> Value {
+    serde_json::from_str(json).expect("Conformance vectors should be valid JSON")
+}
+
+fn description(vector: &Value) -> &str {
+    vector["description"].as_str().unwrap()
+}
+
+fn optional_uuri(value: &Value) -> Option<UUri> {
+    value.as_str().map(|uri| UUri::from_str(uri).unwrap())
+}
+
+fn optional_uuid(value: &Value) -> Option<UUID> {
+    let parse = |field: &Value| {
+        let hex = field.as_str().unwrap().trim_start_matches("0x");
+        u64::from_str_radix(hex, 16).unwrap()
+    };
+    value.as_object().map(|uuid| UUID {
+        msb: parse(&uuid["msb"]),
+        lsb: parse(&uuid["lsb"]),
+        ..Default::default()
+    })
+}
+
+fn decode_hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+fn uattributes_from_vector(value: &Value) -> UAttributes {
+    UAttributes {
+        id: optional_uuid(&value["id"]).into(),
+        type_: <UMessageType as Enum>::from_str(value["type"].as_str().unwrap())
+            .unwrap()
+            .into(),
+        source: optional_uuri(&value["source"]).into(),
+        sink: optional_uuri(&value["sink"]).into(),
+        priority: <UPriority as Enum>::from_str(value["priority"].as_str().unwrap())
+            .unwrap()
+            .into(),
+        ttl: value["ttl"].as_u64().map(|ttl| u32::try_from(ttl).unwrap()),
+        reqid: optional_uuid(&value["reqid"]).into(),
+        ..Default::default()
+    }
+}
+
+fn attachment_entries_from_vector(value: &Value) -> Vec<(Vec<u8>, Vec<u8>)> {
+    value
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| {
+            (
+                entry["key"].as_str().unwrap().as_bytes().to_vec(),
+                decode_hex(entry["value"].as_str().unwrap()),
+            )
+        })
+        .collect()
+}
+
+fn message_flag_from_vector(value: &Value) -> MessageFlag {
+    let mut flag = MessageFlag::none();
+    for message_type in value.as_array().unwrap() {
+        flag |= match message_type.as_str().unwrap() {
+            "Publish" => MessageFlag::Publish,
+            "Notification" => MessageFlag::Notification,
+            "Request" => MessageFlag::Request,
+            "Response" => MessageFlag::Response,
+            other => panic!("Unknown message type {other}"),
+        };
+    }
+    flag
+}
+
+#[test]
+fn test_conformance_key_expr() {
+    let vectors = load_vectors(KEY_EXPR_VECTORS);
+    let authority = vectors["local_authority"].as_str().unwrap();
+
+    for vector in vectors["vectors"].as_array().unwrap() {
+        let src = optional_uuri(&vector["source"]).unwrap();
+        let sink = optional_uuri(&vector["sink"]);
+        assert_eq!(
+            to_zenoh_key_string(authority, &src, sink.as_ref()),
+            vector["key_expr"].as_str().unwrap(),
+            "{}",
+            description(vector)
+        );
+    }
+}
+
+#[test]
+fn test_conformance_attachment() {
+    let vectors = load_vectors(ATTACHMENT_VECTORS);
+    assert_eq!(
+        vectors["version"].as_u64(),
+        Some(u64::from(UATTRIBUTE_VERSION))
+    );
+
+    for vector in vectors["vectors"].as_array().unwrap() {
+        let uattributes = uattributes_from_vector(&vector["attributes"]);
+        let expected = attachment_entries_from_vector(&vector["attachment"]);
+
+        // UAttributes => attachment
+        let attachment = UPClientZenoh::uattributes_to_attachment(&uattributes)
+            .unwrap()
+            .build();
+        let entries = attachment
+            .iter()
+            .map(|(key, value)| (key.as_slice().to_vec(), value.as_slice().to_vec()))
+            .collect::<Vec<_>>();
+        assert_eq!(entries, expected, "{}", description(vector));
+
+        // attachment => UAttributes
+        let mut builder = AttachmentBuilder::new();
+        for (key, value) in &expected {
+            builder.insert(&key[..], &value[..]);
+        }
+        assert_eq!(
+            UPClientZenoh::attachment_to_uattributes(&builder.build()).unwrap(),
+            uattributes,
+            "{}",
+            description(vector)
+        );
+    }
+}
+
+#[test]
+fn test_conformance_message_type() {
+    let vectors = load_vectors(MESSAGE_TYPE_VECTORS);
+
+    for vector in vectors["vectors"].as_array().unwrap() {
+        let src = optional_uuri(&vector["source"]).unwrap();
+        let sink = optional_uuri(&vector["sink"]);
+        let expected = message_flag_from_vector(&vector["message_types"]);
+        let result = get_listener_message_type(&src, sink.as_ref());
+        if expected.is_none() {
+            assert!(result.is_err(), "{}", description(vector));
+        } else {
+            assert_eq!(result, Ok(expected), "{}", description(vector));
+        }
+    }
+}
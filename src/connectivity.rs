@@ -0,0 +1,432 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+//! Connectivity of the Zenoh session to the routers and peers.
+//!
+//! The status is read from the Zenoh session info once, and then kept up to date by the transport events
+//! of the Zenoh session admin space (`@/session/<zid>/transport/unicast/<peer zid>`), which are published
+//! as soon as a transport to a router or peer is opened or closed. Reading the status costs no Zenoh call.
+//! Each `ConnectivityListener` receives the events one at a time in the order they happened.
+use crate::{
+    error::TransportError,
+    rate_limit::{InFlight, RateLimiter},
+    QueryingSubscriberMap, SubscriberMap, UPClientZenoh, CB_RUNTIME,
+};
+use async_trait::async_trait;
+use std::{
+    collections::{HashMap, HashSet},
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex, Weak},
+};
+use tokio::sync::{
+    mpsc::{self, UnboundedReceiver, UnboundedSender},
+    watch,
+};
+use up_rust::{ComparableListener, UCode, UStatus};
+use zenoh::{prelude::r#async::*, subscriber::Subscriber};
+
+/// A router or peer connected to the Zenoh session.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ZenohPeer {
+    /// The Zenoh ID
+    pub id: String,
+    /// Whether it's a router or a peer
+    pub is_router: bool,
+}
+
+/// The routers and peers connected to the Zenoh session.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConnectivityStatus {
+    /// The Zenoh IDs of the connected routers
+    pub routers: Vec<String>,
+    /// The Zenoh IDs of the connected peers
+    pub peers: Vec<String>,
+}
+
+impl ConnectivityStatus {
+    /// Whether at least one router or peer is connected.
+    #[must_use]
+    pub fn is_connected(&self) -> bool {
+        !self.routers.is_empty() || !self.peers.is_empty()
+    }
+
+    fn zenoh_peers(&self) -> HashSet<ZenohPeer> {
+        let routers = self.routers.iter().map(|id| ZenohPeer {
+            id: id.clone(),
+            is_router: true,
+        });
+        let peers = self.peers.iter().map(|id| ZenohPeer {
+            id: id.clone(),
+            is_router: false,
+        });
+        routers.chain(peers).collect()
+    }
+}
+
+/// The change of the connectivity.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConnectivityEvent {
+    /// The first router or peer is connected
+    Connected,
+    /// The last router or peer is disconnected
+    Disconnected,
+    /// A router or peer is connected
+    PeerJoined(ZenohPeer),
+    /// A router or peer is disconnected
+    PeerLeft(ZenohPeer),
+}
+
+// The events turning the status `from` into `to`
+fn connectivity_events(
+    from: &ConnectivityStatus,
+    to: &ConnectivityStatus,
+) -> Vec<ConnectivityEvent> {
+    let before = from.zenoh_peers();
+    let after = to.zenoh_peers();
+    let mut events = vec![];
+    if !from.is_connected() && to.is_connected() {
+        events.push(ConnectivityEvent::Connected);
+    }
+    events.extend(
+        after
+            .difference(&before)
+            .cloned()
+            .map(ConnectivityEvent::PeerJoined),
+    );
+    events.extend(
+        before
+            .difference(&after)
+            .cloned()
+            .map(ConnectivityEvent::PeerLeft),
+    );
+    if from.is_connected() && !to.is_connected() {
+        events.push(ConnectivityEvent::Disconnected);
+    }
+    events
+}
+
+/// Listener to be notified when the connectivity of the Zenoh session changes.
+#[async_trait]
+pub trait ConnectivityListener: Send + Sync {
+    /// Called for each change of the connectivity.
+    async fn on_event(&self, event: ConnectivityEvent);
+}
+
+// Make ConnectivityListener able to be the key of HashSet
+struct ComparableConnectivityListener(Arc<dyn ConnectivityListener>);
+impl ComparableConnectivityListener {
+    fn address(&self) -> *const () {
+        Arc::as_ptr(&self.0).cast::<()>()
+    }
+}
+impl PartialEq for ComparableConnectivityListener {
+    fn eq(&self, other: &Self) -> bool {
+        self.address() == other.address()
+    }
+}
+impl Eq for ComparableConnectivityListener {}
+impl Hash for ComparableConnectivityListener {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.address().hash(state);
+    }
+}
+
+// The queue of the events to each listener, delivered by its own task
+type ConnectivityListenerMap = Arc<
+    Mutex<HashMap<ComparableConnectivityListener, UnboundedSender<(ConnectivityEvent, InFlight)>>>,
+>;
+
+// Keep the status up to date with the transport events and notify the listeners of the changes
+pub(crate) struct ConnectivityMonitor {
+    status: watch::Sender<ConnectivityStatus>,
+    listeners: ConnectivityListenerMap,
+    // The transport events are received until it's dropped
+    _subscriber: Subscriber<'static, ()>,
+}
+
+async fn session_status(session: &Session) -> ConnectivityStatus {
+    let info = session.info();
+    ConnectivityStatus {
+        routers: info
+            .routers_zid()
+            .res()
+            .await
+            .map(|zid| zid.to_string())
+            .collect(),
+        peers: info
+            .peers_zid()
+            .res()
+            .await
+            .map(|zid| zid.to_string())
+            .collect(),
+    }
+}
+
+// The Publish and Notification listeners registered with `register_listener`, by their Zenoh keys.
+// The internal subscribers of the session, e.g. of the history or the recording, don't receive the messages.
+#[derive(Clone)]
+pub(crate) struct LocalListeners {
+    subscribers: SubscriberMap,
+    querying_subscribers: QueryingSubscriberMap,
+}
+
+impl LocalListeners {
+    pub(crate) fn new(
+        subscribers: SubscriberMap,
+        querying_subscribers: QueryingSubscriberMap,
+    ) -> Self {
+        LocalListeners {
+            subscribers,
+            querying_subscribers,
+        }
+    }
+
+    fn matches(&self, zenoh_key: &str) -> bool {
+        let Ok(zenoh_key) = keyexpr::new(zenoh_key) else {
+            return false;
+        };
+        let intersects = |(saved_key, _): &(String, ComparableListener)| {
+            keyexpr::new(saved_key).is_ok_and(|saved_key| zenoh_key.intersects(saved_key))
+        };
+        self.subscribers.lock().unwrap().keys().any(intersects)
+            || self
+                .querying_subscribers
+                .lock()
+                .unwrap()
+                .keys()
+                .any(intersects)
+    }
+}
+
+// Whether the message put to `zenoh_key` can be received,
+// i.e. a router or peer is connected, or a local listener matches
+pub(crate) fn has_route(
+    status: &ConnectivityStatus,
+    local_listeners: &LocalListeners,
+    zenoh_key: &str,
+) -> bool {
+    status.is_connected() || local_listeners.matches(zenoh_key)
+}
+
+// Apply the transport events to the status, and queue the resulting changes to the listeners.
+// The session is held weakly, and it stops once the subscriber of the transport events is dropped.
+async fn track_transports(
+    session: Weak<Session>,
+    mut transports: UnboundedReceiver<(SampleKind, String)>,
+    status: watch::Sender<ConnectivityStatus>,
+    listeners: ConnectivityListenerMap,
+    rate_limiter: Arc<RateLimiter>,
+) {
+    while let Some((kind, zid)) = transports.recv().await {
+        let mut next = status.borrow().clone();
+        match kind {
+            SampleKind::Put => {
+                // The event doesn't tell the router from the peer, but the session info does
+                let Some(session) = session.upgrade() else {
+                    break;
+                };
+                let is_router = session
+                    .info()
+                    .routers_zid()
+                    .res()
+                    .await
+                    .any(|id| id.to_string() == zid);
+                drop(session);
+                let ids = if is_router {
+                    &mut next.routers
+                } else {
+                    &mut next.peers
+                };
+                if !ids.contains(&zid) {
+                    ids.push(zid);
+                }
+            }
+            SampleKind::Delete => {
+                next.routers.retain(|id| *id != zid);
+                next.peers.retain(|id| *id != zid);
+            }
+        }
+        let events = connectivity_events(&status.borrow(), &next);
+        status.send_replace(next);
+        for event in events {
+            for sender in listeners.lock().unwrap().values() {
+                // The unregistered listener may still be in the map for a moment
+                let _ = sender.send((event.clone(), rate_limiter.track()));
+            }
+        }
+    }
+}
+
+impl ConnectivityMonitor {
+    pub(crate) async fn start(
+        session: &Arc<Session>,
+        rate_limiter: Arc<RateLimiter>,
+    ) -> Result<Self, TransportError> {
+        // Subscribe to the transport events before reading the status, so no change is missed
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let zid = session.info().zid().res().await;
+        let subscriber = session
+            .declare_subscriber(format!("@/session/{zid}/transport/unicast/*"))
+            .callback(move |sample: Sample| {
+                // The last chunk of the key is the Zenoh ID of the router or peer
+                if let Some(peer) = sample.key_expr.as_str().rsplit('/').next() {
+                    let _ = sender.send((sample.kind, peer.to_string()));
+                }
+            })
+            .res()
+            .await
+            .map_err(|e| TransportError::zenoh("declare subscriber", e))?;
+        let (status, _) = watch::channel(session_status(session).await);
+        let listeners = ConnectivityListenerMap::default();
+        CB_RUNTIME.spawn(track_transports(
+            Arc::downgrade(session),
+            receiver,
+            status.clone(),
+            listeners.clone(),
+            rate_limiter,
+        ));
+        Ok(ConnectivityMonitor {
+            status,
+            listeners,
+            _subscriber: subscriber,
+        })
+    }
+
+    // Follow the status, e.g. to flush the offline queue once connected
+    pub(crate) fn subscribe(&self) -> watch::Receiver<ConnectivityStatus> {
+        self.status.subscribe()
+    }
+}
+
+impl UPClientZenoh {
+    pub(crate) fn local_listeners(&self) -> LocalListeners {
+        LocalListeners::new(
+            self.subscriber_map.clone(),
+            self.querying_subscriber_map.clone(),
+        )
+    }
+
+    /// Get the routers and peers currently connected to the Zenoh session.
+    #[must_use]
+    pub fn connectivity_status(&self) -> ConnectivityStatus {
+        self.connectivity.status.borrow().clone()
+    }
+
+    /// Register `ConnectivityListener` to be notified when a router or peer connects or disconnects.
+    /// Only the changes after the registration are notified, use `connectivity_status` to get the current one.
+    ///
+    /// # Panics
+    /// Will panic if the lock is poisoned
+    pub fn register_connectivity_listener(&self, listener: Arc<dyn ConnectivityListener>) {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<(ConnectivityEvent, InFlight)>();
+        let listener_cloned = listener.clone();
+        // Deliver the events one at a time, until the listener is unregistered
+        CB_RUNTIME.spawn(async move {
+            while let Some((event, _in_flight)) = receiver.recv().await {
+                listener_cloned.on_event(event).await;
+            }
+        });
+        self.connectivity
+            .listeners
+            .lock()
+            .unwrap()
+            .insert(ComparableConnectivityListener(listener), sender);
+    }
+
+    /// Unregister `ConnectivityListener`.
+    ///
+    /// # Errors
+    /// Will return `Err` if the listener is not registered
+    ///
+    /// # Panics
+    /// Will panic if the lock is poisoned
+    pub fn unregister_connectivity_listener(
+        &self,
+        listener: Arc<dyn ConnectivityListener>,
+    ) -> Result<(), UStatus> {
+        if self
+            .connectivity
+            .listeners
+            .lock()
+            .unwrap()
+            .remove(&ComparableConnectivityListener(listener))
+            .is_none()
+        {
+            let msg = "Connectivity listener doesn't exist".to_string();
+            tracing::warn!(code = ?UCode::NOT_FOUND, "{msg}");
+            return Err(UStatus::fail_with_code(UCode::NOT_FOUND, msg));
+        }
+        Ok(())
+    }
+
+    // With the fail-fast send, fail the message if no router or peer is connected and no local listener matches
+    pub(crate) fn check_route(&self, zenoh_key: &str) -> Result<(), TransportError> {
+        if !self.send_fail_fast
+            || has_route(
+                &self.connectivity_status(),
+                &self.local_listeners(),
+                zenoh_key,
+            )
+        {
+            Ok(())
+        } else {
+            Err(TransportError::Unavailable(format!(
+                "No router or peer is connected, and nobody listens to {zenoh_key} locally"
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(routers: &[&str], peers: &[&str]) -> ConnectivityStatus {
+        ConnectivityStatus {
+            routers: routers.iter().map(ToString::to_string).collect(),
+            peers: peers.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    fn peer(id: &str, is_router: bool) -> ZenohPeer {
+        ZenohPeer {
+            id: id.to_string(),
+            is_router,
+        }
+    }
+
+    #[test]
+    fn test_connectivity_events() {
+        assert_eq!(
+            connectivity_events(&status(&[], &[]), &status(&["r1"], &[])),
+            vec![
+                ConnectivityEvent::Connected,
+                ConnectivityEvent::PeerJoined(peer("r1", true))
+            ]
+        );
+        assert_eq!(
+            connectivity_events(&status(&["r1"], &[]), &status(&["r1"], &["p1"])),
+            vec![ConnectivityEvent::PeerJoined(peer("p1", false))]
+        );
+        assert_eq!(
+            connectivity_events(&status(&["r1"], &["p1"]), &status(&["r1"], &["p1"])),
+            vec![]
+        );
+        assert_eq!(
+            connectivity_events(&status(&[], &["p1"]), &status(&[], &[])),
+            vec![
+                ConnectivityEvent::PeerLeft(peer("p1", false)),
+                ConnectivityEvent::Disconnected
+            ]
+        );
+    }
+}
@@ -0,0 +1,259 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+use crate::{
+    key_mapping::{uri_to_zenoh_key, zenoh_key_chunks_to_uri},
+    UPClientZenoh, CB_RUNTIME,
+};
+use async_trait::async_trait;
+use std::{
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+use up_rust::{ComparableListener, UCode, UListener, UStatus, UUri};
+use zenoh::prelude::{r#async::*, Sample};
+
+// The liveliness key expression covering all the authorities and RPC methods
+const LIVELINESS_KEY_ALL: &str = "up/**";
+
+/// Listener to be notified when the authorities and RPC methods reachable through Zenoh change.
+///
+/// The authority is reported as a `UUri` with only `authority_name`,
+/// and the RPC method is reported as a complete `UUri`.
+#[async_trait]
+pub trait DiscoveryListener: Send + Sync {
+    /// Called when an authority or an RPC method becomes alive.
+    async fn on_join(&self, uri: UUri);
+    /// Called when an authority or an RPC method is no longer alive.
+    async fn on_leave(&self, uri: UUri);
+}
+
+// Make DiscoveryListener able to be the key of HashMap
+pub(crate) struct ComparableDiscoveryListener(Arc<dyn DiscoveryListener>);
+impl ComparableDiscoveryListener {
+    fn address(&self) -> *const () {
+        Arc::as_ptr(&self.0).cast::<()>()
+    }
+}
+impl PartialEq for ComparableDiscoveryListener {
+    fn eq(&self, other: &Self) -> bool {
+        self.address() == other.address()
+    }
+}
+impl Eq for ComparableDiscoveryListener {}
+impl Hash for ComparableDiscoveryListener {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.address().hash(state);
+    }
+}
+
+impl UPClientZenoh {
+    // The format of liveliness key for authority should be up/[authority]
+    pub(crate) fn authority_liveliness_key(authority_name: &str) -> String {
+        format!("up/{authority_name}")
+    }
+
+    // The format of liveliness key for RPC method should be up/[authority]/[ue_id]/[ue_version_major]/[resource_id]
+    // Return None if the method contains wildcard, since it's not a specific method.
+    pub(crate) fn method_liveliness_key(&self, method: &UUri) -> Option<String> {
+        let key = format!("up/{}", uri_to_zenoh_key(&self.authority_name, method));
+        (!key.contains('*')).then_some(key)
+    }
+
+    fn liveliness_key_to_uuri(key: &str) -> Option<UUri> {
+        let chunks = key.strip_prefix("up/")?.split('/').collect::<Vec<_>>();
+        match chunks.as_slice() {
+            [authority_name] => Some(UUri {
+                authority_name: (*authority_name).to_string(),
+                ..Default::default()
+            }),
+            chunks @ [_, _, _, _] => zenoh_key_chunks_to_uri(chunks),
+            _ => None,
+        }
+    }
+
+    // Announce the RPC method served by the listener is alive
+    pub(crate) async fn declare_method_liveliness(
+        &self,
+        method: &UUri,
+        listener: Arc<dyn UListener>,
+    ) -> Result<(), UStatus> {
+        let Some(liveliness_key) = self.method_liveliness_key(method) else {
+            return Ok(());
+        };
+        let token = self
+            .session
+            .liveliness()
+            .declare_token(liveliness_key.clone())
+            .res()
+            .await
+            .map_err(|e| {
+                let msg = format!("Unable to declare Zenoh liveliness token: {e:?}");
+                tracing::error!(code = ?UCode::INTERNAL, "{msg}");
+                UStatus::fail_with_code(UCode::INTERNAL, msg)
+            })?;
+        self.liveliness_token_map
+            .lock()
+            .unwrap()
+            .insert((liveliness_key, ComparableListener::new(listener)), token);
+        Ok(())
+    }
+
+    // Announce the RPC method served by the listener is gone
+    pub(crate) fn undeclare_method_liveliness(&self, method: &UUri, listener: Arc<dyn UListener>) {
+        if let Some(liveliness_key) = self.method_liveliness_key(method) {
+            self.liveliness_token_map
+                .lock()
+                .unwrap()
+                .remove(&(liveliness_key, ComparableListener::new(listener)));
+        }
+    }
+
+    /// Get all the authorities and RPC methods which are currently alive.
+    ///
+    /// # Errors
+    /// Will return `Err` if unable to query the liveliness from Zenoh
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[tokio::main]
+    /// # async fn main() {
+    /// use up_transport_zenoh::UPClientZenoh;
+    /// let runtime = UPClientZenoh::new_loopback_runtime().await.unwrap();
+    /// let upclient = UPClientZenoh::new_with_runtime(runtime, String::from("MyAuthName"))
+    ///     .await
+    ///     .unwrap();
+    /// let entities = upclient.get_alive_entities().await.unwrap();
+    /// assert!(entities.iter().any(|uri| uri.authority_name == "MyAuthName"));
+    /// # }
+    /// ```
+    pub async fn get_alive_entities(&self) -> Result<Vec<UUri>, UStatus> {
+        let replies = self
+            .session
+            .liveliness()
+            .get(LIVELINESS_KEY_ALL)
+            .res()
+            .await
+            .map_err(|e| {
+                let msg = format!("Unable to query liveliness with Zenoh: {e:?}");
+                tracing::error!(code = ?UCode::INTERNAL, "{msg}");
+                UStatus::fail_with_code(UCode::INTERNAL, msg)
+            })?;
+        let mut entities = vec![];
+        while let Ok(reply) = replies.recv_async().await {
+            match reply.sample {
+                Ok(sample) => {
+                    if let Some(uri) =
+                        UPClientZenoh::liveliness_key_to_uuri(sample.key_expr.as_str())
+                    {
+                        entities.push(uri);
+                    } else {
+                        tracing::warn!("Unknown liveliness key: {}", sample.key_expr);
+                    }
+                }
+                Err(e) => tracing::warn!("Error while receiving Zenoh liveliness reply: {e:?}"),
+            }
+        }
+        Ok(entities)
+    }
+
+    /// Register `DiscoveryListener` to be notified when an authority or an RPC method joins or leaves.
+    /// Only the changes after the registration are notified, use `get_alive_entities` to get the current ones.
+    ///
+    /// # Errors
+    /// Will return `Err` if unable to declare the liveliness subscriber in Zenoh
+    pub async fn register_discovery_listener(
+        &self,
+        listener: Arc<dyn DiscoveryListener>,
+    ) -> Result<(), UStatus> {
+        let listener_cloned = listener.clone();
+        let rate_limiter = self.rate_limiter.clone();
+        let callback = move |sample: Sample| {
+            let Some(uri) = UPClientZenoh::liveliness_key_to_uuri(sample.key_expr.as_str()) else {
+                tracing::warn!("Unknown liveliness key: {}", sample.key_expr);
+                return;
+            };
+            let listener = listener_cloned.clone();
+            let in_flight = rate_limiter.track();
+            match sample.kind {
+                SampleKind::Put => {
+                    CB_RUNTIME.spawn(async move {
+                        let _in_flight = in_flight;
+                        listener.on_join(uri).await;
+                    });
+                }
+                SampleKind::Delete => {
+                    CB_RUNTIME.spawn(async move {
+                        let _in_flight = in_flight;
+                        listener.on_leave(uri).await;
+                    });
+                }
+            }
+        };
+
+        let subscriber = self
+            .session
+            .liveliness()
+            .declare_subscriber(LIVELINESS_KEY_ALL)
+            .callback(callback)
+            .res()
+            .await
+            .map_err(|e| {
+                let msg = format!("Unable to declare Zenoh liveliness subscriber: {e:?}");
+                tracing::error!(code = ?UCode::INTERNAL, "{msg}");
+                UStatus::fail_with_code(UCode::INTERNAL, msg)
+            })?;
+        self.discovery_subscriber_map
+            .lock()
+            .unwrap()
+            .insert(ComparableDiscoveryListener(listener), subscriber);
+        Ok(())
+    }
+
+    /// Unregister `DiscoveryListener`.
+    ///
+    /// # Errors
+    /// Will return `Err` if the listener is not registered
+    pub fn unregister_discovery_listener(
+        &self,
+        listener: Arc<dyn DiscoveryListener>,
+    ) -> Result<(), UStatus> {
+        if self
+            .discovery_subscriber_map
+            .lock()
+            .unwrap()
+            .remove(&ComparableDiscoveryListener(listener))
+            .is_none()
+        {
+            let msg = "Discovery listener doesn't exist".to_string();
+            tracing::warn!(code = ?UCode::NOT_FOUND, "{msg}");
+            return Err(UStatus::fail_with_code(UCode::NOT_FOUND, msg));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("up/vehicle1", Some(UUri { authority_name: "vehicle1".to_string(), ..Default::default() }); "Authority")]
+    #[test_case("up/vehicle1/10AB/3/B", Some(UUri { authority_name: "vehicle1".to_string(), ue_id: 0x10AB, ue_version_major: 3, resource_id: 0xB, ..Default::default() }); "RPC method")]
+    #[test_case("up/vehicle1/10AB/3", None; "Wrong number of chunks")]
+    #[test_case("up/vehicle1/XYZ/3/B", None; "Wrong ue_id")]
+    #[test_case("other/vehicle1", None; "Wrong prefix")]
+    fn test_liveliness_key_to_uuri(key: &str, expected: Option<UUri>) {
+        assert_eq!(UPClientZenoh::liveliness_key_to_uuri(key), expected);
+    }
+}
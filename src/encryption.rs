@@ -0,0 +1,203 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+//! Authenticated payload encryption of the selected topics with ChaCha20-Poly1305.
+//!
+//! Enable it with `UPClientZenohBuilder::with_encryption`. The payload of the message whose source or sink
+//! matches a configured `UUri` pattern is encrypted with the key of the pattern. The serialized `UAttributes`
+//! is the associated data, so the ciphertext can't be moved to another message. The random nonce is prepended
+//! to the ciphertext, and the key id is carried in the Zenoh attachment as the entry keyed by `kid`.
+//!
+//! The receiver decrypts the payload with the key of the carried key id before calling the listeners,
+//! so the keys can be rotated by adding the new key to all the peers before switching the pattern to it.
+//! The unencrypted message of a configured topic is rejected.
+//!
+//! If signing is also enabled, the signature covers the ciphertext.
+use crate::{access_control::uri_includes, error::TransportError};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use std::{collections::HashMap, fs, path::Path};
+use up_rust::{UAttributes, UCode, UStatus, UUri};
+use zenoh::sample::{Attachment, AttachmentBuilder};
+
+const KEY_ID_KEY: &str = "kid";
+const NONCE_LEN: usize = 12;
+
+/// The length of the ChaCha20-Poly1305 key in bytes.
+pub const KEY_LEN: usize = 32;
+
+/// The keys and the encrypted topics.
+#[derive(Clone, Default)]
+pub struct EncryptionConfig {
+    // The key of each key id
+    keys: HashMap<String, [u8; KEY_LEN]>,
+    // The UUri pattern and the key id to encrypt with, checked in order
+    topics: Vec<(UUri, String)>,
+}
+
+impl EncryptionConfig {
+    /// Create the configuration without any key and topic.
+    #[must_use]
+    pub fn new() -> Self {
+        EncryptionConfig::default()
+    }
+
+    /// Use `key` for `key_id`. All the keys are used to decrypt, no matter whether a topic encrypts with it.
+    #[must_use]
+    pub fn with_key(mut self, key_id: impl Into<String>, key: [u8; KEY_LEN]) -> Self {
+        self.keys.insert(key_id.into(), key);
+        self
+    }
+
+    /// Read the key of `key_id` from the file at `path`, which contains exactly `KEY_LEN` bytes.
+    ///
+    /// # Errors
+    /// Will return `Err` if unable to read the file or the length of the key is wrong
+    pub fn with_key_file(
+        self,
+        key_id: impl Into<String>,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, UStatus> {
+        let key = fs::read(path).map_err(|e| {
+            let msg = format!("Unable to read the encryption key file: {e:?}");
+            tracing::error!(code = ?UCode::INVALID_ARGUMENT, "{msg}");
+            UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg)
+        })?;
+        let key = <[u8; KEY_LEN]>::try_from(key.as_slice()).map_err(|_| {
+            let msg = format!(
+                "The encryption key should be {KEY_LEN} bytes, but it's {} bytes",
+                key.len()
+            );
+            tracing::error!(code = ?UCode::INVALID_ARGUMENT, "{msg}");
+            UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg)
+        })?;
+        Ok(self.with_key(key_id, key))
+    }
+
+    /// Encrypt the payload of the messages whose source or sink matches `pattern` with the key of `key_id`.
+    /// The pattern can contain wildcard, and the first matching pattern decides the key.
+    #[must_use]
+    pub fn with_topic(mut self, pattern: UUri, key_id: impl Into<String>) -> Self {
+        self.topics.push((pattern, key_id.into()));
+        self
+    }
+
+    // The key id to encrypt the message with, if the message should be encrypted
+    fn topic_key_id(&self, authority: &str, attributes: &UAttributes) -> Option<&str> {
+        let source = attributes.source.as_ref();
+        let sink = attributes.sink.as_ref();
+        self.topics
+            .iter()
+            .find(|(pattern, _)| {
+                source.map_or(false, |uri| uri_includes(authority, pattern, uri))
+                    || sink.map_or(false, |uri| uri_includes(authority, pattern, uri))
+            })
+            .map(|(_, key_id)| key_id.as_str())
+    }
+
+    fn cipher(&self, key_id: &str) -> Result<ChaCha20Poly1305, TransportError> {
+        let key = self
+            .keys
+            .get(key_id)
+            .ok_or_else(|| TransportError::Encryption(format!("No key {key_id}")))?;
+        Ok(ChaCha20Poly1305::new(Key::from_slice(key)))
+    }
+
+    // Encrypt the payload if the topic is configured. Return None if it's not.
+    pub(crate) fn encrypt(
+        &self,
+        attachment: &mut AttachmentBuilder,
+        authority: &str,
+        attributes: &UAttributes,
+        payload: &[u8],
+    ) -> Result<Option<Vec<u8>>, TransportError> {
+        let Some(key_id) = self.topic_key_id(authority, attributes) else {
+            return Ok(None);
+        };
+        let cipher = self.cipher(key_id)?;
+        let aad = protobuf::Message::write_to_bytes(attributes)
+            .map_err(|e| TransportError::AttachmentEncode(e.into()))?;
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: payload,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| TransportError::Encryption(format!("Unable to encrypt with {key_id}")))?;
+        attachment.insert(KEY_ID_KEY, key_id.as_bytes());
+        Ok(Some([nonce.as_slice(), &ciphertext].concat()))
+    }
+
+    // Decrypt the payload with the key id and the raw UAttributes in the attachment
+    fn decrypt(&self, key_id: &str, aad: &[u8], payload: &[u8]) -> Result<Vec<u8>, TransportError> {
+        let cipher = self.cipher(key_id)?;
+        if payload.len() < NONCE_LEN {
+            return Err(TransportError::Encryption(
+                "The encrypted payload is too short".to_string(),
+            ));
+        }
+        let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+        cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|_| TransportError::Encryption(format!("Unable to decrypt with {key_id}")))
+    }
+}
+
+// Return the payload to deliver to the listener, decrypted if it's encrypted.
+// The encrypted message can't be read without `config`, and the unencrypted message of a configured topic is rejected.
+pub(crate) fn decrypt_payload(
+    config: Option<&EncryptionConfig>,
+    authority: &str,
+    attachment: &Attachment,
+    attributes: &UAttributes,
+    payload: &[u8],
+) -> Result<Vec<u8>, TransportError> {
+    let mut raw_attributes = None;
+    let mut key_id = None;
+    for (index, (key, value)) in attachment.iter().enumerate() {
+        match (index, key.as_slice()) {
+            (1, _) => raw_attributes = Some(value),
+            (_, key) if key == KEY_ID_KEY.as_bytes() => key_id = Some(value),
+            _ => {}
+        }
+    }
+    match (config, key_id, raw_attributes) {
+        (_, None, _) => {
+            if let Some(key_id) = config.and_then(|c| c.topic_key_id(authority, attributes)) {
+                return Err(TransportError::Encryption(format!(
+                    "The message should be encrypted with {key_id}"
+                )));
+            }
+            Ok(payload.to_vec())
+        }
+        (Some(config), Some(key_id), Some(raw_attributes)) => config.decrypt(
+            &String::from_utf8_lossy(key_id.as_slice()),
+            raw_attributes.as_slice(),
+            payload,
+        ),
+        (_, Some(key_id), _) => Err(TransportError::Encryption(format!(
+            "No key {}",
+            String::from_utf8_lossy(key_id.as_slice())
+        ))),
+    }
+}
@@ -0,0 +1,162 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+use std::{error::Error, fmt, time::Instant};
+use up_rust::{UAttributesError, UCode, UMessageError, UStatus};
+use zenoh::value::Value;
+
+/// The error of the transport, which keeps the underlying cause.
+/// It's converted to `UStatus` with the corresponding `UCode` at the `UTransport` boundary.
+#[derive(Debug)]
+pub enum TransportError {
+    /// The `UMessage` or its `UAttributes` is invalid
+    InvalidMessage(String),
+    /// Unable to encode the `UAttributes` into the Zenoh attachment
+    AttachmentEncode(anyhow::Error),
+    /// The received Zenoh attachment can't be decoded into `UAttributes`
+    AttachmentDecode(String),
+    /// The Zenoh operation failed
+    Zenoh {
+        /// The operation being done, e.g. "put"
+        operation: &'static str,
+        /// The error returned by Zenoh
+        source: zenoh::Error,
+    },
+    /// No response listener is registered for the source of the request
+    NoResponseListener,
+    /// The request to respond is unknown, or it has been responded already
+    UnknownRequest(String),
+    /// Nobody can receive the message, e.g. no RPC server serves the method or no router is connected
+    Unavailable(String),
+    /// No response arrives before the TTL of the request
+    Timeout,
+    /// The RPC server replies with the error
+    ReplyError(String),
+    /// The operation is denied by the access control
+    PermissionDenied(String),
+    /// The signature of the received message is missing or wrong
+    InvalidSignature(String),
+    /// The payload can't be encrypted or decrypted with the configured keys
+    Encryption(String),
+    /// The received message is dropped by the rate limits
+    RateLimited(String),
+    /// `UPClientZenoh` is closed by `close`
+    Closed,
+}
+
+impl TransportError {
+    /// The `UCode` of the error when it's converted to `UStatus`.
+    #[must_use]
+    pub fn code(&self) -> UCode {
+        match self {
+            TransportError::InvalidMessage(_)
+            | TransportError::AttachmentEncode(_)
+            | TransportError::AttachmentDecode(_) => UCode::INVALID_ARGUMENT,
+            TransportError::Zenoh { .. } | TransportError::ReplyError(_) => UCode::INTERNAL,
+            TransportError::NoResponseListener | TransportError::Closed => {
+                UCode::FAILED_PRECONDITION
+            }
+            TransportError::UnknownRequest(_) => UCode::NOT_FOUND,
+            TransportError::Unavailable(_) => UCode::UNAVAILABLE,
+            TransportError::Timeout => UCode::DEADLINE_EXCEEDED,
+            TransportError::PermissionDenied(_) => UCode::PERMISSION_DENIED,
+            TransportError::RateLimited(_) => UCode::RESOURCE_EXHAUSTED,
+            TransportError::InvalidSignature(_) | TransportError::Encryption(_) => {
+                UCode::UNAUTHENTICATED
+            }
+        }
+    }
+
+    // Log the error and convert it to UStatus at the UTransport boundary
+    pub(crate) fn into_status(self) -> UStatus {
+        tracing::error!(code = ?self.code(), error = %self);
+        self.into()
+    }
+
+    pub(crate) fn zenoh(operation: &'static str, source: impl Into<zenoh::Error>) -> Self {
+        TransportError::Zenoh {
+            operation,
+            source: source.into(),
+        }
+    }
+
+    // The error replied after the query deadline is the timeout of Zenoh, not of the RPC server
+    pub(crate) fn from_reply_error(value: &Value, deadline: Instant) -> Self {
+        if Instant::now() >= deadline {
+            TransportError::Timeout
+        } else {
+            let reason = String::from_utf8_lossy(&value.payload.contiguous()).into_owned();
+            TransportError::ReplyError(reason)
+        }
+    }
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransportError::InvalidMessage(reason) => write!(f, "Invalid UMessage: {reason}"),
+            TransportError::AttachmentEncode(e) => {
+                write!(f, "Unable to transform UAttributes to attachment: {e}")
+            }
+            TransportError::AttachmentDecode(reason) => {
+                write!(f, "Unable to transform attachment to UAttributes: {reason}")
+            }
+            TransportError::Zenoh { operation, source } => {
+                write!(f, "Unable to {operation} with Zenoh: {source}")
+            }
+            TransportError::NoResponseListener => {
+                write!(f, "No response listener is registered for the request")
+            }
+            TransportError::UnknownRequest(reqid) => {
+                write!(f, "The request {reqid} doesn't exist or has been responded")
+            }
+            TransportError::Unavailable(reason) => write!(f, "Unavailable: {reason}"),
+            TransportError::Timeout => write!(f, "No response before the request TTL"),
+            TransportError::ReplyError(reason) => write!(f, "RPC server replied error: {reason}"),
+            TransportError::PermissionDenied(reason) => write!(f, "Permission denied: {reason}"),
+            TransportError::InvalidSignature(reason) => write!(f, "Invalid signature: {reason}"),
+            TransportError::Encryption(reason) => write!(f, "Payload encryption: {reason}"),
+            TransportError::RateLimited(reason) => write!(f, "Rate limited: {reason}"),
+            TransportError::Closed => write!(f, "UPClientZenoh is closed"),
+        }
+    }
+}
+
+impl Error for TransportError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TransportError::AttachmentEncode(e) => Some(e.as_ref()),
+            TransportError::Zenoh { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<TransportError> for UStatus {
+    fn from(e: TransportError) -> Self {
+        UStatus::fail_with_code(e.code(), e.to_string())
+    }
+}
+
+// RpcClient can only return UMessageError, so the UCode is kept in the message
+impl From<TransportError> for UMessageError {
+    fn from(e: TransportError) -> Self {
+        match e {
+            TransportError::InvalidMessage(_) | TransportError::AttachmentEncode(_) => {
+                UMessageError::AttributesValidationError(UAttributesError::ParsingError(
+                    e.to_string(),
+                ))
+            }
+            _ => UMessageError::PayloadError(format!("{:?}: {e}", e.code())),
+        }
+    }
+}
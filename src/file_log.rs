@@ -0,0 +1,149 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+// The file of the length-prefixed records written by a background thread,
+// so the Zenoh callbacks and `send` never block on the disk I/O.
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Read, Write},
+    path::PathBuf,
+    sync::mpsc::{self, Sender},
+    thread::{self, JoinHandle},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// The time in ms since UNIX epoch, as stored in the files
+pub(crate) fn to_timestamp(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |d| u64::try_from(d.as_millis()).unwrap_or(u64::MAX))
+}
+
+// Append the chunk with its length (u32 LE) as the prefix
+pub(crate) fn write_chunk(buf: &mut Vec<u8>, chunk: &[u8]) -> std::io::Result<()> {
+    let len =
+        u32::try_from(chunk.len()).map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?;
+    buf.extend_from_slice(&len.to_le_bytes());
+    buf.extend_from_slice(chunk);
+    Ok(())
+}
+
+// Read the chunk written by write_chunk. Return None if it reaches the end.
+pub(crate) fn read_chunk(reader: &mut impl Read) -> std::io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let mut chunk = vec![0u8; u32::from_le_bytes(len) as usize];
+    reader.read_exact(&mut chunk)?;
+    Ok(Some(chunk))
+}
+
+enum FileOp {
+    // Append the records to the end of the file
+    Append(Vec<u8>),
+    // Replace the whole file with the records
+    Rewrite(Vec<u8>),
+    // Notify once the operations before are done
+    Sync(Sender<()>),
+}
+
+pub(crate) struct FileLog {
+    sender: Option<Sender<FileOp>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+// Write into the temporary file and rename it, so a crash in the middle keeps the old file
+fn rewrite(file_path: &PathBuf, records: &[u8]) -> std::io::Result<File> {
+    let mut tmp_path = file_path.clone().into_os_string();
+    tmp_path.push(".tmp");
+    let mut tmp_file = File::create(&tmp_path)?;
+    tmp_file.write_all(records)?;
+    tmp_file.sync_all()?;
+    drop(tmp_file);
+    fs::rename(&tmp_path, file_path)?;
+    OpenOptions::new().append(true).open(file_path)
+}
+
+impl FileLog {
+    pub(crate) fn open(file_path: PathBuf) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let writer = thread::spawn(move || {
+            let mut file = None;
+            for op in receiver {
+                let result = match op {
+                    FileOp::Append(records) => {
+                        let opened = match file.take() {
+                            Some(opened) => Ok(opened),
+                            None => OpenOptions::new()
+                                .create(true)
+                                .append(true)
+                                .open(&file_path),
+                        };
+                        opened.and_then(|mut opened: File| {
+                            opened.write_all(&records)?;
+                            file = Some(opened);
+                            Ok(())
+                        })
+                    }
+                    FileOp::Rewrite(records) => rewrite(&file_path, &records).map(|opened| {
+                        file = Some(opened);
+                    }),
+                    FileOp::Sync(done) => {
+                        let _ = done.send(());
+                        Ok(())
+                    }
+                };
+                if let Err(e) = result {
+                    tracing::error!("Unable to write the file {file_path:?}: {e:?}");
+                }
+            }
+        });
+        FileLog {
+            sender: Some(sender),
+            writer: Some(writer),
+        }
+    }
+
+    pub(crate) fn append(&self, records: Vec<u8>) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(FileOp::Append(records));
+        }
+    }
+
+    pub(crate) fn rewrite(&self, records: Vec<u8>) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(FileOp::Rewrite(records));
+        }
+    }
+
+    // Block until the operations sent so far are written
+    pub(crate) fn sync(&self) {
+        if let Some(sender) = &self.sender {
+            let (done, wait) = mpsc::channel();
+            if sender.send(FileOp::Sync(done)).is_ok() {
+                let _ = wait.recv();
+            }
+        }
+    }
+}
+
+// Finish the pending writes, so the file is complete once dropped
+impl Drop for FileLog {
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
@@ -0,0 +1,290 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+use crate::{
+    file_log::{read_chunk, to_timestamp, write_chunk, FileLog},
+    utransport::InboundCheck,
+    UPClientZenoh,
+};
+use protobuf::Message;
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::{BufReader, ErrorKind, Read},
+    ops::Range,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+use up_rust::{UCode, UMessage, UStatus, UUri};
+use zenoh::{
+    prelude::{r#async::*, Sample},
+    subscriber::Subscriber,
+};
+
+// The key expression covering all the Publish and Notification messages
+const HISTORY_KEY_ALL: &str = "up/**";
+
+/// Configuration of the message history kept by `UPClientZenoh`.
+///
+/// The history records all the Publish and Notification messages reachable through Zenoh.
+/// RPC requests and responses are not recorded.
+#[derive(Clone, Debug)]
+pub struct HistoryConfig {
+    capacity: usize,
+    file_path: Option<PathBuf>,
+}
+
+impl HistoryConfig {
+    /// Keep at most `capacity` messages for each source / sink pair in memory.
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        HistoryConfig {
+            capacity,
+            file_path: None,
+        }
+    }
+
+    /// Also store the messages in the file, so the history survives the restart.
+    /// The existing messages in the file are loaded when `UPClientZenoh` is created.
+    #[must_use]
+    pub fn with_file(mut self, file_path: impl Into<PathBuf>) -> Self {
+        self.file_path = Some(file_path.into());
+        self
+    }
+}
+
+// Get the timestamp (ms since UNIX epoch) from the UUID in UAttributes.id
+fn message_timestamp(msg: &UMessage) -> u64 {
+    msg.attributes.id.msb >> 16
+}
+
+// The UUID is ordered by the timestamp and then the counter within the same millisecond
+fn message_order(msg: &UMessage) -> (u64, u64) {
+    (msg.attributes.id.msb, msg.attributes.id.lsb)
+}
+
+fn io_error_to_ustatus(e: &std::io::Error) -> UStatus {
+    let msg = format!("Unable to access the history file: {e:?}");
+    tracing::error!(code = ?UCode::INTERNAL, "{msg}");
+    UStatus::fail_with_code(UCode::INTERNAL, msg)
+}
+
+// The record in the history file: [key length (u32 LE)][key][message length (u32 LE)][message]
+fn write_record(buf: &mut Vec<u8>, key: &str, msg: &UMessage) -> std::io::Result<()> {
+    let msg_bytes = msg
+        .write_to_bytes()
+        .map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?;
+    write_chunk(buf, key.as_bytes())?;
+    write_chunk(buf, &msg_bytes)
+}
+
+fn read_record(reader: &mut impl Read) -> std::io::Result<Option<(String, UMessage)>> {
+    let Some(key) = read_chunk(reader)? else {
+        return Ok(None);
+    };
+    let Some(msg) = read_chunk(reader)? else {
+        return Err(ErrorKind::UnexpectedEof.into());
+    };
+    let key = String::from_utf8(key).map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?;
+    let msg = UMessage::parse_from_bytes(&msg)
+        .map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?;
+    Ok(Some((key, msg)))
+}
+
+struct HistoryStore {
+    capacity: usize,
+    // The messages of each Zenoh key, ordered by the timestamp
+    buffers: HashMap<String, VecDeque<UMessage>>,
+    file: Option<FileLog>,
+    // The records appended to the file since it's compacted
+    records_in_file: usize,
+}
+
+impl HistoryStore {
+    fn open(config: &HistoryConfig) -> Result<Self, UStatus> {
+        let mut store = HistoryStore {
+            capacity: config.capacity,
+            buffers: HashMap::new(),
+            file: None,
+            records_in_file: 0,
+        };
+        let Some(file_path) = &config.file_path else {
+            return Ok(store);
+        };
+
+        // Load the existing messages
+        match File::open(file_path) {
+            Ok(file) => {
+                let mut reader = BufReader::new(file);
+                loop {
+                    match read_record(&mut reader) {
+                        Ok(Some((key, msg))) => store.insert(key, msg),
+                        Ok(None) => break,
+                        Err(e) => {
+                            // Keep the messages loaded so far if the last record is truncated
+                            tracing::warn!("Stop loading the corrupted history file: {e:?}");
+                            break;
+                        }
+                    }
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(io_error_to_ustatus(&e)),
+        }
+
+        // Rewrite the file with the messages still in the ring buffers
+        store.file = Some(FileLog::open(file_path.clone()));
+        store.compact().map_err(|e| io_error_to_ustatus(&e))?;
+        Ok(store)
+    }
+
+    fn len(&self) -> usize {
+        self.buffers.values().map(VecDeque::len).sum()
+    }
+
+    // Replace the file with the messages still in the ring buffers
+    fn compact(&mut self) -> std::io::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        let mut records = vec![];
+        for (key, buffer) in &self.buffers {
+            for msg in buffer {
+                write_record(&mut records, key, msg)?;
+            }
+        }
+        file.rewrite(records);
+        self.records_in_file = self.len();
+        Ok(())
+    }
+
+    fn insert(&mut self, key: String, msg: UMessage) {
+        let buffer = self.buffers.entry(key).or_default();
+        // Messages might arrive out of order, so insert by the timestamp
+        let order = message_order(&msg);
+        let pos = buffer
+            .iter()
+            .rposition(|m| message_order(m) <= order)
+            .map_or(0, |pos| pos + 1);
+        buffer.insert(pos, msg);
+        while buffer.len() > self.capacity {
+            buffer.pop_front();
+        }
+    }
+
+    fn store(&mut self, key: String, msg: UMessage) {
+        if let Some(file) = &self.file {
+            let mut record = vec![];
+            match write_record(&mut record, &key, &msg) {
+                Ok(()) => {
+                    file.append(record);
+                    self.records_in_file += 1;
+                }
+                Err(e) => tracing::error!("Unable to write the history file: {e:?}"),
+            }
+        }
+        self.insert(key, msg);
+        // Compact once the messages dropped from the ring buffers take half of the file
+        if self.file.is_some() && self.records_in_file > 2 * self.len().max(self.capacity) {
+            if let Err(e) = self.compact() {
+                tracing::error!("Unable to compact the history file: {e:?}");
+            }
+        }
+    }
+
+    fn query(&self, filter: &keyexpr, time_range: &Range<u64>) -> Vec<UMessage> {
+        let mut messages = self
+            .buffers
+            .iter()
+            .filter(|(key, _)| keyexpr::new(key.as_str()).is_ok_and(|key| filter.intersects(key)))
+            .flat_map(|(_, buffer)| buffer.iter())
+            .filter(|msg| time_range.contains(&message_timestamp(msg)))
+            .cloned()
+            .collect::<Vec<_>>();
+        messages.sort_by_key(message_order);
+        messages
+    }
+}
+
+// Record the messages into the store until dropped
+pub(crate) struct History {
+    store: Arc<Mutex<HistoryStore>>,
+    _subscriber: Subscriber<'static, ()>,
+}
+
+impl History {
+    pub(crate) async fn new(
+        session: &Arc<Session>,
+        config: &HistoryConfig,
+        inbound_check: InboundCheck,
+    ) -> Result<Self, UStatus> {
+        let store = Arc::new(Mutex::new(HistoryStore::open(config)?));
+        let store_cloned = store.clone();
+        let callback = move |sample: Sample| match inbound_check.sample_to_umessage(&sample) {
+            Ok(msg) => store_cloned
+                .lock()
+                .unwrap()
+                .store(sample.key_expr.as_str().to_string(), msg),
+            Err(e) => tracing::warn!("Unable to record the message into history: {e}"),
+        };
+        let subscriber = session
+            .declare_subscriber(HISTORY_KEY_ALL)
+            .callback_mut(callback)
+            .res()
+            .await
+            .map_err(|e| {
+                let msg = format!("Unable to declare Zenoh subscriber for history: {e:?}");
+                tracing::error!(code = ?UCode::INTERNAL, "{msg}");
+                UStatus::fail_with_code(UCode::INTERNAL, msg)
+            })?;
+        Ok(History {
+            store,
+            _subscriber: subscriber,
+        })
+    }
+}
+
+impl UPClientZenoh {
+    /// Get the recorded Publish / Notification `UMessage`s matching the filters within the time range,
+    /// ordered by the timestamp in `UAttributes.id`. The history needs to be enabled by
+    /// `UPClientZenohBuilder::with_history`.
+    ///
+    /// # Arguments
+    ///
+    /// * `source_filter` - The source `UUri` of the messages, which can contain wildcard.
+    /// * `sink_filter` - The sink `UUri` of the messages. `None` for Publish.
+    /// * `time_range` - The messages sent within the range are returned.
+    ///
+    /// # Errors
+    /// Will return `Err` if the history is not enabled
+    pub fn get_history(
+        &self,
+        source_filter: &UUri,
+        sink_filter: Option<&UUri>,
+        time_range: Range<SystemTime>,
+    ) -> Result<Vec<UMessage>, UStatus> {
+        let Some(history) = &self.history else {
+            let msg = "History is not enabled".to_string();
+            tracing::error!(code = ?UCode::FAILED_PRECONDITION, "{msg}");
+            return Err(UStatus::fail_with_code(UCode::FAILED_PRECONDITION, msg));
+        };
+        let zenoh_key = self.to_zenoh_key_string(source_filter, sink_filter);
+        let filter = keyexpr::new(&zenoh_key).map_err(|e| {
+            let msg = format!("Invalid filter: {e:?}");
+            tracing::error!(code = ?UCode::INVALID_ARGUMENT, "{msg}");
+            UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg)
+        })?;
+        let time_range = to_timestamp(time_range.start)..to_timestamp(time_range.end);
+        Ok(history.store.lock().unwrap().query(filter, &time_range))
+    }
+}
@@ -0,0 +1,278 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+//! Mapping between uProtocol `UUri` and Zenoh key expression defined in
+//! [up-l1/zenoh](https://github.com/eclipse-uprotocol/up-spec/blob/main/up-l1/zenoh.adoc).
+//!
+//! The mapping doesn't need a Zenoh session, so bridges, routers and tools can reuse it.
+//!
+//! ```
+//! use std::str::FromStr;
+//! use up_rust::UUri;
+//! use up_transport_zenoh::key_mapping::{to_zenoh_key_string, zenoh_key_to_uuris};
+//! let src = UUri::from_str("//my-host1/10AB/3/0").unwrap();
+//! let sink = UUri::from_str("//my-host2/20EF/4/B").unwrap();
+//! let key = to_zenoh_key_string("my-host1", &src, Some(&sink));
+//! assert_eq!(key, "up/my-host1/10AB/3/0/my-host2/20EF/4/B");
+//! assert_eq!(zenoh_key_to_uuris(&key).unwrap(), (src, Some(sink)));
+//! ```
+use bitmask_enum::bitmask;
+use up_rust::{UCode, UStatus, UUri};
+
+/// The wildcard of `UUri.ue_id`
+pub const WILDCARD_ENTITY_ID: u32 = 0x0000_FFFF;
+/// The wildcard of `UUri.ue_version_major`
+pub const WILDCARD_ENTITY_VERSION: u32 = 0x0000_00FF;
+/// The wildcard of `UUri.resource_id`
+pub const WILDCARD_RESOURCE_ID: u32 = 0x0000_FFFF;
+
+/// The types of uProtocol messages, which can be combined as the bitmask.
+#[bitmask(u8)]
+pub enum MessageFlag {
+    Publish,
+    Notification,
+    Request,
+    Response,
+}
+
+/// Map `UUri` to the 4 chunks of Zenoh key: `[authority]/[ue_id]/[ue_version_major]/[resource_id]`.
+/// The wildcard is mapped to `*`, and `authority_name` replaces the omitted authority in `uri`.
+#[must_use]
+pub fn uri_to_zenoh_key(authority_name: &str, uri: &UUri) -> String {
+    // authority_name
+    let authority = if uri.authority_name.is_empty() {
+        authority_name
+    } else {
+        &uri.authority_name
+    };
+    // ue_id
+    let ue_id = if uri.ue_id == WILDCARD_ENTITY_ID {
+        "*".to_string()
+    } else {
+        format!("{:X}", uri.ue_id)
+    };
+    // ue_version_major
+    let ue_version_major = if uri.ue_version_major == WILDCARD_ENTITY_VERSION {
+        "*".to_string()
+    } else {
+        format!("{:X}", uri.ue_version_major)
+    };
+    // resource_id
+    let resource_id = if uri.resource_id == WILDCARD_RESOURCE_ID {
+        "*".to_string()
+    } else {
+        format!("{:X}", uri.resource_id)
+    };
+    format!("{authority}/{ue_id}/{ue_version_major}/{resource_id}")
+}
+
+/// Map the source and sink `UUri` to the Zenoh key expression, which should be
+/// `up/[src.authority]/[src.ue_id]/[src.ue_version_major]/[src.resource_id]/[sink.authority]/[sink.ue_id]/[sink.ue_version_major]/[sink.resource_id]`.
+/// The sink chunks are `{}/{}/{}/{}` if `dst_uri` is `None`, and `authority_name` replaces the omitted authority in `UUri`.
+#[must_use]
+pub fn to_zenoh_key_string(authority_name: &str, src_uri: &UUri, dst_uri: Option<&UUri>) -> String {
+    let src = uri_to_zenoh_key(authority_name, src_uri);
+    let dst = if let Some(dst) = dst_uri {
+        uri_to_zenoh_key(authority_name, dst)
+    } else {
+        "{}/{}/{}/{}".to_string()
+    };
+    format!("up/{src}/{dst}")
+}
+
+// The inverse of `uri_to_zenoh_key` with the key split into 4 chunks. `*` is mapped back to the wildcard.
+pub(crate) fn zenoh_key_chunks_to_uri(chunks: &[&str]) -> Option<UUri> {
+    let parse = |chunk: &str, wildcard: u32| {
+        if chunk == "*" {
+            Some(wildcard)
+        } else {
+            u32::from_str_radix(chunk, 16).ok()
+        }
+    };
+    let [authority_name, ue_id, ue_version_major, resource_id] = chunks else {
+        return None;
+    };
+    Some(UUri {
+        authority_name: (*authority_name).to_string(),
+        ue_id: parse(ue_id, WILDCARD_ENTITY_ID)?,
+        ue_version_major: parse(ue_version_major, WILDCARD_ENTITY_VERSION)?,
+        resource_id: parse(resource_id, WILDCARD_RESOURCE_ID)?,
+        ..Default::default()
+    })
+}
+
+/// Map the Zenoh key back to the source and sink `UUri`, which is the inverse of `to_zenoh_key_string`.
+/// The sink is `None` if the key is for Publish, and `*` is mapped back to the wildcard.
+///
+/// # Errors
+/// Will return `Err` if the key is not in the `up/` key space
+pub fn zenoh_key_to_uuris(zenoh_key: &str) -> Result<(UUri, Option<UUri>), UStatus> {
+    let invalid_key = || {
+        let msg = format!("Invalid uProtocol Zenoh key: {zenoh_key}");
+        tracing::warn!(code = ?UCode::INVALID_ARGUMENT, "{msg}");
+        UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg)
+    };
+    let chunks = zenoh_key
+        .strip_prefix("up/")
+        .ok_or_else(invalid_key)?
+        .split('/')
+        .collect::<Vec<_>>();
+    if chunks.len() != 8 {
+        return Err(invalid_key());
+    }
+    let (src_chunks, sink_chunks) = chunks.split_at(4);
+    let src = zenoh_key_chunks_to_uri(src_chunks).ok_or_else(invalid_key)?;
+    if sink_chunks.iter().all(|chunk| *chunk == "{}") {
+        return Ok((src, None));
+    }
+    let sink = zenoh_key_chunks_to_uri(sink_chunks).ok_or_else(invalid_key)?;
+    Ok((src, Some(sink)))
+}
+
+/*        The table for mapping resource ID to message type
+ *
+ *  |   src rid   | sink rid | Publish | Notification | Request | Response |
+ *  |-------------|----------|---------|--------------|---------|----------|
+ *  | [8000-FFFF) |   None   |    V    |              |         |          |
+ *  | [8000-FFFF) |     0    |         |      V       |         |          |
+ *  |      0      | (0-8000) |         |              |    V    |          |
+ *  |   (0-8000)  |     0    |         |              |         |    V     |
+ *  |     FFFF    |     0    |         |      V       |         |    V     |
+ *  |     FFFF    | (0-8000) |         |              |    V    |          |
+ *  |      0      |   FFFF   |         |              |    V    |          |
+ *  |   (0-8000)  |   FFFF   |         |              |         |    V     |
+ *  | [8000-FFFF) |   FFFF   |         |      V       |         |          |
+ *  |     FFFF    |   FFFF   |         |      V       |    V    |    V     |
+ *
+ *  Some organization:
+ *  - Publish: {[8000-FFFF), None}
+ *  - Notification: {[8000-FFFF), 0}, {[8000-FFFF), FFFF]}, {FFFF, 0}, {FFFF, FFFF}
+ *  - Request: {0, (0-8000)}, {0, FFFF}, {FFFF, (0-8000)}, {FFFF, FFFF}
+ *  - Response: {(0-8000), 0}, {(0-8000), FFFF}, (FFFF, 0), {FFFF, FFFF}
+ */
+/// Classify the types of messages the listener registered with `source_uuri` and `sink_uuri` receives.
+///
+/// # Errors
+/// Will return `Err` if no message type matches the combination of source and sink
+#[allow(clippy::nonminimal_bool)] // Don't simplify the boolean expression for better understanding
+pub fn get_listener_message_type(
+    source_uuri: &UUri,
+    sink_uuri: Option<&UUri>,
+) -> Result<MessageFlag, UStatus> {
+    let mut flag = MessageFlag::none();
+    let rpc_range = 1..0x7FFF_u32;
+    let nonrpc_range = 0x8000..0xFFFE_u32;
+
+    let src_resource = source_uuri.resource_id;
+    // Notification / Request / Response
+    if let Some(dst_uuri) = sink_uuri {
+        let dst_resource = dst_uuri.resource_id;
+
+        if (nonrpc_range.contains(&src_resource) && dst_resource == 0)
+            || (nonrpc_range.contains(&src_resource) && dst_resource == 0xFFFF)
+            || (src_resource == 0xFFFF && dst_resource == 0)
+            || (src_resource == 0xFFFF && dst_resource == 0xFFFF)
+        {
+            flag |= MessageFlag::Notification;
+        }
+        if (src_resource == 0 && rpc_range.contains(&dst_resource))
+            || (src_resource == 0 && dst_resource == 0xFFFF)
+            || (src_resource == 0xFFFF && rpc_range.contains(&dst_resource))
+            || (src_resource == 0xFFFF && dst_resource == 0xFFFF)
+        {
+            flag |= MessageFlag::Request;
+        }
+        if (rpc_range.contains(&src_resource) && dst_resource == 0)
+            || (rpc_range.contains(&src_resource) && dst_resource == 0xFFFF)
+            || (src_resource == 0xFFFF && dst_resource == 0)
+            || (src_resource == 0xFFFF && dst_resource == 0xFFFF)
+        {
+            flag |= MessageFlag::Response;
+        }
+    } else if nonrpc_range.contains(&src_resource) || src_resource == 0xFFFF {
+        flag |= MessageFlag::Publish;
+    }
+    if flag.is_none() {
+        Err(UStatus::fail_with_code(
+            UCode::INTERNAL,
+            "Wrong combination of source UUri and sink UUri",
+        ))
+    } else {
+        Ok(flag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use test_case::test_case;
+
+    // Mapping with the examples in Zenoh spec
+    #[test_case("/10AB/3/80CD", None, "up/192.168.1.100/10AB/3/80CD/{}/{}/{}/{}"; "Send Publish")]
+    #[test_case("//192.168.1.100/10AB/3/80CD", None, "up/192.168.1.100/10AB/3/80CD/{}/{}/{}/{}"; "Subscribe messages")]
+    #[test_case("//192.168.1.100/10AB/3/80CD", Some("//192.168.1.101/20EF/4/0"), "up/192.168.1.100/10AB/3/80CD/192.168.1.101/20EF/4/0"; "Send Notification")]
+    #[test_case("//*/FFFF/FF/FFFF", Some("//192.168.1.101/20EF/4/0"), "up/*/*/*/*/192.168.1.101/20EF/4/0"; "Receive all Notifications")]
+    #[test_case("//my-host1/10AB/3/0", Some("//my-host2/20EF/4/B"), "up/my-host1/10AB/3/0/my-host2/20EF/4/B"; "Send Request")]
+    #[test_case("//*/FFFF/FF/FFFF", Some("//my-host2/20EF/4/B"), "up/*/*/*/*/my-host2/20EF/4/B"; "Receive all Requests")]
+    #[test_case("//*/FFFF/FF/FFFF", Some("//[::1]/FFFF/FF/FFFF"), "up/*/*/*/*/[::1]/*/*/*"; "Receive all messages to a device")]
+    fn test_to_zenoh_key_string(src_uri: &str, sink_uri: Option<&str>, zenoh_key: &str) {
+        let src = UUri::from_str(src_uri).unwrap();
+        let sink = sink_uri.map(|sink| UUri::from_str(sink).unwrap());
+        assert_eq!(
+            to_zenoh_key_string("192.168.1.100", &src, sink.as_ref()),
+            zenoh_key.to_string()
+        );
+    }
+
+    #[test_case("up/192.168.1.100/10AB/3/80CD/{}/{}/{}/{}", Some(("//192.168.1.100/10AB/3/80CD", None)); "Publish")]
+    #[test_case("up/192.168.1.100/10AB/3/80CD/192.168.1.101/20EF/4/0", Some(("//192.168.1.100/10AB/3/80CD", Some("//192.168.1.101/20EF/4/0"))); "Notification")]
+    #[test_case("up/my-host1/10AB/3/0/my-host2/20EF/4/B", Some(("//my-host1/10AB/3/0", Some("//my-host2/20EF/4/B"))); "Request")]
+    #[test_case("up/*/*/*/*/[::1]/*/*/*", Some(("//*/FFFF/FF/FFFF", Some("//[::1]/FFFF/FF/FFFF"))); "Wildcard")]
+    #[test_case("up/my-host1/10AB/3/0", None; "Wrong number of chunks")]
+    #[test_case("up/my-host1/XYZ/3/0/my-host2/20EF/4/B", None; "Wrong ue_id")]
+    #[test_case("other/my-host1/10AB/3/0/my-host2/20EF/4/B", None; "Wrong prefix")]
+    fn test_zenoh_key_to_uuris(zenoh_key: &str, expected: Option<(&str, Option<&str>)>) {
+        let expected = expected.map(|(src, sink)| {
+            (
+                UUri::from_str(src).unwrap(),
+                sink.map(|sink| UUri::from_str(sink).unwrap()),
+            )
+        });
+        assert_eq!(zenoh_key_to_uuris(zenoh_key).ok(), expected);
+    }
+
+    #[test_case("//192.168.1.100/10AB/3/80CD", None, Ok(MessageFlag::Publish); "Publish Message")]
+    #[test_case("//192.168.1.100/10AB/3/80CD", Some("//192.168.1.101/20EF/4/0"), Ok(MessageFlag::Notification); "Notification Message")]
+    #[test_case("//192.168.1.100/10AB/3/0", Some("//192.168.1.101/20EF/4/B"), Ok(MessageFlag::Request); "Request Message")]
+    #[test_case("//192.168.1.101/20EF/4/B", Some("//192.168.1.100/10AB/3/0"), Ok(MessageFlag::Response); "Response Message")]
+    #[test_case("//*/FFFF/FF/FFFF", Some("//192.168.1.100/10AB/3/0"), Ok(MessageFlag::Notification | MessageFlag::Response); "Listen to Notification and Response Message")]
+    #[test_case("//*/FFFF/FF/FFFF", Some("//192.168.1.101/20EF/4/B"), Ok(MessageFlag::Request); "Listen to Request Message")]
+    #[test_case("//192.168.1.100/10AB/3/0", Some("//*/FFFF/FF/FFFF"), Ok(MessageFlag::Request); "Broadcast Request Message")]
+    #[test_case("//192.168.1.101/20EF/4/B", Some("//*/FFFF/FF/FFFF"), Ok(MessageFlag::Response); "Broadcast Response Message")]
+    #[test_case("//192.168.1.100/10AB/3/80CD", Some("//*/FFFF/FF/FFFF"), Ok(MessageFlag::Notification); "Broadcast Notification Message")]
+    #[test_case("//*/FFFF/FF/FFFF", Some("//[::1]/FFFF/FF/FFFF"), Ok(MessageFlag::Notification | MessageFlag::Request | MessageFlag::Response); "All messages to a device")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_get_listener_message_type(
+        src_uri: &str,
+        sink_uri: Option<&str>,
+        result: Result<MessageFlag, UStatus>,
+    ) {
+        let src = UUri::from_str(src_uri).unwrap();
+        if let Some(uri) = sink_uri {
+            let dst = UUri::from_str(uri).unwrap();
+            assert_eq!(get_listener_message_type(&src, Some(&dst)), result);
+        } else {
+            assert_eq!(get_listener_message_type(&src, None), result);
+        }
+    }
+}
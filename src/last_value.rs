@@ -0,0 +1,178 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+use crate::{
+    access_control::Operation,
+    error::TransportError,
+    key_mapping::{get_listener_message_type, MessageFlag},
+    UPClientZenoh, CB_RUNTIME,
+};
+use std::sync::Arc;
+use up_rust::{ComparableListener, UCode, UListener, UMessage, UMessageType, UStatus, UUri};
+use zenoh::prelude::{r#async::*, Sample};
+use zenoh_ext::{SessionExt, SubscriberBuilderExt};
+
+impl UPClientZenoh {
+    /// Keep the latest Publish `UMessage` sent to `topic` and answer the queries for it,
+    /// so the late joiners can get the value without waiting for the next publish.
+    /// The latest message is kept for each source `UUri` if `topic` contains wildcard.
+    ///
+    /// # Arguments
+    ///
+    /// * `topic` - The source `UUri` of the Publish messages to cache.
+    ///
+    /// # Errors
+    /// Will return `Err` if unable to declare the publication cache in Zenoh
+    pub async fn enable_last_value_cache(&self, topic: &UUri) -> Result<(), UStatus> {
+        let zenoh_key = self.to_zenoh_key_string(topic, None);
+        let publication_cache = self
+            .session
+            .declare_publication_cache(zenoh_key.clone())
+            .history(1)
+            .res()
+            .await
+            .map_err(|e| {
+                let msg = format!("Unable to declare Zenoh publication cache: {e:?}");
+                tracing::error!(code = ?UCode::INTERNAL, "{msg}");
+                UStatus::fail_with_code(UCode::INTERNAL, msg)
+            })?;
+        self.publication_cache_map
+            .lock()
+            .unwrap()
+            .insert(zenoh_key, publication_cache);
+        Ok(())
+    }
+
+    /// Stop caching the latest Publish `UMessage` sent to `topic`.
+    ///
+    /// # Errors
+    /// Will return `Err` if the last-value cache of `topic` is not enabled
+    pub fn disable_last_value_cache(&self, topic: &UUri) -> Result<(), UStatus> {
+        let zenoh_key = self.to_zenoh_key_string(topic, None);
+        if self
+            .publication_cache_map
+            .lock()
+            .unwrap()
+            .remove(&zenoh_key)
+            .is_none()
+        {
+            let msg = "Last-value cache doesn't exist".to_string();
+            tracing::warn!(code = ?UCode::NOT_FOUND, "{msg}");
+            return Err(UStatus::fail_with_code(UCode::NOT_FOUND, msg));
+        }
+        Ok(())
+    }
+
+    /// Get the cached latest Publish `UMessage`s matching `source_filter`, one for each source `UUri`.
+    /// Only the topics with the last-value cache enabled by their publishers are answered.
+    ///
+    /// # Errors
+    /// Will return `Err` if unable to query Zenoh
+    pub async fn get_last_values(&self, source_filter: &UUri) -> Result<Vec<UMessage>, UStatus> {
+        let zenoh_key = self.to_zenoh_key_string(source_filter, None);
+        let replies = self
+            .session
+            .get(zenoh_key)
+            .target(QueryTarget::All)
+            .res()
+            .await
+            .map_err(|e| {
+                let msg = format!("Unable to send get with Zenoh: {e:?}");
+                tracing::error!(code = ?UCode::INTERNAL, "{msg}");
+                UStatus::fail_with_code(UCode::INTERNAL, msg)
+            })?;
+        let inbound_check = self.inbound_check();
+        let mut messages = vec![];
+        while let Ok(reply) = replies.recv_async().await {
+            match reply.sample {
+                Ok(sample) => match inbound_check.sample_to_umessage(&sample) {
+                    Ok(msg) => messages.push(msg),
+                    Err(e) => tracing::warn!(code = ?e.code(), error = %e, "Drop the last value"),
+                },
+                Err(e) => tracing::warn!("Error while receiving Zenoh reply: {e:?}"),
+            }
+        }
+        Ok(messages)
+    }
+
+    /// Register the Publish listener like `register_listener`, and also deliver the cached latest
+    /// `UMessage`s matching `source_filter` to the listener right after the registration.
+    /// Use `unregister_listener` to unregister it.
+    ///
+    /// # Errors
+    /// Will return `Err` if `source_filter` is not for Publish, the registration is not authorized,
+    /// or unable to declare the subscriber in Zenoh
+    pub async fn register_listener_with_last_value(
+        &self,
+        source_filter: &UUri,
+        listener: Arc<dyn UListener>,
+    ) -> Result<(), UStatus> {
+        let flag = get_listener_message_type(source_filter, None)?;
+        if !flag.contains(MessageFlag::Publish) {
+            let msg = "The last value is only available for Publish".to_string();
+            tracing::error!(code = ?UCode::INVALID_ARGUMENT, "{msg}");
+            return Err(UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg));
+        }
+        self.authorize(
+            Operation::RegisterListener,
+            UMessageType::UMESSAGE_TYPE_PUBLISH,
+            source_filter,
+            None,
+        )
+        .map_err(TransportError::into_status)?;
+
+        // Setup callback
+        let listener_cloned = listener.clone();
+        let inbound_check = self.inbound_check();
+        let rate_limiter = self.rate_limiter.clone();
+        let callback = move |sample: Sample| {
+            let listener = listener_cloned.clone();
+            let in_flight = rate_limiter.track();
+            match inbound_check.sample_to_umessage(&sample) {
+                Ok(msg) => {
+                    CB_RUNTIME.spawn(async move {
+                        let _in_flight = in_flight;
+                        listener.on_receive(msg).await;
+                    });
+                }
+                Err(e) => {
+                    tracing::error!(code = ?e.code(), error = %e, "Unable to receive the message");
+                    CB_RUNTIME.spawn(async move {
+                        let _in_flight = in_flight;
+                        listener.on_error(UStatus::from(e)).await;
+                    });
+                }
+            }
+        };
+
+        // Create Zenoh querying subscriber, which fetches the cached values once declared
+        let zenoh_key = self.to_zenoh_key_string(source_filter, None);
+        let subscriber = self
+            .session
+            .declare_subscriber(zenoh_key.clone())
+            .querying()
+            .query_target(QueryTarget::All)
+            .callback(callback)
+            .res()
+            .await
+            .map_err(|e| {
+                let msg = format!("Unable to declare Zenoh querying subscriber: {e:?}");
+                tracing::error!(code = ?UCode::INTERNAL, "{msg}");
+                UStatus::fail_with_code(UCode::INTERNAL, msg)
+            })?;
+        self.querying_subscriber_map
+            .lock()
+            .unwrap()
+            .insert((zenoh_key, ComparableListener::new(listener)), subscriber);
+        Ok(())
+    }
+}
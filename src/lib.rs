@@ -10,32 +10,70 @@
  *
  * SPDX-License-Identifier: Apache-2.0
  ********************************************************************************/
+pub mod access_control;
+pub mod builder;
+#[cfg(test)]
+mod conformance;
+pub mod connectivity;
+pub mod discovery;
+pub mod encryption;
+pub mod error;
+mod file_log;
+pub mod history;
+pub mod key_mapping;
+pub mod last_value;
+pub mod matching;
+mod metrics;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod offline_queue;
+pub mod rate_limit;
+pub mod record;
 pub mod rpc;
+mod shutdown;
+pub mod signing;
+pub mod sniffer;
+pub mod streamer;
+pub mod tls;
+mod trace_context;
 pub mod utransport;
 
-use bitmask_enum::bitmask;
+use access_control::AccessControl;
+pub use builder::{AuthorityPolicy, UPClientZenohBuilder};
+use connectivity::ConnectivityMonitor;
+use discovery::ComparableDiscoveryListener;
+use encryption::EncryptionConfig;
+pub use error::TransportError;
+use history::History;
+use metrics::Metrics;
+use offline_queue::OfflineQueue;
 use protobuf::Message;
+use rate_limit::RateLimiter;
+use signing::SigningConfig;
 use std::{
     collections::HashMap,
-    sync::{Arc, Mutex},
+    sync::{atomic::AtomicBool, Arc, Mutex},
 };
+use tls::PeerCertificateCheck;
 use tokio::runtime::Runtime;
-use up_rust::{ComparableListener, UAttributes, UCode, UListener, UPriority, UStatus, UUri};
+use up_rust::{
+    ComparableListener, UAttributes, UCode, UListener, UMessageType, UPriority, UStatus, UUri,
+};
 // Re-export Zenoh config
 pub use zenoh::config::Config;
 use zenoh::{
+    config::EndPoint,
+    liveliness::LivelinessToken,
     prelude::r#async::*,
     queryable::{Query, Queryable},
     runtime::Runtime as ZRuntime,
     sample::{Attachment, AttachmentBuilder},
     subscriber::Subscriber,
 };
+use zenoh_ext::{FetchingSubscriber, PublicationCache};
 
-// CY_TODO: Whether to expose from up_rust or not
-const _WILDCARD_AUTHORITY: &str = "*";
-const WILDCARD_ENTITY_ID: u32 = 0x0000_FFFF;
-const WILDCARD_ENTITY_VERSION: u32 = 0x0000_00FF;
-const WILDCARD_RESOURCE_ID: u32 = 0x0000_FFFF;
+// The only endpoint listened by the loopback runtime
+const LOOPBACK_ENDPOINT: &str = "tcp/127.0.0.1:0";
 
 const _RESOURCE_ID_RESPONSE: u32 = 0;
 const _RESOURCE_ID_MIN_EVENT: u32 = 0x8000;
@@ -52,18 +90,17 @@ lazy_static::lazy_static! {
                .expect("Unable to create callback runtime");
 }
 
-#[bitmask(u8)]
-enum MessageFlag {
-    Publish,
-    Notification,
-    Request,
-    Response,
-}
-
 type SubscriberMap = Arc<Mutex<HashMap<(String, ComparableListener), Subscriber<'static, ()>>>>;
 type QueryableMap = Arc<Mutex<HashMap<(String, ComparableListener), Queryable<'static, ()>>>>;
 type QueryMap = Arc<Mutex<HashMap<String, Query>>>;
 type RpcCallbackMap = Arc<Mutex<HashMap<OwnedKeyExpr, Arc<dyn UListener>>>>;
+type LivelinessTokenMap =
+    Arc<Mutex<HashMap<(String, ComparableListener), LivelinessToken<'static>>>>;
+type DiscoverySubscriberMap =
+    Arc<Mutex<HashMap<ComparableDiscoveryListener, Subscriber<'static, ()>>>>;
+type PublicationCacheMap = Arc<Mutex<HashMap<String, PublicationCache<'static>>>>;
+type QueryingSubscriberMap =
+    Arc<Mutex<HashMap<(String, ComparableListener), FetchingSubscriber<'static, ()>>>>;
 pub struct UPClientZenoh {
     session: Arc<Session>,
     // Able to unregister Subscriber
@@ -74,8 +111,46 @@ pub struct UPClientZenoh {
     query_map: QueryMap,
     // Save the callback for RPC response
     rpc_callback_map: RpcCallbackMap,
+    // Able to undeclare the liveliness token of RPC request listener
+    liveliness_token_map: LivelinessTokenMap,
+    // Able to unregister DiscoveryListener
+    discovery_subscriber_map: DiscoverySubscriberMap,
+    // Able to disable the last-value cache
+    publication_cache_map: PublicationCacheMap,
+    // Able to unregister the Publish listener with last value
+    querying_subscriber_map: QueryingSubscriberMap,
+    // Record the messages if the history is enabled
+    history: Option<History>,
+    // No-op without the metrics feature
+    metrics: Arc<Metrics>,
+    // Keep my authority alive until UPClientZenoh is dropped
+    _authority_token: LivelinessToken<'static>,
+    // Fail the RPC request immediately if nobody serves the method
+    rpc_fail_fast: bool,
+    // Allow everything if None
+    access_control: Option<Arc<dyn AccessControl>>,
+    // How to handle the source authority differing from mine
+    authority_policy: AuthorityPolicy,
+    // Sign and verify the messages if enabled
+    signing: Option<Arc<SigningConfig>>,
+    // Encrypt and decrypt the payload of the configured topics if enabled
+    encryption: Option<Arc<EncryptionConfig>>,
+    // Bind the certificates to the source authorities of the messages if enabled
+    peer_certificates: Option<Arc<PeerCertificateCheck>>,
+    // Admit the received messages before spawning the listener callbacks
+    rate_limiter: Arc<RateLimiter>,
+    // Notify the connectivity listeners
+    connectivity: ConnectivityMonitor,
+    // Fail Publish and Notification immediately if nobody can receive them
+    send_fail_fast: bool,
+    // Hold Publish and Notification while offline if enabled
+    offline_queue: Option<OfflineQueue>,
     // My authority
     authority_name: String,
+    // Refuse to send or register listeners once closed, also for the other holders of UPClientZenoh
+    closed: AtomicBool,
+    // The session on the runtime given by `with_runtime` shares it, so closing the session would close it
+    shared_runtime: bool,
 }
 
 impl UPClientZenoh {
@@ -101,24 +176,13 @@ impl UPClientZenoh {
     /// # }
     /// ```
     pub async fn new(config: Config, authority_name: String) -> Result<UPClientZenoh, UStatus> {
-        // Create Zenoh session
-        let Ok(session) = zenoh::open(config).res().await else {
-            let msg = "Unable to open Zenoh session".to_string();
-            log::error!("{msg}");
-            return Err(UStatus::fail_with_code(UCode::INTERNAL, msg));
-        };
-        // Return UPClientZenoh
-        Ok(UPClientZenoh {
-            session: Arc::new(session),
-            subscriber_map: Arc::new(Mutex::new(HashMap::new())),
-            queryable_map: Arc::new(Mutex::new(HashMap::new())),
-            query_map: Arc::new(Mutex::new(HashMap::new())),
-            rpc_callback_map: Arc::new(Mutex::new(HashMap::new())),
-            authority_name,
-        })
+        UPClientZenoh::builder(authority_name)
+            .with_config(config)
+            .build()
+            .await
     }
 
-    /// Create `UPClientZenoh` by applying the Zenoh Runtime and `UAuthority`. This can be used by uStreamer.
+    /// Create `UPClientZenoh` by applying the Zenoh Runtime and `UAuthority`. This can be used by uStreamer, see `streamer::UStreamer`.
     ///
     /// # Arguments
     ///
@@ -131,60 +195,135 @@ impl UPClientZenoh {
         runtime: ZRuntime,
         authority_name: String,
     ) -> Result<UPClientZenoh, UStatus> {
-        let Ok(session) = zenoh::init(runtime).res().await else {
-            let msg = "Unable to open Zenoh session".to_string();
-            log::error!("{msg}");
+        UPClientZenoh::builder(authority_name)
+            .with_runtime(runtime)
+            .build()
+            .await
+    }
+
+    /// Create `UPClientZenohBuilder` to configure `UPClientZenoh` with the options beyond `new`.
+    ///
+    /// # Arguments
+    ///
+    /// * `authority_name` - The authority name. We need it to generate Zenoh key since authority might be omitted in `UUri`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[tokio::main]
+    /// # async fn main() {
+    /// use up_transport_zenoh::{Config, UPClientZenoh};
+    /// let upclient = UPClientZenoh::builder("MyAuthName")
+    ///     .with_config(Config::default())
+    ///     .with_rpc_fail_fast(true)
+    ///     .build()
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn builder(authority_name: impl Into<String>) -> UPClientZenohBuilder {
+        UPClientZenohBuilder::new(authority_name.into())
+    }
+
+    /// Create a Zenoh Runtime which isn't reachable from the network.
+    /// Scouting is disabled and no endpoint is connected, so all the `UPClientZenoh`
+    /// created on this runtime with `new_with_runtime` talk to each other in-process.
+    /// Zenoh listens on all the interfaces in the peer mode without any endpoint to listen,
+    /// so it only listens on a random port of the loopback interface instead.
+    /// Since the routing happens locally, a listener is active as soon as `register_listener` returns.
+    ///
+    /// # Errors
+    /// Will return `Err` if unable to create the Zenoh Runtime
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[tokio::main]
+    /// # async fn main() {
+    /// use up_transport_zenoh::UPClientZenoh;
+    /// let runtime = UPClientZenoh::new_loopback_runtime().await.unwrap();
+    /// let upclient1 = UPClientZenoh::new_with_runtime(runtime.clone(), String::from("MyAuthName1"))
+    ///     .await
+    ///     .unwrap();
+    /// let upclient2 = UPClientZenoh::new_with_runtime(runtime, String::from("MyAuthName2"))
+    ///     .await
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub async fn new_loopback_runtime() -> Result<ZRuntime, UStatus> {
+        let mut config = Config::default();
+        if config.scouting.multicast.set_enabled(Some(false)).is_err()
+            || config.scouting.gossip.set_enabled(Some(false)).is_err()
+        {
+            let msg = "Unable to disable scouting in Zenoh config".to_string();
+            tracing::error!(code = ?UCode::INTERNAL, "{msg}");
+            return Err(UStatus::fail_with_code(UCode::INTERNAL, msg));
+        }
+        let Ok(endpoint) = LOOPBACK_ENDPOINT.parse::<EndPoint>() else {
+            let msg = format!("Unable to parse the endpoint {LOOPBACK_ENDPOINT}");
+            tracing::error!(code = ?UCode::INTERNAL, "{msg}");
             return Err(UStatus::fail_with_code(UCode::INTERNAL, msg));
         };
-        // Return UPClientZenoh
-        Ok(UPClientZenoh {
-            session: Arc::new(session),
-            subscriber_map: Arc::new(Mutex::new(HashMap::new())),
-            queryable_map: Arc::new(Mutex::new(HashMap::new())),
-            query_map: Arc::new(Mutex::new(HashMap::new())),
-            rpc_callback_map: Arc::new(Mutex::new(HashMap::new())),
-            authority_name,
+        config.listen.endpoints = vec![endpoint];
+        config.connect.endpoints = vec![];
+        ZRuntime::new(config).await.map_err(|e| {
+            let msg = format!("Unable to create Zenoh runtime: {e:?}");
+            tracing::error!(code = ?UCode::INTERNAL, "{msg}");
+            UStatus::fail_with_code(UCode::INTERNAL, msg)
         })
     }
 
-    fn uri_to_zenoh_key(&self, uri: &UUri) -> String {
-        // authority_name
-        let authority = if uri.authority_name.is_empty() {
-            &self.authority_name
-        } else {
-            &uri.authority_name
-        };
-        // ue_id
-        let ue_id = if uri.ue_id == WILDCARD_ENTITY_ID {
-            "*".to_string()
-        } else {
-            format!("{:X}", uri.ue_id)
-        };
-        // ue_version_major
-        let ue_version_major = if uri.ue_version_major == WILDCARD_ENTITY_VERSION {
-            "*".to_string()
-        } else {
-            format!("{:X}", uri.ue_version_major)
-        };
-        // resource_id
-        let resource_id = if uri.resource_id == WILDCARD_RESOURCE_ID {
-            "*".to_string()
-        } else {
-            format!("{:X}", uri.resource_id)
+    // Map to Zenoh key with my authority replacing the omitted one
+    fn to_zenoh_key_string(&self, src_uri: &UUri, dst_uri: Option<&UUri>) -> String {
+        key_mapping::to_zenoh_key_string(&self.authority_name, src_uri, dst_uri)
+    }
+
+    // Apply the authority policy to the source of the outgoing message
+    fn check_outbound_authority(&self, attributes: &mut UAttributes) -> Result<(), TransportError> {
+        let Some(source) = attributes.source.as_mut() else {
+            return Ok(());
         };
-        format!("{authority}/{ue_id}/{ue_version_major}/{resource_id}")
+        if source.authority_name.is_empty() || source.authority_name == self.authority_name {
+            return Ok(());
+        }
+        match self.authority_policy {
+            AuthorityPolicy::Trust => Ok(()),
+            AuthorityPolicy::Reject => Err(TransportError::PermissionDenied(format!(
+                "The source authority {} is not mine ({})",
+                source.authority_name, self.authority_name
+            ))),
+            AuthorityPolicy::Rewrite => {
+                source.authority_name = self.authority_name.clone();
+                Ok(())
+            }
+        }
     }
 
-    // The format of Zenoh key should be
-    // up/[src.authority]/[src.ue_id]/[src.ue_version_major]/[src.resource_id]/[sink.authority]/[sink.ue_id]/[sink.ue_version_major]/[sink.resource_id]
-    fn to_zenoh_key_string(&self, src_uri: &UUri, dst_uri: Option<&UUri>) -> String {
-        let src = self.uri_to_zenoh_key(src_uri);
-        let dst = if let Some(dst) = dst_uri {
-            self.uri_to_zenoh_key(dst)
-        } else {
-            "{}/{}/{}/{}".to_string()
+    // Reject the inbound message whose authority in the Zenoh key differs from its UAttributes.
+    // The source of Response is the sink in the Zenoh key, since the reply reuses the key of the request.
+    fn check_inbound_authority(
+        zenoh_key: &str,
+        attributes: &UAttributes,
+    ) -> Result<(), TransportError> {
+        let uuris = key_mapping::zenoh_key_to_uuris(zenoh_key)
+            .map_err(|e| TransportError::PermissionDenied(e.message.unwrap_or_default()))?;
+        let key_authority = match uuris {
+            (_, Some(sink))
+                if attributes.type_.enum_value_or_default()
+                    == UMessageType::UMESSAGE_TYPE_RESPONSE =>
+            {
+                sink.authority_name
+            }
+            (src, _) => src.authority_name,
         };
-        format!("up/{src}/{dst}")
+        let authority = &attributes.source.authority_name;
+        if authority.is_empty() || *authority == key_authority {
+            Ok(())
+        } else {
+            Err(TransportError::PermissionDenied(format!(
+                "The source authority {authority} differs from {key_authority} in the Zenoh key"
+            )))
+        }
     }
 
     #[allow(clippy::match_same_arms)]
@@ -207,6 +346,7 @@ impl UPClientZenoh {
         let mut attachment = AttachmentBuilder::new();
         attachment.insert("", &UATTRIBUTE_VERSION.to_le_bytes());
         attachment.insert("", &uattributes.write_to_bytes()?);
+        trace_context::inject(&mut attachment);
         Ok(attachment)
     }
 
@@ -215,107 +355,35 @@ impl UPClientZenoh {
         if let Some((_, value)) = attachment_iter.next() {
             let version = *value.as_slice().first().ok_or_else(|| {
                 let msg = format!("UAttributes version is empty (should be {UATTRIBUTE_VERSION})");
-                log::error!("{msg}");
+                tracing::error!(code = ?UCode::INVALID_ARGUMENT, "{msg}");
                 UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg)
             })?;
             if version != UATTRIBUTE_VERSION {
                 let msg =
                     format!("UAttributes version is {version} (should be {UATTRIBUTE_VERSION})");
-                log::error!("{msg}");
+                tracing::error!(code = ?UCode::INVALID_ARGUMENT, "{msg}");
                 return Err(UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg).into());
             }
         } else {
             let msg = "Unable to get the UAttributes version".to_string();
-            log::error!("{msg}");
+            tracing::error!(code = ?UCode::INVALID_ARGUMENT, "{msg}");
             return Err(UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg).into());
         }
         let uattributes = if let Some((_, value)) = attachment_iter.next() {
             UAttributes::parse_from_bytes(value.as_slice())?
         } else {
             let msg = "Unable to get the UAttributes".to_string();
-            log::error!("{msg}");
+            tracing::error!(code = ?UCode::INVALID_ARGUMENT, "{msg}");
             return Err(UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg).into());
         };
         Ok(uattributes)
     }
-
-    /*        The table for mapping resource ID to message type
-     *
-     *  |   src rid   | sink rid | Publish | Notification | Request | Response |
-     *  |-------------|----------|---------|--------------|---------|----------|
-     *  | [8000-FFFF) |   None   |    V    |              |         |          |
-     *  | [8000-FFFF) |     0    |         |      V       |         |          |
-     *  |      0      | (0-8000) |         |              |    V    |          |
-     *  |   (0-8000)  |     0    |         |              |         |    V     |
-     *  |     FFFF    |     0    |         |      V       |         |    V     |
-     *  |     FFFF    | (0-8000) |         |              |    V    |          |
-     *  |      0      |   FFFF   |         |              |    V    |          |
-     *  |   (0-8000)  |   FFFF   |         |              |         |    V     |
-     *  | [8000-FFFF) |   FFFF   |         |      V       |         |          |
-     *  |     FFFF    |   FFFF   |         |      V       |    V    |    V     |
-     *
-     *  Some organization:
-     *  - Publish: {[8000-FFFF), None}
-     *  - Notification: {[8000-FFFF), 0}, {[8000-FFFF), FFFF]}, {FFFF, 0}, {FFFF, FFFF}
-     *  - Request: {0, (0-8000)}, {0, FFFF}, {FFFF, (0-8000)}, {FFFF, FFFF}
-     *  - Response: {(0-8000), 0}, {(0-8000), FFFF}, (FFFF, 0), {FFFF, FFFF}
-     */
-    #[allow(clippy::nonminimal_bool)] // Don't simplify the boolean expression for better understanding
-    fn get_listener_message_type(
-        source_uuri: &UUri,
-        sink_uuri: Option<&UUri>,
-    ) -> Result<MessageFlag, UStatus> {
-        let mut flag = MessageFlag::none();
-        let rpc_range = 1..0x7FFF_u32;
-        let nonrpc_range = 0x8000..0xFFFE_u32;
-
-        let src_resource = source_uuri.resource_id;
-        // Notification / Request / Response
-        if let Some(dst_uuri) = sink_uuri {
-            let dst_resource = dst_uuri.resource_id;
-
-            if (nonrpc_range.contains(&src_resource) && dst_resource == 0)
-                || (nonrpc_range.contains(&src_resource) && dst_resource == 0xFFFF)
-                || (src_resource == 0xFFFF && dst_resource == 0)
-                || (src_resource == 0xFFFF && dst_resource == 0xFFFF)
-            {
-                flag |= MessageFlag::Notification;
-            }
-            if (src_resource == 0 && rpc_range.contains(&dst_resource))
-                || (src_resource == 0 && dst_resource == 0xFFFF)
-                || (src_resource == 0xFFFF && rpc_range.contains(&dst_resource))
-                || (src_resource == 0xFFFF && dst_resource == 0xFFFF)
-            {
-                flag |= MessageFlag::Request;
-            }
-            if (rpc_range.contains(&src_resource) && dst_resource == 0)
-                || (rpc_range.contains(&src_resource) && dst_resource == 0xFFFF)
-                || (src_resource == 0xFFFF && dst_resource == 0)
-                || (src_resource == 0xFFFF && dst_resource == 0xFFFF)
-            {
-                flag |= MessageFlag::Response;
-            }
-        } else if nonrpc_range.contains(&src_resource) || src_resource == 0xFFFF {
-            flag |= MessageFlag::Publish;
-        }
-        if flag.is_none() {
-            Err(UStatus::fail_with_code(
-                UCode::INTERNAL,
-                "Wrong combination of source UUri and sink UUri",
-            ))
-        } else {
-            Ok(flag)
-        }
-    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::str::FromStr;
-
     use super::*;
     use test_case::test_case;
-    use up_rust::UUri;
 
     // CY_TODO: Test invalid authority
     #[test_case("vehicle1".to_string(), true; "succeeds with both valid authority and entity")]
@@ -325,59 +393,13 @@ mod tests {
         assert_eq!(up_client_zenoh.is_ok(), expected_result);
     }
 
-    // Mapping with the examples in Zenoh spec
-    #[test_case("/10AB/3/80CD", None, "up/192.168.1.100/10AB/3/80CD/{}/{}/{}/{}"; "Send Publish")]
-    #[test_case("//192.168.1.100/10AB/3/80CD", None, "up/192.168.1.100/10AB/3/80CD/{}/{}/{}/{}"; "Subscribe messages")]
-    #[test_case("//192.168.1.100/10AB/3/80CD", Some("//192.168.1.101/20EF/4/0"), "up/192.168.1.100/10AB/3/80CD/192.168.1.101/20EF/4/0"; "Send Notification")]
-    #[test_case("//*/FFFF/FF/FFFF", Some("//192.168.1.101/20EF/4/0"), "up/*/*/*/*/192.168.1.101/20EF/4/0"; "Receive all Notifications")]
-    #[test_case("//my-host1/10AB/3/0", Some("//my-host2/20EF/4/B"), "up/my-host1/10AB/3/0/my-host2/20EF/4/B"; "Send Request")]
-    #[test_case("//*/FFFF/FF/FFFF", Some("//my-host2/20EF/4/B"), "up/*/*/*/*/my-host2/20EF/4/B"; "Receive all Requests")]
-    #[test_case("//*/FFFF/FF/FFFF", Some("//[::1]/FFFF/FF/FFFF"), "up/*/*/*/*/[::1]/*/*/*"; "Receive all messages to a device")]
-    #[tokio::test(flavor = "multi_thread")]
-    async fn test_to_zenoh_key_string(src_uri: &str, sink_uri: Option<&str>, zenoh_key: &str) {
-        let up_client_zenoh = UPClientZenoh::new(Config::default(), String::from("192.168.1.100"))
-            .await
-            .unwrap();
-        let src = UUri::from_str(src_uri).unwrap();
-        if let Some(sink) = sink_uri {
-            let sink = UUri::from_str(sink).unwrap();
-            assert_eq!(
-                up_client_zenoh.to_zenoh_key_string(&src, Some(&sink)),
-                zenoh_key.to_string()
-            );
-        } else {
-            assert_eq!(
-                up_client_zenoh.to_zenoh_key_string(&src, None),
-                zenoh_key.to_string()
-            );
-        }
-    }
-
-    #[test_case("//192.168.1.100/10AB/3/80CD", None, Ok(MessageFlag::Publish); "Publish Message")]
-    #[test_case("//192.168.1.100/10AB/3/80CD", Some("//192.168.1.101/20EF/4/0"), Ok(MessageFlag::Notification); "Notification Message")]
-    #[test_case("//192.168.1.100/10AB/3/0", Some("//192.168.1.101/20EF/4/B"), Ok(MessageFlag::Request); "Request Message")]
-    #[test_case("//192.168.1.101/20EF/4/B", Some("//192.168.1.100/10AB/3/0"), Ok(MessageFlag::Response); "Response Message")]
-    #[test_case("//*/FFFF/FF/FFFF", Some("//192.168.1.100/10AB/3/0"), Ok(MessageFlag::Notification | MessageFlag::Response); "Listen to Notification and Response Message")]
-    #[test_case("//*/FFFF/FF/FFFF", Some("//192.168.1.101/20EF/4/B"), Ok(MessageFlag::Request); "Listen to Request Message")]
-    #[test_case("//192.168.1.100/10AB/3/0", Some("//*/FFFF/FF/FFFF"), Ok(MessageFlag::Request); "Broadcast Request Message")]
-    #[test_case("//192.168.1.101/20EF/4/B", Some("//*/FFFF/FF/FFFF"), Ok(MessageFlag::Response); "Broadcast Response Message")]
-    #[test_case("//192.168.1.100/10AB/3/80CD", Some("//*/FFFF/FF/FFFF"), Ok(MessageFlag::Notification); "Broadcast Notification Message")]
-    #[test_case("//*/FFFF/FF/FFFF", Some("//[::1]/FFFF/FF/FFFF"), Ok(MessageFlag::Notification | MessageFlag::Request | MessageFlag::Response); "All messages to a device")]
     #[tokio::test(flavor = "multi_thread")]
-    async fn test_get_listener_message_type(
-        src_uri: &str,
-        sink_uri: Option<&str>,
-        result: Result<MessageFlag, UStatus>,
-    ) {
-        let src = UUri::from_str(src_uri).unwrap();
-        if let Some(uri) = sink_uri {
-            let dst = UUri::from_str(uri).unwrap();
-            assert_eq!(
-                UPClientZenoh::get_listener_message_type(&src, Some(&dst)),
-                result
-            );
-        } else {
-            assert_eq!(UPClientZenoh::get_listener_message_type(&src, None), result);
-        }
+    async fn test_loopback_runtime_locators() {
+        let runtime = UPClientZenoh::new_loopback_runtime().await.unwrap();
+        let locators = runtime.get_locators();
+        assert!(!locators.is_empty());
+        assert!(locators
+            .iter()
+            .all(|locator| locator.address().starts_with("127.0.0.1:")));
     }
 }
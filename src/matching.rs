@@ -0,0 +1,126 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+use crate::UPClientZenoh;
+use up_rust::{UCode, UStatus, UUri};
+use zenoh::{prelude::r#async::*, publication::Publisher};
+
+impl UPClientZenoh {
+    /// Check whether there is at least one Publish / Notification listener matching the messages
+    /// sent from `source` to `sink`, no matter the listener is in this `UPClientZenoh` or a remote one.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source `UUri` of the messages to send.
+    /// * `sink` - The sink `UUri` of the messages to send. `None` for Publish.
+    ///
+    /// # Errors
+    /// Will return `Err` if unable to get the matching status from Zenoh
+    pub async fn has_matching_listener(
+        &self,
+        source: &UUri,
+        sink: Option<&UUri>,
+    ) -> Result<bool, UStatus> {
+        let publisher = self.declare_matching_publisher(source, sink).await?;
+        publisher
+            .matching_status()
+            .res()
+            .await
+            .map(|status| status.matching_subscribers())
+            .map_err(|e| {
+                let msg = format!("Unable to get the matching status from Zenoh: {e:?}");
+                tracing::error!(code = ?UCode::INTERNAL, "{msg}");
+                UStatus::fail_with_code(UCode::INTERNAL, msg)
+            })
+    }
+
+    /// Wait until at least one Publish / Notification listener matches the messages sent from
+    /// `source` to `sink`. This can be used to make sure the critical messages are not sent into the void.
+    /// Wrap the future with a timeout if the listener might never show up.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The source `UUri` of the messages to send.
+    /// * `sink` - The sink `UUri` of the messages to send. `None` for Publish.
+    ///
+    /// # Errors
+    /// Will return `Err` if unable to get the matching status from Zenoh
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// #[tokio::main]
+    /// # async fn main() {
+    /// use std::{str::FromStr, time::Duration};
+    /// use up_rust::UUri;
+    /// use up_transport_zenoh::{Config, UPClientZenoh};
+    /// let upclient = UPClientZenoh::new(Config::default(), String::from("MyAuthName"))
+    ///     .await
+    ///     .unwrap();
+    /// let topic = UUri::from_str("//MyAuthName/1/1/8000").unwrap();
+    /// // Nobody listens to the topic
+    /// let matched = tokio::time::timeout(
+    ///     Duration::from_millis(100),
+    ///     upclient.wait_for_matching_listener(&topic, None),
+    /// )
+    /// .await;
+    /// assert!(matched.is_err());
+    /// # }
+    /// ```
+    pub async fn wait_for_matching_listener(
+        &self,
+        source: &UUri,
+        sink: Option<&UUri>,
+    ) -> Result<(), UStatus> {
+        let publisher = self.declare_matching_publisher(source, sink).await?;
+        // Declare the matching listener before checking the current status to avoid missing any update
+        let matching_listener = publisher.matching_listener().res().await.map_err(|e| {
+            let msg = format!("Unable to declare the matching listener in Zenoh: {e:?}");
+            tracing::error!(code = ?UCode::INTERNAL, "{msg}");
+            UStatus::fail_with_code(UCode::INTERNAL, msg)
+        })?;
+        let matched = publisher
+            .matching_status()
+            .res()
+            .await
+            .map(|status| status.matching_subscribers())
+            .unwrap_or(false);
+        if matched {
+            return Ok(());
+        }
+        while let Ok(status) = matching_listener.recv_async().await {
+            if status.matching_subscribers() {
+                return Ok(());
+            }
+        }
+        let msg = "Zenoh matching listener is closed".to_string();
+        tracing::error!(code = ?UCode::INTERNAL, "{msg}");
+        Err(UStatus::fail_with_code(UCode::INTERNAL, msg))
+    }
+
+    async fn declare_matching_publisher(
+        &self,
+        source: &UUri,
+        sink: Option<&UUri>,
+    ) -> Result<Publisher<'static>, UStatus> {
+        let zenoh_key = self.to_zenoh_key_string(source, sink);
+        self.session
+            .declare_publisher(zenoh_key)
+            .res()
+            .await
+            .map_err(|e| {
+                let msg = format!("Unable to declare Zenoh publisher: {e:?}");
+                tracing::error!(code = ?UCode::INTERNAL, "{msg}");
+                UStatus::fail_with_code(UCode::INTERNAL, msg)
+            })
+    }
+}
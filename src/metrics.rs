@@ -0,0 +1,337 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+// The metrics are only collected with the `metrics` feature.
+// Without it, `Metrics` is a no-op with the same interface, so the callers don't need `cfg`.
+use crate::error::TransportError;
+use std::{sync::Arc, time::Instant};
+use up_rust::{UAttributes, UMessageType, UPriority, UStatus};
+
+#[cfg(feature = "metrics")]
+use crate::UPClientZenoh;
+#[cfg(feature = "metrics")]
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+
+#[cfg(feature = "metrics")]
+fn message_type_label(message_type: UMessageType) -> &'static str {
+    match message_type {
+        UMessageType::UMESSAGE_TYPE_PUBLISH => "publish",
+        UMessageType::UMESSAGE_TYPE_NOTIFICATION => "notification",
+        UMessageType::UMESSAGE_TYPE_REQUEST => "request",
+        UMessageType::UMESSAGE_TYPE_RESPONSE => "response",
+        UMessageType::UMESSAGE_TYPE_UNSPECIFIED => "unspecified",
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn priority_label(priority: UPriority) -> &'static str {
+    match priority {
+        UPriority::UPRIORITY_CS0 => "CS0",
+        UPriority::UPRIORITY_CS1 => "CS1",
+        UPriority::UPRIORITY_CS2 => "CS2",
+        UPriority::UPRIORITY_CS3 => "CS3",
+        UPriority::UPRIORITY_CS4 => "CS4",
+        UPriority::UPRIORITY_CS5 => "CS5",
+        UPriority::UPRIORITY_CS6 => "CS6",
+        UPriority::UPRIORITY_UNSPECIFIED => "unspecified",
+    }
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) struct Metrics {
+    registry: Registry,
+    messages_sent: IntCounterVec,
+    messages_received: IntCounterVec,
+    send_failures: IntCounterVec,
+    attachment_errors: IntCounter,
+    messages_dropped: IntCounterVec,
+    rpc_latency: Histogram,
+    rpc_timeouts: IntCounter,
+    pending_queries: IntGauge,
+    callback_queue_depth: IntGauge,
+    offline_queue_depth: IntGauge,
+}
+
+#[cfg(feature = "metrics")]
+impl Metrics {
+    pub(crate) fn new() -> Self {
+        let registry = Registry::new();
+        let messages_sent = IntCounterVec::new(
+            Opts::new("up_zenoh_messages_sent_total", "Messages sent"),
+            &["type", "priority"],
+        )
+        .unwrap();
+        let messages_received = IntCounterVec::new(
+            Opts::new("up_zenoh_messages_received_total", "Messages received"),
+            &["type", "priority"],
+        )
+        .unwrap();
+        let send_failures = IntCounterVec::new(
+            Opts::new("up_zenoh_send_failures_total", "Failures of send by UCode"),
+            &["code"],
+        )
+        .unwrap();
+        let attachment_errors = IntCounter::new(
+            "up_zenoh_attachment_decode_errors_total",
+            "Attachments unable to be decoded into UAttributes",
+        )
+        .unwrap();
+        let messages_dropped = IntCounterVec::new(
+            Opts::new(
+                "up_zenoh_messages_dropped_total",
+                "Messages dropped by the rate limits or the offline queue",
+            ),
+            &["reason"],
+        )
+        .unwrap();
+        let rpc_latency = Histogram::with_opts(HistogramOpts::new(
+            "up_zenoh_rpc_latency_seconds",
+            "Latency from sending RPC request to receiving its response",
+        ))
+        .unwrap();
+        let rpc_timeouts = IntCounter::new(
+            "up_zenoh_rpc_timeouts_total",
+            "RPC requests without any response before the TTL",
+        )
+        .unwrap();
+        let pending_queries = IntGauge::new(
+            "up_zenoh_pending_queries",
+            "RPC requests received and waiting for the response",
+        )
+        .unwrap();
+        let callback_queue_depth = IntGauge::new(
+            "up_zenoh_callback_queue_depth",
+            "Listener callbacks spawned and not finished yet",
+        )
+        .unwrap();
+        let offline_queue_depth = IntGauge::new(
+            "up_zenoh_offline_queue_depth",
+            "Messages held in the offline queue until reconnected",
+        )
+        .unwrap();
+        registry.register(Box::new(messages_sent.clone())).unwrap();
+        registry
+            .register(Box::new(messages_received.clone()))
+            .unwrap();
+        registry.register(Box::new(send_failures.clone())).unwrap();
+        registry
+            .register(Box::new(attachment_errors.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(messages_dropped.clone()))
+            .unwrap();
+        registry.register(Box::new(rpc_latency.clone())).unwrap();
+        registry.register(Box::new(rpc_timeouts.clone())).unwrap();
+        registry
+            .register(Box::new(pending_queries.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(callback_queue_depth.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(offline_queue_depth.clone()))
+            .unwrap();
+        Metrics {
+            registry,
+            messages_sent,
+            messages_received,
+            send_failures,
+            attachment_errors,
+            messages_dropped,
+            rpc_latency,
+            rpc_timeouts,
+            pending_queries,
+            callback_queue_depth,
+            offline_queue_depth,
+        }
+    }
+
+    pub(crate) fn on_send(
+        &self,
+        message_type: UMessageType,
+        priority: UPriority,
+        result: &Result<(), UStatus>,
+    ) {
+        match result {
+            Ok(()) => self
+                .messages_sent
+                .with_label_values(&[message_type_label(message_type), priority_label(priority)])
+                .inc(),
+            Err(e) => self
+                .send_failures
+                .with_label_values(&[&format!("{:?}", e.code.enum_value_or_default())])
+                .inc(),
+        }
+    }
+
+    pub(crate) fn on_receive(&self, attributes: &UAttributes) {
+        self.messages_received
+            .with_label_values(&[
+                message_type_label(attributes.type_.enum_value_or_default()),
+                priority_label(attributes.priority.enum_value_or_default()),
+            ])
+            .inc();
+    }
+
+    pub(crate) fn on_attachment_error(&self) {
+        self.attachment_errors.inc();
+    }
+
+    pub(crate) fn on_drop(&self, reason: &str) {
+        self.messages_dropped.with_label_values(&[reason]).inc();
+    }
+
+    pub(crate) fn set_pending_queries(&self, pending: usize) {
+        self.pending_queries
+            .set(i64::try_from(pending).unwrap_or(i64::MAX));
+    }
+
+    pub(crate) fn start_callback(self: &Arc<Self>) -> CallbackGuard {
+        self.callback_queue_depth.inc();
+        CallbackGuard {
+            metrics: self.clone(),
+        }
+    }
+
+    pub(crate) fn set_offline_queue_depth(&self, depth: usize) {
+        self.offline_queue_depth
+            .set(i64::try_from(depth).unwrap_or(i64::MAX));
+    }
+
+    pub(crate) fn start_rpc(self: &Arc<Self>, deadline: Instant) -> RpcGuard {
+        RpcGuard {
+            metrics: self.clone(),
+            start: Instant::now(),
+            deadline,
+            finished: false,
+        }
+    }
+}
+
+// Count the callback as finished when dropped, even if the listener panics
+#[cfg(feature = "metrics")]
+pub(crate) struct CallbackGuard {
+    metrics: Arc<Metrics>,
+}
+
+#[cfg(feature = "metrics")]
+impl Drop for CallbackGuard {
+    fn drop(&mut self) {
+        self.metrics.callback_queue_depth.dec();
+    }
+}
+
+// Observe the RPC latency on the first response, or count the first failure by its code.
+// The timeout is only counted once the deadline is reached, also if dropped without any reply.
+#[cfg(feature = "metrics")]
+pub(crate) struct RpcGuard {
+    metrics: Arc<Metrics>,
+    start: Instant,
+    deadline: Instant,
+    finished: bool,
+}
+
+#[cfg(feature = "metrics")]
+impl RpcGuard {
+    pub(crate) fn finish<T>(&mut self, result: &Result<T, TransportError>) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        match result {
+            Ok(_) => self
+                .metrics
+                .rpc_latency
+                .observe(self.start.elapsed().as_secs_f64()),
+            Err(TransportError::Timeout) => self.metrics.rpc_timeouts.inc(),
+            Err(e) => self
+                .metrics
+                .send_failures
+                .with_label_values(&[&format!("{:?}", e.code())])
+                .inc(),
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Drop for RpcGuard {
+    fn drop(&mut self) {
+        if !self.finished && Instant::now() >= self.deadline {
+            self.metrics.rpc_timeouts.inc();
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl UPClientZenoh {
+    /// The Prometheus registry of the transport metrics, which can be scraped by the application
+    /// or merged into its own registry. Only available with the `metrics` feature.
+    #[must_use]
+    pub fn metrics_registry(&self) -> &Registry {
+        &self.metrics.registry
+    }
+
+    /// Encode the transport metrics in the Prometheus text exposition format,
+    /// which can be served directly as the `/metrics` endpoint. Only available with the `metrics` feature.
+    #[must_use]
+    pub fn gather_metrics(&self) -> String {
+        let mut buffer = vec![];
+        if let Err(e) = TextEncoder::new().encode(&self.metrics.registry.gather(), &mut buffer) {
+            tracing::error!("Unable to encode the metrics: {e:?}");
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) struct Metrics;
+
+#[cfg(not(feature = "metrics"))]
+#[allow(clippy::unused_self)]
+impl Metrics {
+    pub(crate) fn new() -> Self {
+        Metrics
+    }
+    pub(crate) fn on_send(
+        &self,
+        _message_type: UMessageType,
+        _priority: UPriority,
+        _result: &Result<(), UStatus>,
+    ) {
+    }
+    pub(crate) fn on_receive(&self, _attributes: &UAttributes) {}
+    pub(crate) fn on_attachment_error(&self) {}
+    pub(crate) fn on_drop(&self, _reason: &str) {}
+    pub(crate) fn set_pending_queries(&self, _pending: usize) {}
+    pub(crate) fn start_callback(self: &Arc<Self>) -> CallbackGuard {
+        CallbackGuard
+    }
+    pub(crate) fn set_offline_queue_depth(&self, _depth: usize) {}
+    pub(crate) fn start_rpc(self: &Arc<Self>, _deadline: Instant) -> RpcGuard {
+        RpcGuard
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) struct CallbackGuard;
+
+#[cfg(not(feature = "metrics"))]
+pub(crate) struct RpcGuard;
+
+#[cfg(not(feature = "metrics"))]
+#[allow(clippy::unused_self)]
+impl RpcGuard {
+    pub(crate) fn finish<T>(&mut self, _result: &Result<T, TransportError>) {}
+}
@@ -0,0 +1,437 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+//! A transport double of `UPClientZenoh` for unit testing the code built on top of it.
+//!
+//! `MockTransport` routes the messages with the same Zenoh key and message type classification
+//! as `UPClientZenoh`, but without any Zenoh session.
+//! The messages sent are recorded, and the inbound messages and RPC responses are injected by the tests.
+use crate::key_mapping::{get_listener_message_type, to_zenoh_key_string, MessageFlag};
+use async_trait::async_trait;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use up_rust::{
+    ComparableListener, RpcClient, RpcClientResult, UAttributes, UAttributesValidators, UCode,
+    UListener, UMessage, UMessageBuilder, UMessageError, UMessageType, UPayloadFormat, UStatus,
+    UTransport, UUri,
+};
+use zenoh::prelude::{keyexpr, OwnedKeyExpr};
+
+type ListenerMap = Mutex<HashMap<(String, ComparableListener), Arc<dyn UListener>>>;
+type RpcResponseMap = Mutex<HashMap<OwnedKeyExpr, (Vec<u8>, UPayloadFormat)>>;
+
+/// A `UTransport` and `RpcClient` which doesn't need Zenoh.
+///
+/// # Examples
+///
+/// ```
+/// #[tokio::main]
+/// # async fn main() {
+/// use std::str::FromStr;
+/// use up_rust::{UMessageBuilder, UPayloadFormat, UTransport, UUri};
+/// use up_transport_zenoh::mock::MockTransport;
+///
+/// let transport = MockTransport::new(String::from("MyAuthName"));
+/// let topic = UUri::from_str("//MyAuthName/1/1/8000").unwrap();
+/// let umessage = UMessageBuilder::publish(topic)
+///     .build_with_payload("Hello", UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+///     .unwrap();
+/// transport.send(umessage).await.unwrap();
+/// assert_eq!(transport.sent_messages().len(), 1);
+/// # }
+/// ```
+pub struct MockTransport {
+    // Publish & Notification listeners
+    subscriber_map: ListenerMap,
+    // RPC request listeners
+    queryable_map: ListenerMap,
+    // RPC response listeners
+    rpc_callback_map: Mutex<HashMap<OwnedKeyExpr, Arc<dyn UListener>>>,
+    // The predefined RPC responses
+    rpc_response_map: RpcResponseMap,
+    // The messages sent through the transport
+    sent_messages: Mutex<Vec<UMessage>>,
+    // My authority
+    authority_name: String,
+}
+
+impl MockTransport {
+    /// Create `MockTransport` with the authority name used to fill the authority omitted in `UUri`.
+    #[must_use]
+    pub fn new(authority_name: String) -> Self {
+        MockTransport {
+            subscriber_map: Mutex::new(HashMap::new()),
+            queryable_map: Mutex::new(HashMap::new()),
+            rpc_callback_map: Mutex::new(HashMap::new()),
+            rpc_response_map: Mutex::new(HashMap::new()),
+            sent_messages: Mutex::new(Vec::new()),
+            authority_name,
+        }
+    }
+
+    /// Get all the messages sent through `send` and `invoke_method` so far.
+    ///
+    /// # Panics
+    /// Will panic if the lock is poisoned
+    #[must_use]
+    pub fn sent_messages(&self) -> Vec<UMessage> {
+        self.sent_messages.lock().unwrap().clone()
+    }
+
+    /// Forget the messages sent so far.
+    ///
+    /// # Panics
+    /// Will panic if the lock is poisoned
+    pub fn clear_sent_messages(&self) {
+        self.sent_messages.lock().unwrap().clear();
+    }
+
+    /// Check whether `listener` is registered with exactly the same filters.
+    ///
+    /// # Panics
+    /// Will panic if the lock is poisoned
+    #[must_use]
+    pub fn has_listener(
+        &self,
+        source_filter: &UUri,
+        sink_filter: Option<&UUri>,
+        listener: &Arc<dyn UListener>,
+    ) -> bool {
+        let Ok(flag) = get_listener_message_type(source_filter, sink_filter) else {
+            return false;
+        };
+        let zenoh_key = self.to_zenoh_key_string(source_filter, sink_filter);
+        // Publish & Notification
+        if (flag.contains(MessageFlag::Publish) || flag.contains(MessageFlag::Notification))
+            && !self
+                .subscriber_map
+                .lock()
+                .unwrap()
+                .contains_key(&(zenoh_key.clone(), ComparableListener::new(listener.clone())))
+        {
+            return false;
+        }
+        // RPC request
+        if flag.contains(MessageFlag::Request)
+            && !self
+                .queryable_map
+                .lock()
+                .unwrap()
+                .contains_key(&(zenoh_key, ComparableListener::new(listener.clone())))
+        {
+            return false;
+        }
+        // RPC response
+        if flag.contains(MessageFlag::Response) {
+            let Some(sink_filter) = sink_filter else {
+                return false;
+            };
+            let zenoh_key = self.to_zenoh_key_string(sink_filter, Some(source_filter));
+            return keyexpr::new(&zenoh_key).is_ok_and(|zenoh_key| {
+                self.rpc_callback_map
+                    .lock()
+                    .unwrap()
+                    .get(zenoh_key)
+                    .is_some_and(|saved| {
+                        ComparableListener::new(saved.clone())
+                            == ComparableListener::new(listener.clone())
+                    })
+            });
+        }
+        true
+    }
+
+    /// Get the number of the registered listeners.
+    ///
+    /// # Panics
+    /// Will panic if the lock is poisoned
+    #[must_use]
+    pub fn listener_count(&self) -> usize {
+        self.subscriber_map.lock().unwrap().len()
+            + self.queryable_map.lock().unwrap().len()
+            + self.rpc_callback_map.lock().unwrap().len()
+    }
+
+    /// Deliver an inbound message to all the listeners matching it, as if it was received from Zenoh.
+    /// The listeners are called before the function returns.
+    ///
+    /// # Errors
+    /// Will return `Err` if the message is invalid
+    ///
+    /// # Panics
+    /// Will panic if the lock is poisoned
+    pub async fn inject_message(&self, message: UMessage) -> Result<(), UStatus> {
+        let attributes = MockTransport::validate(&message)?;
+        let source = attributes.source.get_or_default();
+        let listeners = match attributes.type_.enum_value_or_default() {
+            UMessageType::UMESSAGE_TYPE_PUBLISH => {
+                let zenoh_key = self.to_zenoh_key_string(source, None);
+                MockTransport::matching_listeners(&self.subscriber_map, &zenoh_key)
+            }
+            UMessageType::UMESSAGE_TYPE_NOTIFICATION => {
+                let zenoh_key =
+                    self.to_zenoh_key_string(source, Some(attributes.sink.get_or_default()));
+                MockTransport::matching_listeners(&self.subscriber_map, &zenoh_key)
+            }
+            UMessageType::UMESSAGE_TYPE_REQUEST => {
+                let zenoh_key =
+                    self.to_zenoh_key_string(source, Some(attributes.sink.get_or_default()));
+                MockTransport::matching_listeners(&self.queryable_map, &zenoh_key)
+            }
+            UMessageType::UMESSAGE_TYPE_RESPONSE => {
+                // The response listener is stored with the key of the request
+                let zenoh_key =
+                    self.to_zenoh_key_string(attributes.sink.get_or_default(), Some(source));
+                self.matching_response_listeners(&zenoh_key)
+            }
+            UMessageType::UMESSAGE_TYPE_UNSPECIFIED => vec![],
+        };
+        for listener in listeners {
+            listener.on_receive(message.clone()).await;
+        }
+        Ok(())
+    }
+
+    /// Answer the requests sent to `method` with `payload`.
+    /// The response is delivered to the response listeners for `send` and returned by `invoke_method`.
+    ///
+    /// # Panics
+    /// Will panic if the lock is poisoned
+    pub fn set_rpc_response(&self, method: &UUri, payload: Vec<u8>, format: UPayloadFormat) {
+        let any_uuri = UUri {
+            authority_name: "*".to_string(),
+            ue_id: 0xFFFF,
+            ue_version_major: 0xFF,
+            resource_id: 0xFFFF,
+            ..Default::default()
+        };
+        let zenoh_key = self.to_zenoh_key_string(&any_uuri, Some(method));
+        // The key generated from UUri is always a valid key expression
+        let zenoh_key = OwnedKeyExpr::new(zenoh_key).unwrap();
+        self.rpc_response_map
+            .lock()
+            .unwrap()
+            .insert(zenoh_key, (payload, format));
+    }
+
+    fn to_zenoh_key_string(&self, src_uri: &UUri, dst_uri: Option<&UUri>) -> String {
+        to_zenoh_key_string(&self.authority_name, src_uri, dst_uri)
+    }
+
+    fn validate(message: &UMessage) -> Result<&UAttributes, UStatus> {
+        let attributes = message.attributes.as_ref().ok_or_else(|| {
+            UStatus::fail_with_code(UCode::INVALID_ARGUMENT, "Invalid UAttributes")
+        })?;
+        let validator = match attributes.type_.enum_value_or_default() {
+            UMessageType::UMESSAGE_TYPE_PUBLISH => UAttributesValidators::Publish,
+            UMessageType::UMESSAGE_TYPE_NOTIFICATION => UAttributesValidators::Notification,
+            UMessageType::UMESSAGE_TYPE_REQUEST => UAttributesValidators::Request,
+            UMessageType::UMESSAGE_TYPE_RESPONSE => UAttributesValidators::Response,
+            UMessageType::UMESSAGE_TYPE_UNSPECIFIED => {
+                return Err(UStatus::fail_with_code(
+                    UCode::INVALID_ARGUMENT,
+                    "Wrong Message type in UAttributes",
+                ));
+            }
+        };
+        validator.validator().validate(attributes).map_err(|e| {
+            UStatus::fail_with_code(UCode::INVALID_ARGUMENT, format!("Wrong UAttributes: {e:?}"))
+        })?;
+        Ok(attributes)
+    }
+
+    fn matching_listeners(map: &ListenerMap, zenoh_key: &str) -> Vec<Arc<dyn UListener>> {
+        let Ok(zenoh_key) = keyexpr::new(zenoh_key) else {
+            return vec![];
+        };
+        map.lock()
+            .unwrap()
+            .iter()
+            .filter(|((saved_key, _), _)| {
+                keyexpr::new(saved_key).is_ok_and(|saved_key| zenoh_key.intersects(saved_key))
+            })
+            .map(|(_, listener)| listener.clone())
+            .collect()
+    }
+
+    fn matching_response_listeners(&self, zenoh_key: &str) -> Vec<Arc<dyn UListener>> {
+        let Ok(zenoh_key) = keyexpr::new(zenoh_key) else {
+            return vec![];
+        };
+        self.rpc_callback_map
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(saved_key, _)| zenoh_key.intersects(saved_key))
+            .map(|(_, listener)| listener.clone())
+            .collect()
+    }
+
+    fn predefined_response(&self, request: &UAttributes) -> Option<UMessage> {
+        let zenoh_key = self.to_zenoh_key_string(
+            request.source.get_or_default(),
+            Some(request.sink.get_or_default()),
+        );
+        let zenoh_key = keyexpr::new(&zenoh_key).ok()?;
+        let (payload, format) = self
+            .rpc_response_map
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(saved_key, _)| zenoh_key.intersects(saved_key))
+            .map(|(_, response)| response.clone())?;
+        UMessageBuilder::response_for_request(request)
+            .build_with_payload(payload, format)
+            .ok()
+    }
+}
+
+#[async_trait]
+impl UTransport for MockTransport {
+    async fn send(&self, message: UMessage) -> Result<(), UStatus> {
+        let attributes = MockTransport::validate(&message)?.clone();
+        self.sent_messages.lock().unwrap().push(message);
+
+        // Answer the request with the predefined response
+        if attributes.type_.enum_value_or_default() == UMessageType::UMESSAGE_TYPE_REQUEST {
+            if let Some(response) = self.predefined_response(&attributes) {
+                self.inject_message(response).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn receive(
+        &self,
+        _source_filter: &UUri,
+        _sink_filter: Option<&UUri>,
+    ) -> Result<UMessage, UStatus> {
+        Err(UStatus::fail_with_code(
+            UCode::UNIMPLEMENTED,
+            "Not implemented",
+        ))
+    }
+
+    async fn register_listener(
+        &self,
+        source_filter: &UUri,
+        sink_filter: Option<&UUri>,
+        listener: Arc<dyn UListener>,
+    ) -> Result<(), UStatus> {
+        let flag = get_listener_message_type(source_filter, sink_filter)?;
+        let zenoh_key = self.to_zenoh_key_string(source_filter, sink_filter);
+        // Publish & Notification
+        if flag.contains(MessageFlag::Publish) || flag.contains(MessageFlag::Notification) {
+            self.subscriber_map.lock().unwrap().insert(
+                (zenoh_key.clone(), ComparableListener::new(listener.clone())),
+                listener.clone(),
+            );
+        }
+        // RPC request
+        if flag.contains(MessageFlag::Request) {
+            self.queryable_map.lock().unwrap().insert(
+                (zenoh_key, ComparableListener::new(listener.clone())),
+                listener.clone(),
+            );
+        }
+        // RPC response
+        if flag.contains(MessageFlag::Response) {
+            let Some(sink_filter) = sink_filter else {
+                return Err(UStatus::fail_with_code(
+                    UCode::INVALID_ARGUMENT,
+                    "Sink should not be None in Response",
+                ));
+            };
+            let zenoh_key = self.to_zenoh_key_string(sink_filter, Some(source_filter));
+            let zenoh_key = OwnedKeyExpr::new(zenoh_key)
+                .map_err(|e| UStatus::fail_with_code(UCode::INVALID_ARGUMENT, format!("{e:?}")))?;
+            self.rpc_callback_map
+                .lock()
+                .unwrap()
+                .insert(zenoh_key, listener);
+        }
+        Ok(())
+    }
+
+    async fn unregister_listener(
+        &self,
+        source_filter: &UUri,
+        sink_filter: Option<&UUri>,
+        listener: Arc<dyn UListener>,
+    ) -> Result<(), UStatus> {
+        let flag = get_listener_message_type(source_filter, sink_filter)?;
+        let zenoh_key = self.to_zenoh_key_string(source_filter, sink_filter);
+        // Publish & Notification
+        if (flag.contains(MessageFlag::Publish) || flag.contains(MessageFlag::Notification))
+            && self
+                .subscriber_map
+                .lock()
+                .unwrap()
+                .remove(&(zenoh_key.clone(), ComparableListener::new(listener.clone())))
+                .is_none()
+        {
+            return Err(UStatus::fail_with_code(
+                UCode::NOT_FOUND,
+                "Publish / Notifcation listener doesn't exist",
+            ));
+        }
+        // RPC request
+        if flag.contains(MessageFlag::Request)
+            && self
+                .queryable_map
+                .lock()
+                .unwrap()
+                .remove(&(zenoh_key, ComparableListener::new(listener.clone())))
+                .is_none()
+        {
+            return Err(UStatus::fail_with_code(
+                UCode::NOT_FOUND,
+                "RPC request listener doesn't exist",
+            ));
+        }
+        // RPC response
+        if flag.contains(MessageFlag::Response) {
+            let Some(sink_filter) = sink_filter else {
+                return Err(UStatus::fail_with_code(
+                    UCode::INVALID_ARGUMENT,
+                    "Sink should not be None in Response",
+                ));
+            };
+            let zenoh_key = self.to_zenoh_key_string(sink_filter, Some(source_filter));
+            let removed = keyexpr::new(&zenoh_key)
+                .ok()
+                .and_then(|zenoh_key| self.rpc_callback_map.lock().unwrap().remove(zenoh_key));
+            if removed.is_none() {
+                return Err(UStatus::fail_with_code(
+                    UCode::NOT_FOUND,
+                    "RPC response callback doesn't exist",
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl RpcClient for MockTransport {
+    async fn invoke_method(&self, _method: UUri, request: UMessage) -> RpcClientResult {
+        let attributes = MockTransport::validate(&request)
+            .map_err(|e| UMessageError::PayloadError(format!("{e:?}")))?
+            .clone();
+        self.sent_messages.lock().unwrap().push(request);
+        self.predefined_response(&attributes).ok_or_else(|| {
+            UMessageError::PayloadError("No response is set for the method".to_string())
+        })
+    }
+}
@@ -0,0 +1,519 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+//! Store-and-forward of the Publish and Notification messages sent while offline.
+//!
+//! The messages are queued as the Zenoh puts prepared by `send`, i.e. already signed and encrypted,
+//! and put in order once a router or peer is connected again, or a local listener matches them.
+//! They are flushed as soon as the connectivity changes, and retried every `FLUSH_RETRY_INTERVAL`
+//! for the local listeners registered meanwhile or the puts failed before.
+use crate::{
+    connectivity::{has_route, ConnectivityStatus, LocalListeners},
+    file_log::{read_chunk, to_timestamp, write_chunk, FileLog},
+    metrics::Metrics,
+    UPClientZenoh, CB_RUNTIME,
+};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufReader, ErrorKind, Read},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, Weak,
+    },
+    time::{Duration, SystemTime},
+};
+use tokio::{sync::watch, task::JoinHandle};
+use up_rust::{UAttributes, UCode, UStatus};
+use zenoh::{
+    prelude::r#async::*,
+    publication::Priority,
+    sample::{Attachment, AttachmentBuilder},
+};
+
+// The interval of retrying to flush while the connectivity doesn't change
+const FLUSH_RETRY_INTERVAL: Duration = Duration::from_millis(500);
+
+// The expiry of the message without TTL in the queue file
+const NEVER_EXPIRES: u64 = u64::MAX;
+
+/// Configuration of the outbound queue holding the messages sent while offline.
+///
+/// While no router or peer is connected and no local listener matches, `send` queues the Publish
+/// and Notification messages instead of putting them, and they are flushed in order on reconnect.
+/// RPC requests and responses are never queued.
+#[derive(Clone, Debug)]
+pub struct OfflineQueueConfig {
+    capacity: usize,
+    file_path: Option<PathBuf>,
+}
+
+impl OfflineQueueConfig {
+    /// Hold at most `capacity` messages. The oldest one is dropped if the queue is full.
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        OfflineQueueConfig {
+            capacity,
+            file_path: None,
+        }
+    }
+
+    /// Also store the queued messages in the file, so they survive the restart.
+    /// The existing messages in the file are loaded when `UPClientZenoh` is created.
+    /// The file is appended in the background, and compacted once the removed messages take half of it.
+    #[must_use]
+    pub fn with_file(mut self, file_path: impl Into<PathBuf>) -> Self {
+        self.file_path = Some(file_path.into());
+        self
+    }
+}
+
+fn io_error_to_ustatus(e: &std::io::Error) -> UStatus {
+    let msg = format!("Unable to access the offline queue file: {e:?}");
+    tracing::error!(code = ?UCode::INTERNAL, "{msg}");
+    UStatus::fail_with_code(UCode::INTERNAL, msg)
+}
+
+fn invalid_data(msg: &str) -> std::io::Error {
+    std::io::Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+// The Zenoh put prepared by send, replayed when the queue is flushed
+#[derive(Clone)]
+pub(crate) struct QueuedPut {
+    zenoh_key: String,
+    payload: Vec<u8>,
+    attachment: Vec<(Vec<u8>, Vec<u8>)>,
+    priority: Priority,
+    // The timestamp (ms since UNIX epoch) after which the message is expired
+    expires_at: u64,
+}
+
+impl QueuedPut {
+    pub(crate) fn new(
+        zenoh_key: &str,
+        payload: &[u8],
+        attachment: &Attachment,
+        priority: Priority,
+        attributes: &UAttributes,
+    ) -> Self {
+        // The TTL counts from the creation time in the UUID, and 0 means no expiry
+        let expires_at = match attributes.ttl {
+            Some(ttl) if ttl > 0 => (attributes.id.msb >> 16).saturating_add(u64::from(ttl)),
+            _ => NEVER_EXPIRES,
+        };
+        QueuedPut {
+            zenoh_key: zenoh_key.to_string(),
+            payload: payload.to_vec(),
+            attachment: attachment
+                .iter()
+                .map(|(key, value)| (key.as_slice().to_vec(), value.as_slice().to_vec()))
+                .collect(),
+            priority,
+            expires_at,
+        }
+    }
+
+    fn is_expired(&self, now: u64) -> bool {
+        self.expires_at < now
+    }
+
+    async fn put(&self, session: &Session) -> zenoh::Result<()> {
+        let mut attachment = AttachmentBuilder::new();
+        for (key, value) in &self.attachment {
+            attachment.insert(key.as_slice(), value.as_slice());
+        }
+        session
+            .put(self.zenoh_key.as_str(), self.payload.as_slice())
+            .priority(self.priority)
+            .with_attachment(attachment.build())
+            .res()
+            .await
+    }
+}
+
+// The record in the queue file starts with the chunk of its kind (u8) and sequence number (u64 LE).
+// The put record continues with
+// [key][payload][priority (u8) and expiry (u64 LE)][number of attachment entries (u32 LE)][entry key][entry value]...
+// and the trim record removes the puts up to its sequence number, since they're always removed from the front.
+// Each of them is a chunk prefixed with its length (u32 LE).
+const PUT_RECORD: u8 = 0;
+const TRIM_RECORD: u8 = 1;
+
+enum Record {
+    Put(u64, QueuedPut),
+    Trim(u64),
+}
+
+fn write_header(buf: &mut Vec<u8>, kind: u8, seq: u64) -> std::io::Result<()> {
+    let mut header = vec![kind];
+    header.extend_from_slice(&seq.to_le_bytes());
+    write_chunk(buf, &header)
+}
+
+fn put_record(seq: u64, put: &QueuedPut) -> std::io::Result<Vec<u8>> {
+    let mut buf = vec![];
+    write_put(&mut buf, seq, put)?;
+    Ok(buf)
+}
+
+fn trim_record(seq: u64) -> std::io::Result<Vec<u8>> {
+    let mut buf = vec![];
+    write_header(&mut buf, TRIM_RECORD, seq)?;
+    Ok(buf)
+}
+
+fn write_put(buf: &mut Vec<u8>, seq: u64, put: &QueuedPut) -> std::io::Result<()> {
+    write_header(buf, PUT_RECORD, seq)?;
+    write_chunk(buf, put.zenoh_key.as_bytes())?;
+    write_chunk(buf, &put.payload)?;
+    let mut meta = vec![put.priority as u8];
+    meta.extend_from_slice(&put.expires_at.to_le_bytes());
+    write_chunk(buf, &meta)?;
+    let entries =
+        u32::try_from(put.attachment.len()).map_err(|_| invalid_data("too many entries"))?;
+    write_chunk(buf, &entries.to_le_bytes())?;
+    for (key, value) in &put.attachment {
+        write_chunk(buf, key)?;
+        write_chunk(buf, value)?;
+    }
+    Ok(())
+}
+
+// The chunk in the middle of the record, which can't be the end
+fn read_next_chunk(reader: &mut impl Read) -> std::io::Result<Vec<u8>> {
+    read_chunk(reader)?.ok_or_else(|| ErrorKind::UnexpectedEof.into())
+}
+
+fn read_put(reader: &mut impl Read) -> std::io::Result<QueuedPut> {
+    let zenoh_key =
+        String::from_utf8(read_next_chunk(reader)?).map_err(|_| invalid_data("invalid key"))?;
+    let payload = read_next_chunk(reader)?;
+    let meta = read_next_chunk(reader)?;
+    let Some((&priority, expires_at)) = meta.split_first() else {
+        return Err(invalid_data("empty priority and expiry"));
+    };
+    let priority = Priority::try_from(priority).map_err(|_| invalid_data("invalid priority"))?;
+    let expires_at = <[u8; 8]>::try_from(expires_at).map_err(|_| invalid_data("invalid expiry"))?;
+    let entries = <[u8; 4]>::try_from(read_next_chunk(reader)?.as_slice())
+        .map_err(|_| invalid_data("invalid number of entries"))?;
+    let mut attachment = vec![];
+    for _ in 0..u32::from_le_bytes(entries) {
+        attachment.push((read_next_chunk(reader)?, read_next_chunk(reader)?));
+    }
+    Ok(QueuedPut {
+        zenoh_key,
+        payload,
+        attachment,
+        priority,
+        expires_at: u64::from_le_bytes(expires_at),
+    })
+}
+
+fn read_record(reader: &mut impl Read) -> std::io::Result<Option<Record>> {
+    let Some(header) = read_chunk(reader)? else {
+        return Ok(None);
+    };
+    let Some((&kind, seq)) = header.split_first() else {
+        return Err(invalid_data("empty record header"));
+    };
+    let seq = u64::from_le_bytes(
+        <[u8; 8]>::try_from(seq).map_err(|_| invalid_data("invalid sequence number"))?,
+    );
+    match kind {
+        PUT_RECORD => Ok(Some(Record::Put(seq, read_put(reader)?))),
+        TRIM_RECORD => Ok(Some(Record::Trim(seq))),
+        _ => Err(invalid_data("unknown record")),
+    }
+}
+
+#[derive(Default)]
+struct QueueState {
+    // Each put has the sequence number, so the flushed one can be removed after it's put
+    puts: VecDeque<(u64, QueuedPut)>,
+    next_seq: u64,
+    // The records appended to the file since it's compacted
+    records_in_file: usize,
+}
+
+struct QueueStore {
+    capacity: usize,
+    file: Option<FileLog>,
+    state: Mutex<QueueState>,
+    dropped: AtomicU64,
+    metrics: Arc<Metrics>,
+}
+
+impl QueueStore {
+    fn open(config: &OfflineQueueConfig, metrics: Arc<Metrics>) -> Result<Self, UStatus> {
+        let mut store = QueueStore {
+            capacity: config.capacity,
+            file: None,
+            state: Mutex::new(QueueState::default()),
+            dropped: AtomicU64::new(0),
+            metrics,
+        };
+        let Some(file_path) = &config.file_path else {
+            return Ok(store);
+        };
+
+        // Load the messages queued before the restart
+        match File::open(file_path) {
+            Ok(file) => {
+                let mut reader = BufReader::new(file);
+                let mut state = store.state.lock().unwrap();
+                loop {
+                    match read_record(&mut reader) {
+                        Ok(Some(Record::Put(seq, put))) => {
+                            state.next_seq = seq;
+                            store.insert(&mut state, put);
+                        }
+                        Ok(Some(Record::Trim(seq))) => {
+                            state.puts.retain(|(put_seq, _)| *put_seq > seq);
+                        }
+                        Ok(None) => break,
+                        Err(e) => {
+                            // Keep the messages loaded so far if the last record is truncated
+                            tracing::warn!("Stop loading the corrupted offline queue file: {e:?}");
+                            break;
+                        }
+                    }
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(io_error_to_ustatus(&e)),
+        }
+        // Rewrite the file with the messages still in the queue
+        store.file = Some(FileLog::open(file_path.clone()));
+        let mut state = store.state.lock().unwrap();
+        store.metrics.set_offline_queue_depth(state.puts.len());
+        store.compact(&mut state);
+        drop(state);
+        Ok(store)
+    }
+
+    // Replace the file with the puts in the queue
+    fn compact(&self, state: &mut QueueState) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        let mut buf = vec![];
+        for (seq, put) in &state.puts {
+            if let Err(e) = write_put(&mut buf, *seq, put) {
+                tracing::error!("Unable to compact the offline queue file: {e:?}");
+                return;
+            }
+        }
+        file.rewrite(buf);
+        state.records_in_file = state.puts.len();
+    }
+
+    // Append the record to the file, and compact it once the removed puts take half of it
+    fn append(&self, state: &mut QueueState, record: std::io::Result<Vec<u8>>) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        match record {
+            Ok(record) => file.append(record),
+            Err(e) => {
+                tracing::error!("Unable to write the offline queue file: {e:?}");
+                return;
+            }
+        }
+        state.records_in_file += 1;
+        if state.records_in_file > 2 * state.puts.len().max(self.capacity) {
+            self.compact(state);
+        }
+    }
+
+    // Record the puts at the front up to `seq` are removed, either flushed or dropped
+    fn trim(&self, state: &mut QueueState, seq: u64) {
+        self.metrics.set_offline_queue_depth(state.puts.len());
+        if self.file.is_some() {
+            self.append(state, trim_record(seq));
+        }
+    }
+
+    // Wait until the file is written
+    fn sync(&self) {
+        if let Some(file) = &self.file {
+            file.sync();
+        }
+    }
+
+    fn on_drop(&self, reason: &str) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+        self.metrics.on_drop(reason);
+    }
+
+    fn len(&self) -> usize {
+        self.state.lock().unwrap().puts.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.state.lock().unwrap().puts.is_empty()
+    }
+
+    // Append the put, dropping the oldest ones beyond the capacity.
+    // Return the sequence number of the last dropped one if any.
+    fn insert(&self, state: &mut QueueState, put: QueuedPut) -> Option<u64> {
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        state.puts.push_back((seq, put));
+        let mut dropped = None;
+        while state.puts.len() > self.capacity {
+            dropped = state.puts.pop_front().map(|(seq, _)| seq);
+            tracing::warn!("Drop the oldest message since the offline queue is full");
+            self.on_drop("overflow");
+        }
+        dropped
+    }
+
+    fn push(&self, put: QueuedPut) {
+        let mut state = self.state.lock().unwrap();
+        // Only the serialization is done here, and the file is written in the background
+        let record = self
+            .file
+            .is_some()
+            .then(|| put_record(state.next_seq, &put));
+        let dropped = self.insert(&mut state, put);
+        self.metrics.set_offline_queue_depth(state.puts.len());
+        if let Some(record) = record {
+            self.append(&mut state, record);
+        }
+        if let Some(dropped) = dropped {
+            self.trim(&mut state, dropped);
+        }
+    }
+
+    // Drop the expired messages at the front, and return the first one still valid
+    fn front(&self, now: u64) -> Option<(u64, QueuedPut)> {
+        let mut state = self.state.lock().unwrap();
+        let mut expired = None;
+        while state
+            .puts
+            .front()
+            .is_some_and(|(_, put)| put.is_expired(now))
+        {
+            expired = state.puts.pop_front().map(|(seq, _)| seq);
+            self.on_drop("expired");
+        }
+        if let Some(expired) = expired {
+            self.trim(&mut state, expired);
+        }
+        state.puts.front().cloned()
+    }
+
+    // Remove the flushed put, unless it's already dropped for the overflow meanwhile
+    fn remove(&self, seq: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state.puts.front().is_some_and(|(front, _)| *front == seq) {
+            state.puts.pop_front();
+            self.trim(&mut state, seq);
+        }
+    }
+
+    // Put the queued messages in order. Stop at the first one nobody can receive or failed,
+    // and retry it next time.
+    async fn flush(
+        &self,
+        session: &Session,
+        status: &ConnectivityStatus,
+        local_listeners: &LocalListeners,
+    ) {
+        while let Some((seq, put)) = self.front(to_timestamp(SystemTime::now())) {
+            if !has_route(status, local_listeners, &put.zenoh_key) {
+                break;
+            }
+            if let Err(e) = put.put(session).await {
+                tracing::warn!("Unable to flush the offline queue, retry later: {e:?}");
+                break;
+            }
+            self.remove(seq);
+        }
+    }
+}
+
+// Queue the messages while offline and flush them in the background
+pub(crate) struct OfflineQueue {
+    store: Arc<QueueStore>,
+    task: JoinHandle<()>,
+}
+
+impl Drop for OfflineQueue {
+    fn drop(&mut self) {
+        self.task.abort();
+        // The aborted task may still hold the store, so don't rely on dropping it to write the file
+        self.store.sync();
+    }
+}
+
+impl OfflineQueue {
+    // The session is held weakly, so flushing stops once it's dropped
+    pub(crate) fn start(
+        config: &OfflineQueueConfig,
+        session: Weak<Session>,
+        mut status: watch::Receiver<ConnectivityStatus>,
+        local_listeners: LocalListeners,
+        metrics: Arc<Metrics>,
+    ) -> Result<Self, UStatus> {
+        let store = Arc::new(QueueStore::open(config, metrics)?);
+        let flush_store = store.clone();
+        let task = CB_RUNTIME.spawn(async move {
+            loop {
+                // Stop once the connectivity monitor is gone with the client
+                if let Ok(Err(_)) =
+                    tokio::time::timeout(FLUSH_RETRY_INTERVAL, status.changed()).await
+                {
+                    break;
+                }
+                let Some(session) = session.upgrade() else {
+                    break;
+                };
+                if !flush_store.is_empty() {
+                    let current = status.borrow_and_update().clone();
+                    flush_store
+                        .flush(&session, &current, &local_listeners)
+                        .await;
+                }
+            }
+        });
+        Ok(OfflineQueue { store, task })
+    }
+
+    pub(crate) fn push(&self, put: QueuedPut) {
+        self.store.push(put);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.store.is_empty()
+    }
+}
+
+impl UPClientZenoh {
+    /// The number of messages held in the offline queue. Always 0 if the queue is not enabled.
+    #[must_use]
+    pub fn offline_queue_depth(&self) -> usize {
+        self.offline_queue
+            .as_ref()
+            .map_or(0, |queue| queue.store.len())
+    }
+
+    /// The number of messages dropped from the offline queue, either expired or overflowed.
+    #[must_use]
+    pub fn offline_queue_dropped(&self) -> u64 {
+        self.offline_queue
+            .as_ref()
+            .map_or(0, |queue| queue.store.dropped.load(Ordering::Relaxed))
+    }
+}
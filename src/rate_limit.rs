@@ -0,0 +1,376 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+//! Flood protection of the listeners registered by `register_listener`.
+//!
+//! Enable it with `UPClientZenohBuilder::with_rate_limit`. The received Publish, Notification and Request
+//! messages are admitted by the token buckets of their source `UUri` and of the listener, and by the global
+//! cap on the in-flight listener callbacks. The dropped message never reaches the listener, and the listener
+//! gets `UCode::RESOURCE_EXHAUSTED` from `on_error` once when it starts dropping, not for every dropped message.
+//! The dropped request is never responded, so the requester times out.
+//!
+//! The messages are admitted before their signature is verified and their payload is decrypted, so the flood
+//! costs no cryptography. The `on_error` callbacks not finished yet are capped for each listener.
+use crate::{
+    error::TransportError, key_mapping::uri_to_zenoh_key, metrics::Metrics, UPClientZenoh,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+use up_rust::UAttributes;
+
+// Forget the least recently used sources once there are too many of them,
+// so the spoofed sources can't exhaust the memory
+const MAX_SOURCE_BUCKETS: usize = 4096;
+// The recent samples remembered to take the token of their source only once for all the listeners
+const MAX_RECENT_SAMPLES: usize = 1024;
+// The error callbacks of a listener not finished yet, so the flood of the invalid messages can't spawn
+// the unbounded tasks. The errors beyond it are only logged.
+const MAX_ERROR_CALLBACKS: usize = 16;
+
+/// The token bucket: `burst` messages at most at once, refilled by `per_second` messages every second.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    per_second: f64,
+    burst: f64,
+}
+
+impl RateLimit {
+    /// Allow `per_second` messages every second on average, with bursts of `burst` messages.
+    #[must_use]
+    pub fn new(per_second: u32, burst: u32) -> Self {
+        RateLimit {
+            per_second: f64::from(per_second),
+            burst: f64::from(burst.max(1)),
+        }
+    }
+}
+
+/// The limits of the received messages. Nothing is limited by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RateLimitConfig {
+    per_source: Option<RateLimit>,
+    per_listener: Option<RateLimit>,
+    max_in_flight: Option<usize>,
+}
+
+impl RateLimitConfig {
+    /// Create the configuration without any limit.
+    #[must_use]
+    pub fn new() -> Self {
+        RateLimitConfig::default()
+    }
+
+    /// Limit the messages from each source `UUri`, shared by all the listeners.
+    #[must_use]
+    pub fn with_per_source(mut self, limit: RateLimit) -> Self {
+        self.per_source = Some(limit);
+        self
+    }
+
+    /// Limit the messages delivered to each registered listener.
+    #[must_use]
+    pub fn with_per_listener(mut self, limit: RateLimit) -> Self {
+        self.per_listener = Some(limit);
+        self
+    }
+
+    /// Limit the listener callbacks spawned and not finished yet across all the listeners.
+    #[must_use]
+    pub fn with_max_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = Some(max_in_flight);
+        self
+    }
+}
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: &RateLimit) -> Self {
+        TokenBucket {
+            tokens: limit.burst,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, limit: &RateLimit) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.per_second).min(limit.burst);
+        self.last_refill = now;
+    }
+
+    fn try_take(&mut self, limit: &RateLimit) -> bool {
+        self.refill(limit);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+// The map keeping `capacity` entries at most, which evicts the least recently used one
+struct LruMap<V> {
+    capacity: usize,
+    entries: HashMap<String, (V, u64)>,
+    // The keys ordered by their last use
+    order: BTreeMap<u64, String>,
+    next_use: u64,
+}
+
+impl<V> LruMap<V> {
+    fn new(capacity: usize) -> Self {
+        LruMap {
+            capacity,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            next_use: 0,
+        }
+    }
+
+    // Use the entry of `key`, which is created by `default` if missing
+    fn get_or_insert_with(&mut self, key: String, default: impl FnOnce() -> V) -> &mut V {
+        let last_use = self.next_use;
+        self.next_use += 1;
+        if let Some((_, used)) = self.entries.get_mut(&key) {
+            self.order.remove(used);
+            *used = last_use;
+        } else {
+            if self.entries.len() >= self.capacity {
+                if let Some((_, oldest)) = self.order.pop_first() {
+                    self.entries.remove(&oldest);
+                }
+            }
+            self.entries.insert(key.clone(), (default(), last_use));
+        }
+        self.order.insert(last_use, key.clone());
+        &mut self.entries.get_mut(&key).unwrap().0
+    }
+}
+
+// Whether the sample is admitted by its source, and the listeners which received it
+#[derive(Default)]
+struct RecentSample {
+    admitted: Option<bool>,
+    listeners: Vec<u64>,
+}
+
+struct SourceState {
+    buckets: LruMap<TokenBucket>,
+    samples: LruMap<RecentSample>,
+}
+
+// The state shared by all the listeners of UPClientZenoh
+pub(crate) struct RateLimiter {
+    config: RateLimitConfig,
+    sources: Mutex<SourceState>,
+    in_flight: Arc<AtomicUsize>,
+    // The callbacks not limited, which are counted only for `close` to wait for them
+    unlimited_in_flight: Arc<AtomicUsize>,
+    dropped: AtomicU64,
+    next_listener_id: AtomicU64,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(config: RateLimitConfig) -> Self {
+        RateLimiter {
+            config,
+            sources: Mutex::new(SourceState {
+                buckets: LruMap::new(MAX_SOURCE_BUCKETS),
+                samples: LruMap::new(MAX_RECENT_SAMPLES),
+            }),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            unlimited_in_flight: Arc::new(AtomicUsize::new(0)),
+            dropped: AtomicU64::new(0),
+            next_listener_id: AtomicU64::new(0),
+        }
+    }
+
+    // The callbacks spawned and not finished yet, limited or not
+    pub(crate) fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire) + self.unlimited_in_flight.load(Ordering::Acquire)
+    }
+
+    // Count the callback not limited by the rate limits, e.g. the last value, discovery, connectivity
+    // and response callbacks, so `close` waits for it as well
+    pub(crate) fn track(&self) -> InFlight {
+        InFlight::track(&self.unlimited_in_flight)
+    }
+
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    // Take the token of the source once for each sample, however many listeners it matches.
+    // The sample is identified by its source and ID, so another source can't take the place of it
+    // by reusing the ID. The same sample received again by the same listener is a new sample.
+    fn admit_source(&self, attributes: &UAttributes, listener_id: u64) -> bool {
+        let Some(limit) = &self.config.per_source else {
+            return true;
+        };
+        let source = uri_to_zenoh_key("", attributes.source.get_or_default());
+        let mut sources = self.sources.lock().unwrap();
+        let SourceState { buckets, samples } = &mut *sources;
+        let sample = samples
+            .get_or_insert_with(format!("{source} {}", attributes.id), RecentSample::default);
+        if sample.listeners.contains(&listener_id) {
+            *sample = RecentSample::default();
+        }
+        sample.listeners.push(listener_id);
+        if let Some(admitted) = sample.admitted {
+            return admitted;
+        }
+        let admitted = buckets
+            .get_or_insert_with(source, || TokenBucket::new(limit))
+            .try_take(limit);
+        sample.admitted = Some(admitted);
+        admitted
+    }
+
+    fn take_in_flight(&self) -> Option<InFlight> {
+        InFlight::take(
+            &self.in_flight,
+            self.config.max_in_flight.unwrap_or(usize::MAX),
+        )
+    }
+}
+
+// Count the callback as in-flight until it's dropped
+pub(crate) struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    // Count one more callback unless `max` callbacks are in flight already
+    fn take(counter: &Arc<AtomicUsize>, max: usize) -> Option<Self> {
+        counter
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (n < max).then_some(n + 1)
+            })
+            .ok()?;
+        Some(InFlight(counter.clone()))
+    }
+
+    // Count one more callback without any limit
+    fn track(counter: &Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::AcqRel);
+        InFlight(counter.clone())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+// The admission of the messages delivered to one listener
+pub(crate) struct ListenerLimiter {
+    shared: Arc<RateLimiter>,
+    id: u64,
+    metrics: Arc<Metrics>,
+    bucket: Option<Mutex<TokenBucket>>,
+    // Report RESOURCE_EXHAUSTED only when the listener starts dropping
+    dropping: AtomicBool,
+    errors_in_flight: Arc<AtomicUsize>,
+}
+
+impl ListenerLimiter {
+    pub(crate) fn new(shared: Arc<RateLimiter>, metrics: Arc<Metrics>) -> Self {
+        let bucket = shared
+            .config
+            .per_listener
+            .as_ref()
+            .map(|limit| Mutex::new(TokenBucket::new(limit)));
+        let id = shared.next_listener_id.fetch_add(1, Ordering::Relaxed);
+        ListenerLimiter {
+            shared,
+            id,
+            metrics,
+            bucket,
+            dropping: AtomicBool::new(false),
+            errors_in_flight: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    // Admit the message and return the in-flight guard to be kept by the callback.
+    // Otherwise, return the error to report, or None if it has been reported since the listener started dropping.
+    pub(crate) fn admit(
+        &self,
+        attributes: &UAttributes,
+    ) -> Result<InFlight, Option<TransportError>> {
+        let result = self
+            .admit_rate(attributes)
+            .and_then(|()| self.shared.take_in_flight().ok_or("in_flight"));
+        match result {
+            Ok(in_flight) => {
+                self.dropping.store(false, Ordering::Relaxed);
+                Ok(in_flight)
+            }
+            Err(reason) => {
+                self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+                self.metrics.on_drop(reason);
+                if self.dropping.swap(true, Ordering::Relaxed) {
+                    Err(None)
+                } else {
+                    Err(Some(TransportError::RateLimited(format!(
+                        "Dropping the messages exceeding the {reason} limit"
+                    ))))
+                }
+            }
+        }
+    }
+
+    // Admit the error callback and return the in-flight guards to be kept by it, or None to drop the error.
+    // It's capped per listener only, so RESOURCE_EXHAUSTED is reported even if the in-flight limit is hit.
+    pub(crate) fn admit_error(&self) -> Option<(InFlight, InFlight)> {
+        let Some(error) = InFlight::take(&self.errors_in_flight, MAX_ERROR_CALLBACKS) else {
+            self.metrics.on_drop("error");
+            return None;
+        };
+        Some((error, InFlight::track(&self.shared.in_flight)))
+    }
+
+    // Return the name of the exceeded limit if any
+    fn admit_rate(&self, attributes: &UAttributes) -> Result<(), &'static str> {
+        if !self.shared.admit_source(attributes, self.id) {
+            return Err("source");
+        }
+        if !self.admit_listener() {
+            return Err("listener");
+        }
+        Ok(())
+    }
+
+    fn admit_listener(&self) -> bool {
+        match (&self.bucket, &self.shared.config.per_listener) {
+            (Some(bucket), Some(limit)) => bucket.lock().unwrap().try_take(limit),
+            _ => true,
+        }
+    }
+}
+
+impl UPClientZenoh {
+    /// The number of the received messages dropped by the rate limits so far.
+    #[must_use]
+    pub fn dropped_messages(&self) -> u64 {
+        self.rate_limiter.dropped()
+    }
+}
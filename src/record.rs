@@ -0,0 +1,241 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+//! Record the uProtocol traffic over Zenoh into a log file and replay it later.
+//!
+//! The log file is a sequence of records, each of them is
+//! `[receive time in µs since UNIX epoch (u64 LE)][message length (u32 LE)][UMessage in protobuf]`.
+use crate::{
+    file_log::{read_chunk, write_chunk, FileLog},
+    key_mapping::uri_to_zenoh_key,
+    UPClientZenoh,
+};
+use protobuf::Message;
+use std::{
+    fs::File,
+    io::{BufReader, ErrorKind, Read},
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::time::Instant;
+use up_rust::{UCode, UMessage, UStatus, UTransport, UUri};
+use zenoh::{
+    prelude::{r#async::*, Sample},
+    subscriber::Subscriber,
+};
+
+// The key expression covering all the Publish and Notification messages
+const RECORD_KEY_ALL: &str = "up/**";
+
+fn io_error_to_ustatus(e: &std::io::Error) -> UStatus {
+    let msg = format!("Unable to access the record file: {e:?}");
+    tracing::error!(code = ?UCode::INTERNAL, "{msg}");
+    UStatus::fail_with_code(UCode::INTERNAL, msg)
+}
+
+fn write_entry(timestamp: u64, msg: &UMessage) -> std::io::Result<Vec<u8>> {
+    let msg_bytes = msg
+        .write_to_bytes()
+        .map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?;
+    let mut entry = timestamp.to_le_bytes().to_vec();
+    write_chunk(&mut entry, &msg_bytes)?;
+    Ok(entry)
+}
+
+fn read_entry(reader: &mut impl Read) -> std::io::Result<Option<(u64, UMessage)>> {
+    let mut timestamp = [0u8; 8];
+    match reader.read_exact(&mut timestamp) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let Some(msg) = read_chunk(reader)? else {
+        return Err(ErrorKind::UnexpectedEof.into());
+    };
+    let msg = UMessage::parse_from_bytes(&msg)
+        .map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?;
+    Ok(Some((u64::from_le_bytes(timestamp), msg)))
+}
+
+/// Read all the recorded messages with their receive time (µs since UNIX epoch) from the log file.
+///
+/// # Errors
+/// Will return `Err` if unable to read the log file or the log file is corrupted
+pub fn read_record_file(path: impl AsRef<Path>) -> Result<Vec<(u64, UMessage)>, UStatus> {
+    let file = File::open(path).map_err(|e| io_error_to_ustatus(&e))?;
+    let mut reader = BufReader::new(file);
+    let mut entries = vec![];
+    while let Some(entry) = read_entry(&mut reader).map_err(|e| io_error_to_ustatus(&e))? {
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Keep recording the Publish and Notification messages into the log file until dropped.
+/// RPC requests and responses are not recorded.
+pub struct Recorder {
+    subscriber: Option<Subscriber<'static, ()>>,
+    // Written in the background, so the Zenoh callback never blocks on the disk I/O
+    log: Arc<FileLog>,
+}
+
+// Stop recording, and finish writing the recorded messages, so the log file is complete once dropped
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        drop(self.subscriber.take());
+        self.log.sync();
+    }
+}
+
+/// Options of replaying the log file.
+#[derive(Clone, Debug)]
+pub struct ReplayOptions {
+    speed: f64,
+    source_filter: Option<UUri>,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        ReplayOptions {
+            speed: 1.0,
+            source_filter: None,
+        }
+    }
+}
+
+impl ReplayOptions {
+    /// Replay `speed` times faster than the original timing. The default is 1.0.
+    /// `replay` fails with `UCode::INVALID_ARGUMENT` if `speed` is not a positive number.
+    #[must_use]
+    pub fn with_speed(mut self, speed: f64) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Only replay the messages whose source matches `source_filter`, which can contain wildcard.
+    #[must_use]
+    pub fn with_source_filter(mut self, source_filter: UUri) -> Self {
+        self.source_filter = Some(source_filter);
+        self
+    }
+}
+
+impl UPClientZenoh {
+    /// Start recording the Publish and Notification messages reachable through Zenoh into
+    /// the log file at `path`. The file is overwritten if it exists.
+    ///
+    /// # Errors
+    /// Will return `Err` if unable to create the log file or declare the subscriber in Zenoh
+    pub async fn start_recording(&self, path: impl AsRef<Path>) -> Result<Recorder, UStatus> {
+        // Truncate the file here, so the failure to create it is returned
+        File::create(path.as_ref()).map_err(|e| io_error_to_ustatus(&e))?;
+        let log = Arc::new(FileLog::open(path.as_ref().to_path_buf()));
+        let callback_log = log.clone();
+        let inbound_check = self.inbound_check();
+        let callback = move |sample: Sample| {
+            let msg = match inbound_check.sample_to_umessage(&sample) {
+                Ok(msg) => msg,
+                Err(e) => {
+                    tracing::warn!("Unable to record the message from {}: {e}", sample.key_expr);
+                    return;
+                }
+            };
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| u64::try_from(d.as_micros()).unwrap_or(u64::MAX));
+            match write_entry(timestamp, &msg) {
+                Ok(entry) => callback_log.append(entry),
+                Err(e) => tracing::error!("Unable to encode the record: {e:?}"),
+            }
+        };
+        let subscriber = self
+            .session
+            .declare_subscriber(RECORD_KEY_ALL)
+            .callback(callback)
+            .res()
+            .await
+            .map_err(|e| {
+                let msg = format!("Unable to declare Zenoh subscriber for recording: {e:?}");
+                tracing::error!(code = ?UCode::INTERNAL, "{msg}");
+                UStatus::fail_with_code(UCode::INTERNAL, msg)
+            })?;
+        Ok(Recorder {
+            subscriber: Some(subscriber),
+            log,
+        })
+    }
+
+    /// Re-publish the messages recorded in the log file at `path` with `send`,
+    /// keeping the original timing between the messages. Return the number of messages sent.
+    ///
+    /// # Errors
+    /// Will return `Err` if the options are invalid or unable to read the log file
+    pub async fn replay(
+        &self,
+        path: impl AsRef<Path>,
+        options: &ReplayOptions,
+    ) -> Result<usize, UStatus> {
+        if !(options.speed.is_finite() && options.speed > 0.0) {
+            let msg = format!("The replay speed should be positive: {}", options.speed);
+            tracing::error!(code = ?UCode::INVALID_ARGUMENT, "{msg}");
+            return Err(UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg));
+        }
+        let entries = read_record_file(path)?;
+        let source_filter = options
+            .source_filter
+            .as_ref()
+            .map(|uri| uri_to_zenoh_key(&self.authority_name, uri));
+        let source_filter = source_filter
+            .as_deref()
+            .map(keyexpr::new)
+            .transpose()
+            .map_err(|e| {
+                let msg = format!("Invalid source filter: {e:?}");
+                tracing::error!(code = ?UCode::INVALID_ARGUMENT, "{msg}");
+                UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg)
+            })?;
+
+        // Only the matching messages are timed, so the unrelated traffic isn't waited for
+        let entries = entries
+            .into_iter()
+            .filter(|(_, msg)| {
+                source_filter.map_or(true, |source_filter| {
+                    let source = uri_to_zenoh_key(
+                        &self.authority_name,
+                        msg.attributes.source.get_or_default(),
+                    );
+                    keyexpr::new(&source).is_ok_and(|source| source_filter.intersects(source))
+                })
+            })
+            .collect::<Vec<_>>();
+        let Some((first_timestamp, _)) = entries.first() else {
+            return Ok(0);
+        };
+        let first_timestamp = *first_timestamp;
+        let start = Instant::now();
+        let mut sent = 0;
+        for (timestamp, msg) in entries {
+            // Wait until the original timing (scaled by the speed). It saturates for a tiny speed
+            // instead of overflowing, i.e. the message is never sent.
+            let offset = Duration::from_micros(timestamp.saturating_sub(first_timestamp));
+            let offset = Duration::try_from_secs_f64(offset.as_secs_f64() / options.speed)
+                .unwrap_or(Duration::MAX);
+            tokio::time::sleep(offset.saturating_sub(start.elapsed())).await;
+            match self.send(msg).await {
+                Ok(()) => sent += 1,
+                Err(e) => tracing::warn!("Unable to replay the message: {e:?}"),
+            }
+        }
+        Ok(sent)
+    }
+}
@@ -10,82 +10,220 @@
  *
  * SPDX-License-Identifier: Apache-2.0
  ********************************************************************************/
-use crate::UPClientZenoh;
+use crate::{access_control::Operation, error::TransportError, UPClientZenoh};
 use async_trait::async_trait;
-use std::{string::ToString, time::Duration};
-use up_rust::{RpcClient, RpcClientResult, UAttributesError, UMessage, UMessageError, UUri};
-use zenoh::prelude::r#async::*;
+use std::{
+    string::ToString,
+    time::{Duration, Instant},
+};
+use up_rust::{RpcClient, RpcClientResult, UCode, UMessage, UMessageType, UStatus, UUri};
+use zenoh::{prelude::r#async::*, sample::Attachment};
+
+// The query timeout of Zenoh if `queries_default_timeout` isn't configured
+const ZENOH_QUERIES_DEFAULT_TIMEOUT: u64 = 10000;
+
+impl UPClientZenoh {
+    // The request without TTL keeps the default query timeout of the Zenoh configuration
+    pub(crate) fn query_timeout(&self, ttl: Option<u32>) -> Duration {
+        let timeout = ttl.map_or_else(
+            || {
+                self.session
+                    .config()
+                    .lock()
+                    .queries_default_timeout()
+                    .unwrap_or(ZENOH_QUERIES_DEFAULT_TIMEOUT)
+            },
+            u64::from,
+        );
+        Duration::from_millis(timeout)
+    }
+
+    /// Check whether any RPC server serves `method` now.
+    /// The method is known from the liveliness announced by `register_listener` on the server side,
+    /// so the server needs to register the request listener with the specific method `UUri`.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The `UUri` of the method, which can't contain wildcard.
+    ///
+    /// # Errors
+    /// Will return `Err` if `method` contains wildcard or unable to query the liveliness from Zenoh
+    pub async fn is_method_available(&self, method: &UUri) -> Result<bool, UStatus> {
+        let Some(liveliness_key) = self.method_liveliness_key(method) else {
+            let msg = "Unable to check the availability of the method with wildcard".to_string();
+            tracing::error!(code = ?UCode::INVALID_ARGUMENT, "{msg}");
+            return Err(UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg));
+        };
+        let replies = self
+            .session
+            .liveliness()
+            .get(liveliness_key)
+            .res()
+            .await
+            .map_err(|e| {
+                let msg = format!("Unable to query liveliness with Zenoh: {e:?}");
+                tracing::error!(code = ?UCode::INTERNAL, "{msg}");
+                UStatus::fail_with_code(UCode::INTERNAL, msg)
+            })?;
+        while let Ok(reply) = replies.recv_async().await {
+            if reply.sample.is_ok() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    // Fail with UNAVAILABLE if nobody serves the method, with one liveliness query at most:
+    // - RPC fail fast checks the method is served, wherever it is
+    // - send fail fast checks a router or peer is connected, or the method is served locally
+    // The method with wildcard is not looked up since it's not a specific method.
+    pub(crate) async fn check_method_available(&self, method: &UUri) -> Result<(), TransportError> {
+        let disconnected = self.send_fail_fast && !self.connectivity_status().is_connected();
+        if !self.rpc_fail_fast && !disconnected {
+            return Ok(());
+        }
+        if self.method_liveliness_key(method).is_none() {
+            return if disconnected {
+                Err(TransportError::Unavailable(format!(
+                    "No router or peer is connected to send the request to {method:?}"
+                )))
+            } else {
+                Ok(())
+            };
+        }
+        match self.is_method_available(method).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(TransportError::Unavailable(format!(
+                "No RPC server serves the method {method:?}"
+            ))),
+            Err(e) => Err(TransportError::zenoh(
+                "query liveliness",
+                e.message.unwrap_or_default(),
+            )),
+        }
+    }
+
+    /// Send the RPC request and wait for the response like `invoke_method`,
+    /// but fail with the typed `TransportError`, e.g. `TransportError::Timeout` if no response
+    /// arrives before the TTL (or the default query timeout of Zenoh without TTL), or `TransportError::Unavailable` if no RPC server replies.
+    ///
+    /// # Errors
+    /// Will return `Err` if the request is invalid or not authorized, unable to send it with Zenoh,
+    /// or no response arrives
+    pub async fn invoke_rpc(&self, request: UMessage) -> Result<UMessage, TransportError> {
+        self.check_open()?;
+        let mut attributes = *request
+            .attributes
+            .0
+            .clone()
+            .ok_or_else(|| TransportError::InvalidMessage("Empty UAttributes".to_string()))?;
+
+        // Only the local authority can be the source, like `send`
+        self.check_outbound_authority(&mut attributes)?;
 
-#[async_trait]
-impl RpcClient for UPClientZenoh {
-    // CY_TODO: Should remove method in the future
-    async fn invoke_method(&self, _method: UUri, request: UMessage) -> RpcClientResult {
         // Get Zenoh key
-        let source = *request.attributes.source.0.clone().ok_or_else(|| {
-            let msg = "attributes.source should not be empty".to_string();
-            log::error!("{msg}");
-            UMessageError::PayloadError(msg)
+        let source = *attributes.source.0.clone().ok_or_else(|| {
+            TransportError::InvalidMessage("attributes.source should not be empty".to_string())
         })?;
-        let zenoh_key = if let Some(sink) = request.attributes.sink.0.clone() {
+        let zenoh_key = if let Some(sink) = attributes.sink.0.clone() {
             self.to_zenoh_key_string(&source, Some(&sink))
         } else {
             self.to_zenoh_key_string(&source, None)
         };
 
-        // Create UAttributes and put into Zenoh user attachment
-        let attributes = *request.attributes.0.clone().ok_or_else(|| {
-            let msg = "Invalid UAttributes".to_string();
-            log::error!("{msg}");
-            UMessageError::AttributesValidationError(UAttributesError::ParsingError(msg))
-        })?;
-        let Ok(attachment) = UPClientZenoh::uattributes_to_attachment(&attributes) else {
-            let msg = "Unable to transform UAttributes to user attachment in Zenoh".to_string();
-            log::error!("{msg}");
-            return Err(UMessageError::AttributesValidationError(
-                UAttributesError::ParsingError(msg),
-            ));
-        };
+        // Check the permission, and put UAttributes into Zenoh user attachment
+        self.authorize(
+            Operation::Send,
+            UMessageType::UMESSAGE_TYPE_REQUEST,
+            &source,
+            attributes.sink.as_ref(),
+        )?;
+        let mut attachment = UPClientZenoh::uattributes_to_attachment(&attributes)
+            .map_err(TransportError::AttachmentEncode)?;
+
+        // Fail fast if nobody serves the method
+        self.check_method_available(attributes.sink.get_or_default())
+            .await?;
 
         // Get the data from UPayload
-        let value = if let Some(payload) = request.payload {
-            Value::new(payload.to_vec().into())
-        } else {
-            Value::new(vec![].into())
-        };
+        let payload = request
+            .payload
+            .map(|payload| payload.to_vec())
+            .unwrap_or_default();
+        let payload = self.seal_payload(&mut attachment, &attributes, &payload)?;
+        let value = Value::new(payload.to_vec().into());
+
+        // Send the query, and remember its deadline to tell the timeout from the replied error
+        let timeout = self.query_timeout(attributes.ttl);
+        let deadline = Instant::now() + timeout;
+        let mut rpc_guard = self.metrics.start_rpc(deadline);
+        let result = self
+            .query_response(&zenoh_key, value, attachment.build(), timeout, deadline)
+            .await;
+        rpc_guard.finish(&result);
+        result
+    }
 
-        // Send the query
-        let mut getbuilder = self
+    // Send the query and wait for the first reply
+    async fn query_response(
+        &self,
+        zenoh_key: &str,
+        value: Value,
+        attachment: Attachment,
+        timeout: Duration,
+        deadline: Instant,
+    ) -> Result<UMessage, TransportError> {
+        let replies = self
             .session
-            .get(&zenoh_key)
+            .get(zenoh_key)
             .with_value(value)
-            .with_attachment(attachment.build())
-            .target(QueryTarget::BestMatching);
-        if let Some(ttl) = request.attributes.ttl {
-            getbuilder = getbuilder.timeout(Duration::from_millis(u64::from(ttl)));
-        }
-        let Ok(replies) = getbuilder.res().await else {
-            let msg = "Error while sending Zenoh query".to_string();
-            log::error!("{msg}");
-            return Err(UMessageError::PayloadError(msg));
-        };
+            .with_attachment(attachment)
+            .target(QueryTarget::BestMatching)
+            .timeout(timeout)
+            .res()
+            .await
+            .map_err(|e| TransportError::zenoh("get", e))?;
 
-        // Receive the reply
+        // Receive the reply. The channel is closed without any reply if no RPC server matches.
         let Ok(reply) = replies.recv_async().await else {
-            let msg = "Error while receiving Zenoh reply".to_string();
-            log::error!("{msg}");
-            return Err(UMessageError::PayloadError(msg));
+            return Err(if Instant::now() >= deadline {
+                TransportError::Timeout
+            } else {
+                TransportError::Unavailable(format!("No RPC server replies to {zenoh_key}"))
+            });
         };
         match reply.sample {
-            Ok(sample) => Ok(UMessage {
-                attributes: Some(attributes).into(),
-                payload: Some(sample.payload.contiguous().to_vec().into()),
-                ..Default::default()
-            }),
-            Err(e) => {
-                let msg = format!("Error while parsing Zenoh reply: {e:?}");
-                log::error!("{msg}");
-                Err(UMessageError::PayloadError(msg))
+            Ok(sample) => {
+                // Verify and decrypt the response like the response listener does
+                let attachment = sample
+                    .attachment()
+                    .ok_or_else(|| TransportError::AttachmentDecode("No attachment".to_string()))?;
+                let reply_attributes = UPClientZenoh::attachment_to_uattributes(attachment)
+                    .map_err(|e| TransportError::AttachmentDecode(format!("{e:?}")))?;
+                let payload = self.inbound_check().check(
+                    sample.key_expr.as_str(),
+                    attachment,
+                    &reply_attributes,
+                    &sample.payload.contiguous(),
+                )?;
+                Ok(UMessage {
+                    attributes: Some(reply_attributes).into(),
+                    payload: Some(payload.into()),
+                    ..Default::default()
+                })
             }
+            Err(e) => Err(TransportError::from_reply_error(&e, deadline)),
         }
     }
 }
+
+#[async_trait]
+impl RpcClient for UPClientZenoh {
+    // CY_TODO: Should remove method in the future
+    async fn invoke_method(&self, _method: UUri, request: UMessage) -> RpcClientResult {
+        self.invoke_rpc(request).await.map_err(|e| {
+            tracing::error!(code = ?e.code(), error = %e);
+            e.into()
+        })
+    }
+}
@@ -0,0 +1,132 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+use crate::{error::TransportError, UPClientZenoh};
+use std::{
+    mem,
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+use tokio::time::{sleep, Instant};
+use up_rust::{UCode, UStatus};
+use zenoh::prelude::r#async::*;
+
+const CLOSE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+impl UPClientZenoh {
+    /// Shut down `UPClientZenoh` gracefully.
+    ///
+    /// All the listeners are unregistered, the pending RPC requests are answered with an error,
+    /// and the in-flight callbacks are waited until `timeout`. It takes `Arc<UPClientZenoh>` to be able to
+    /// close the client shared with `UStreamer`, and the Zenoh session is closed once the other holders of it
+    /// have dropped it before `timeout`. The Zenoh session on the runtime given by `with_runtime` is only dropped,
+    /// since closing it would close the runtime shared with the other sessions. Once closed, `send` and
+    /// `register_listener` fail with `UCode::FAILED_PRECONDITION` for the other holders. The messages still in the offline queue are discarded unless it's
+    /// stored in the file.
+    ///
+    /// # Errors
+    /// Will return `Err` with `UCode::DEADLINE_EXCEEDED` if the callbacks are still running after `timeout`,
+    /// `UCode::FAILED_PRECONDITION` if `UPClientZenoh` or its Zenoh session is still held after `timeout`,
+    /// which is then closed when the last holder drops it, or `UCode::INTERNAL` if unable to close the Zenoh session.
+    pub async fn close(self: Arc<Self>, timeout: Duration) -> Result<(), UStatus> {
+        let deadline = Instant::now() + timeout;
+        self.closed.store(true, Ordering::SeqCst);
+
+        // Stop receiving. Dropping the subscribers, queryables and liveliness tokens undeclares them.
+        self.subscriber_map.lock().unwrap().clear();
+        self.queryable_map.lock().unwrap().clear();
+        self.querying_subscriber_map.lock().unwrap().clear();
+        self.discovery_subscriber_map.lock().unwrap().clear();
+        self.liveliness_token_map.lock().unwrap().clear();
+        self.publication_cache_map.lock().unwrap().clear();
+        self.rpc_callback_map.lock().unwrap().clear();
+
+        // Answer the pending requests, so the requesters don't wait until the TTL
+        let queries = mem::take(&mut *self.query_map.lock().unwrap());
+        self.metrics.set_pending_queries(0);
+        for (reqid, query) in queries {
+            let reply = Err(Value::from("The RPC server is closed"));
+            if let Err(e) = query.reply(reply).res().await {
+                tracing::warn!("Unable to answer the pending request {reqid}: {e:?}");
+            }
+        }
+
+        // Wait for the in-flight callbacks
+        while self.rate_limiter.in_flight() > 0 && Instant::now() < deadline {
+            sleep(CLOSE_POLL_INTERVAL).await;
+        }
+        let in_flight = self.rate_limiter.in_flight();
+        if in_flight > 0 {
+            let msg = format!("{in_flight} callbacks are still running after {timeout:?}");
+            tracing::error!(code = ?UCode::DEADLINE_EXCEEDED, "{msg}");
+            return Err(UStatus::fail_with_code(UCode::DEADLINE_EXCEEDED, msg));
+        }
+
+        // Drop the rest once nobody else holds UPClientZenoh
+        let Some(upclient) = try_unwrap_until(self, deadline).await else {
+            let msg = format!("UPClientZenoh is still held by others after {timeout:?}");
+            tracing::error!(code = ?UCode::FAILED_PRECONDITION, "{msg}");
+            return Err(UStatus::fail_with_code(UCode::FAILED_PRECONDITION, msg));
+        };
+        let UPClientZenoh {
+            session,
+            shared_runtime,
+            history,
+            _authority_token: authority_token,
+            connectivity,
+            offline_queue,
+            ..
+        } = upclient;
+        drop((history, authority_token, connectivity, offline_queue));
+
+        // Close the Zenoh session once the background tasks have released it
+        let Some(session) = try_unwrap_until(session, deadline).await else {
+            let msg = format!("Zenoh session is still held by others after {timeout:?}");
+            tracing::error!(code = ?UCode::FAILED_PRECONDITION, "{msg}");
+            return Err(UStatus::fail_with_code(UCode::FAILED_PRECONDITION, msg));
+        };
+        if shared_runtime {
+            drop(session);
+            return Ok(());
+        }
+        session.close().res().await.map_err(|e| {
+            let msg = format!("Unable to close Zenoh session: {e:?}");
+            tracing::error!(code = ?UCode::INTERNAL, "{msg}");
+            UStatus::fail_with_code(UCode::INTERNAL, msg)
+        })
+    }
+}
+
+impl UPClientZenoh {
+    // Fail the operations after `close`
+    pub(crate) fn check_open(&self) -> Result<(), TransportError> {
+        if self.closed.load(Ordering::SeqCst) {
+            Err(TransportError::Closed)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// Take the value out of `arc` once the other holders drop it, or give up after `deadline`
+async fn try_unwrap_until<T>(mut arc: Arc<T>, deadline: Instant) -> Option<T> {
+    loop {
+        match Arc::try_unwrap(arc) {
+            Ok(value) => return Some(value),
+            Err(shared) if Instant::now() < deadline => {
+                arc = shared;
+                sleep(CLOSE_POLL_INTERVAL).await;
+            }
+            Err(_) => return None,
+        }
+    }
+}
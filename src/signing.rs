@@ -0,0 +1,176 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+//! End-to-end integrity of the messages with HMAC-SHA256 signatures.
+//!
+//! Enable it with `UPClientZenohBuilder::with_signing`. The sender signs the serialized `UAttributes`
+//! plus the payload with the key of its own authority, and carries the signature in the Zenoh attachment
+//! as the entries keyed by `sig` and `signer`. The receiver verifies it with the key of the signer
+//! before calling the listeners, and the signer must be the source authority of the message.
+//!
+//! The signature is verified for the listeners of `register_listener`, the RPC responses,
+//! the last values, the history and the recording.
+//!
+//! Only the messages whose source authority is the local one are signed. The messages sent on behalf
+//! of another authority, e.g. forwarded by `UStreamer`, are sent unsigned, so the receivers accept them
+//! only with `SignaturePolicy::AcceptUnsigned`.
+use crate::error::TransportError;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::{collections::HashMap, fs, path::Path};
+use up_rust::{UAttributes, UCode, UStatus};
+use zenoh::sample::{Attachment, AttachmentBuilder};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const SIGNATURE_KEY: &str = "sig";
+const SIGNER_KEY: &str = "signer";
+
+/// How to handle the received message which can't be verified.
+/// The message with the wrong signature is always rejected.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SignaturePolicy {
+    /// Reject the unsigned messages and the messages signed by the unknown authority
+    #[default]
+    RequireSigned,
+    /// Accept the unsigned messages and the messages signed by the unknown authority
+    AcceptUnsigned,
+}
+
+/// The keys and the policy of signing.
+#[derive(Clone, Default)]
+pub struct SigningConfig {
+    // The key of each authority
+    keys: HashMap<String, Vec<u8>>,
+    policy: SignaturePolicy,
+}
+
+impl SigningConfig {
+    /// Create the configuration without any key.
+    #[must_use]
+    pub fn new() -> Self {
+        SigningConfig::default()
+    }
+
+    /// Use `key` to sign and verify the messages from `authority`.
+    /// The messages are signed only if the key of the local authority is given.
+    #[must_use]
+    pub fn with_key(mut self, authority: impl Into<String>, key: impl Into<Vec<u8>>) -> Self {
+        self.keys.insert(authority.into(), key.into());
+        self
+    }
+
+    /// Read the key of `authority` from the file at `path`. The whole content of the file is the key.
+    ///
+    /// # Errors
+    /// Will return `Err` if unable to read the file or the file is empty
+    pub fn with_key_file(
+        self,
+        authority: impl Into<String>,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, UStatus> {
+        let key = fs::read(path).map_err(|e| {
+            let msg = format!("Unable to read the signing key file: {e:?}");
+            tracing::error!(code = ?UCode::INVALID_ARGUMENT, "{msg}");
+            UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg)
+        })?;
+        if key.is_empty() {
+            let msg = "The signing key file is empty".to_string();
+            tracing::error!(code = ?UCode::INVALID_ARGUMENT, "{msg}");
+            return Err(UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg));
+        }
+        Ok(self.with_key(authority, key))
+    }
+
+    /// Set the policy of the messages which can't be verified. `SignaturePolicy::RequireSigned` by default.
+    #[must_use]
+    pub fn with_policy(mut self, policy: SignaturePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    fn mac(&self, authority: &str, attributes: &[u8], payload: &[u8]) -> Option<HmacSha256> {
+        let key = self.keys.get(authority)?;
+        // HMAC accepts the key of any length
+        let mut mac = HmacSha256::new_from_slice(key).ok()?;
+        mac.update(&u32::try_from(attributes.len()).ok()?.to_le_bytes());
+        mac.update(attributes);
+        mac.update(payload);
+        Some(mac)
+    }
+
+    // Sign the message as `authority` if its key is known. The message from another source authority,
+    // e.g. forwarded by `UStreamer`, is left unsigned, since the receiver requires the source to sign.
+    pub(crate) fn sign(
+        &self,
+        attachment: &mut AttachmentBuilder,
+        authority: &str,
+        attributes: &UAttributes,
+        payload: &[u8],
+    ) -> Result<(), TransportError> {
+        let source_authority = &attributes.source.authority_name;
+        if !source_authority.is_empty() && source_authority != authority {
+            tracing::debug!("Leave the message from {source_authority} unsigned");
+            return Ok(());
+        }
+        let attributes = protobuf::Message::write_to_bytes(attributes)
+            .map_err(|e| TransportError::AttachmentEncode(e.into()))?;
+        if let Some(mac) = self.mac(authority, &attributes, payload) {
+            attachment.insert(SIGNATURE_KEY, mac.finalize().into_bytes().as_slice());
+            attachment.insert(SIGNER_KEY, authority.as_bytes());
+        }
+        Ok(())
+    }
+
+    // Verify the signature over the raw UAttributes in the attachment and the payload
+    pub(crate) fn verify(
+        &self,
+        attachment: &Attachment,
+        attributes: &UAttributes,
+        payload: &[u8],
+    ) -> Result<(), TransportError> {
+        let mut raw_attributes = None;
+        let mut signature = None;
+        let mut signer = None;
+        for (index, (key, value)) in attachment.iter().enumerate() {
+            match (index, key.as_slice()) {
+                (1, _) => raw_attributes = Some(value),
+                (_, key) if key == SIGNATURE_KEY.as_bytes() => signature = Some(value),
+                (_, key) if key == SIGNER_KEY.as_bytes() => signer = Some(value),
+                _ => {}
+            }
+        }
+        let unverifiable = |reason: &str| match self.policy {
+            SignaturePolicy::RequireSigned => {
+                Err(TransportError::InvalidSignature(reason.to_string()))
+            }
+            SignaturePolicy::AcceptUnsigned => Ok(()),
+        };
+        let (Some(raw_attributes), Some(signature), Some(signer)) =
+            (raw_attributes, signature, signer)
+        else {
+            return unverifiable("The message is not signed");
+        };
+        let signer = String::from_utf8_lossy(signer.as_slice());
+        let source_authority = &attributes.source.authority_name;
+        if !source_authority.is_empty() && *source_authority != signer {
+            return Err(TransportError::InvalidSignature(format!(
+                "The message from {source_authority} is signed by {signer}"
+            )));
+        }
+        let Some(mac) = self.mac(&signer, raw_attributes.as_slice(), payload) else {
+            return unverifiable(&format!("No key of the signer {signer}"));
+        };
+        mac.verify_slice(signature.as_slice())
+            .map_err(|_| TransportError::InvalidSignature(format!("Wrong signature from {signer}")))
+    }
+}
@@ -0,0 +1,130 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+use crate::{key_mapping::zenoh_key_to_uuris, UPClientZenoh};
+use std::fmt;
+use up_rust::{UAttributes, UCode, UStatus, UUri, UUID};
+use zenoh::{
+    prelude::{r#async::*, Sample},
+    subscriber::Subscriber,
+};
+
+/// The message seen by the sniffer, decoded back into uProtocol terms as far as possible.
+#[derive(Clone, Debug)]
+pub struct SniffedMessage {
+    /// The raw Zenoh key expression
+    pub key: String,
+    /// The source and sink `UUri` decoded from the key, or the error if the key is not in the `up/` key space
+    pub uris: Result<(UUri, Option<UUri>), UStatus>,
+    /// The `UAttributes` decoded from the attachment, or the error if the attachment is malformed
+    pub attributes: Result<UAttributes, String>,
+    /// The size of the payload in bytes
+    pub payload_size: usize,
+}
+
+fn uuid_to_string(uuid: &UUID) -> String {
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        uuid.msb >> 32,
+        (uuid.msb >> 16) & 0xFFFF,
+        uuid.msb & 0xFFFF,
+        uuid.lsb >> 48,
+        uuid.lsb & 0xFFFF_FFFF_FFFF
+    )
+}
+
+fn uri_to_string(uri: &UUri) -> String {
+    format!(
+        "//{}/{:X}/{:X}/{:X}",
+        uri.authority_name, uri.ue_id, uri.ue_version_major, uri.resource_id
+    )
+}
+
+impl fmt::Display for SniffedMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.uris {
+            Ok((src, Some(sink))) => {
+                write!(f, "{} -> {}", uri_to_string(src), uri_to_string(sink))?;
+            }
+            Ok((src, None)) => write!(f, "{}", uri_to_string(src))?,
+            Err(_) => write!(f, "{}", self.key)?,
+        }
+        match &self.attributes {
+            Ok(attributes) => {
+                write!(
+                    f,
+                    " type={:?} priority={:?} id={}",
+                    attributes.type_.enum_value_or_default(),
+                    attributes.priority.enum_value_or_default(),
+                    uuid_to_string(&attributes.id),
+                )?;
+                if let Some(reqid) = attributes.reqid.as_ref() {
+                    write!(f, " reqid={}", uuid_to_string(reqid))?;
+                }
+                if let Some(ttl) = attributes.ttl {
+                    write!(f, " ttl={ttl}ms")?;
+                }
+            }
+            Err(e) => write!(f, " malformed attachment ({e})")?,
+        }
+        write!(f, " payload={} bytes", self.payload_size)
+    }
+}
+
+impl SniffedMessage {
+    fn from_sample(sample: &Sample) -> Self {
+        let attributes = match sample.attachment() {
+            Some(attachment) => UPClientZenoh::attachment_to_uattributes(attachment)
+                .map_err(|e| format!("Unable to transform attachment to UAttributes: {e:?}")),
+            None => Err("No attachment".to_string()),
+        };
+        SniffedMessage {
+            key: sample.key_expr.to_string(),
+            uris: zenoh_key_to_uuris(sample.key_expr.as_str()),
+            attributes,
+            payload_size: sample.payload.len(),
+        }
+    }
+}
+
+/// Keep sniffing the traffic until dropped.
+pub struct Sniffer {
+    _subscriber: Subscriber<'static, ()>,
+}
+
+impl UPClientZenoh {
+    /// Sniff the Publish and Notification messages whose Zenoh key intersects `key_expr`, e.g. `up/**`.
+    /// RPC requests and responses are not visible to the sniffer since they are Zenoh queries.
+    /// The message with malformed key or attachment is still reported with the error.
+    ///
+    /// # Errors
+    /// Will return `Err` if unable to declare the subscriber in Zenoh
+    pub async fn start_sniffing<F>(&self, key_expr: &str, callback: F) -> Result<Sniffer, UStatus>
+    where
+        F: Fn(SniffedMessage) + Send + Sync + 'static,
+    {
+        let subscriber = self
+            .session
+            .declare_subscriber(key_expr.to_string())
+            .callback(move |sample: Sample| callback(SniffedMessage::from_sample(&sample)))
+            .res()
+            .await
+            .map_err(|e| {
+                let msg = format!("Unable to declare Zenoh subscriber for sniffing: {e:?}");
+                tracing::error!(code = ?UCode::INTERNAL, "{msg}");
+                UStatus::fail_with_code(UCode::INTERNAL, msg)
+            })?;
+        Ok(Sniffer {
+            _subscriber: subscriber,
+        })
+    }
+}
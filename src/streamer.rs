@@ -0,0 +1,332 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+//! The uStreamer routing messages between `UPClientZenoh` and another `UTransport`.
+//!
+//! Each `ForwardingRule` registers a wildcard listener on the input transport and sends the received
+//! messages as they are to the output one. The RPC response is correlated with the forwarded request
+//! by its `reqid`, so `UPClientZenoh` replies to the original query.
+//!
+//! The source authority of the forwarded messages is not its own, so `UPClientZenoh` needs
+//! `AuthorityPolicy::Trust`, which is the default. For the same reason, `UPClientZenoh` can't sign
+//! the forwarded messages, and the receivers with signing enabled need `SignaturePolicy::AcceptUnsigned`
+//! to accept them. The receivers checking the peer certificates reject them.
+use crate::UPClientZenoh;
+use async_trait::async_trait;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use up_rust::{UCode, UListener, UMessage, UMessageType, UStatus, UTransport, UUri};
+
+// The TTL of the forwarded request if it's not set
+const DEFAULT_REQUEST_TTL: u32 = 1000;
+
+/// Which way `UStreamer` forwards the messages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ForwardingDirection {
+    /// From `UPClientZenoh` to the other `UTransport`
+    ToTransport,
+    /// From the other `UTransport` to `UPClientZenoh`
+    ToZenoh,
+}
+
+impl ForwardingDirection {
+    fn reverse(self) -> Self {
+        match self {
+            ForwardingDirection::ToTransport => ForwardingDirection::ToZenoh,
+            ForwardingDirection::ToZenoh => ForwardingDirection::ToTransport,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum RuleKind {
+    // Notification, Request and Response addressed to the authority
+    MessagesTo,
+    // Publish from the authority
+    PublishFrom,
+}
+
+/// The messages of an authority forwarded by `UStreamer`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ForwardingRule {
+    direction: ForwardingDirection,
+    authority: String,
+    kind: RuleKind,
+}
+
+impl ForwardingRule {
+    /// Forward the Notification, Request and Response messages whose sink authority is `authority`.
+    ///
+    /// The responses to the forwarded requests are only delivered if the requester's authority
+    /// is also forwarded in the reverse direction.
+    #[must_use]
+    pub fn messages_to(direction: ForwardingDirection, authority: impl Into<String>) -> Self {
+        ForwardingRule {
+            direction,
+            authority: authority.into(),
+            kind: RuleKind::MessagesTo,
+        }
+    }
+
+    /// Forward the Publish messages whose source authority is `authority`.
+    #[must_use]
+    pub fn publish_from(direction: ForwardingDirection, authority: impl Into<String>) -> Self {
+        ForwardingRule {
+            direction,
+            authority: authority.into(),
+            kind: RuleKind::PublishFrom,
+        }
+    }
+
+    // The filters of the wildcard listener on the input transport
+    fn filters(&self) -> (UUri, Option<UUri>) {
+        let uuri = |authority: &str| UUri {
+            authority_name: authority.to_string(),
+            ue_id: 0xFFFF,
+            ue_version_major: 0xFF,
+            resource_id: 0xFFFF,
+            ..Default::default()
+        };
+        match self.kind {
+            RuleKind::MessagesTo => (uuri("*"), Some(uuri(&self.authority))),
+            RuleKind::PublishFrom => (uuri(&self.authority), None),
+        }
+    }
+
+    // The same messages forwarded back would loop between the transports
+    fn loops_with(&self, other: &ForwardingRule) -> bool {
+        self.kind == other.kind
+            && self.authority == other.authority
+            && self.direction != other.direction
+    }
+}
+
+// The requests forwarded and waiting for the responses, keyed by the request ID
+#[derive(Default)]
+struct PendingRequests {
+    requests: Mutex<HashMap<String, (ForwardingDirection, Instant)>>,
+}
+
+impl PendingRequests {
+    fn insert(&self, reqid: String, direction: ForwardingDirection, ttl: u32) {
+        let now = Instant::now();
+        let mut requests = self.requests.lock().unwrap();
+        // Forget the requests nobody responded to before the TTL
+        requests.retain(|_, (_, deadline)| *deadline > now);
+        requests.insert(
+            reqid,
+            (direction, now + Duration::from_millis(u64::from(ttl))),
+        );
+    }
+
+    // Remove the request forwarded in `direction`, and return whether it's still pending
+    fn remove(&self, reqid: &str, direction: ForwardingDirection) -> bool {
+        let mut requests = self.requests.lock().unwrap();
+        match requests.get(reqid) {
+            Some((pending_direction, _)) if *pending_direction == direction => requests
+                .remove(reqid)
+                .is_some_and(|(_, deadline)| deadline > Instant::now()),
+            _ => false,
+        }
+    }
+
+    fn len(&self) -> usize {
+        let now = Instant::now();
+        self.requests
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|(_, deadline)| *deadline > now)
+            .count()
+    }
+}
+
+// The wildcard listener on the input transport of a rule
+struct Forwarder {
+    direction: ForwardingDirection,
+    output: Arc<dyn UTransport>,
+    pending: Arc<PendingRequests>,
+}
+
+#[async_trait]
+impl UListener for Forwarder {
+    async fn on_receive(&self, msg: UMessage) {
+        let attributes = msg.attributes.get_or_default();
+        let id = attributes.id.to_string();
+        match attributes.type_.enum_value_or_default() {
+            UMessageType::UMESSAGE_TYPE_REQUEST => {
+                let ttl = attributes.ttl.unwrap_or(DEFAULT_REQUEST_TTL);
+                self.pending.insert(id.clone(), self.direction, ttl);
+            }
+            UMessageType::UMESSAGE_TYPE_RESPONSE => {
+                // Only the response to the request forwarded the other way round
+                let reqid = attributes.reqid.to_string();
+                if !self.pending.remove(&reqid, self.direction.reverse()) {
+                    tracing::warn!("Drop the response to the unknown or expired request {reqid}");
+                    return;
+                }
+            }
+            _ => {}
+        }
+        if let Err(e) = self.output.send(msg).await {
+            tracing::warn!(
+                "Unable to forward the message {id} {:?}: {e:?}",
+                self.direction
+            );
+        }
+    }
+
+    async fn on_error(&self, err: UStatus) {
+        tracing::warn!("Error while forwarding {:?}: {err:?}", self.direction);
+    }
+}
+
+/// Route the messages between `UPClientZenoh` and another `UTransport` with the forwarding rules.
+///
+/// # Examples
+///
+/// ```
+/// #[tokio::main]
+/// # async fn main() {
+/// use std::sync::Arc;
+/// use up_transport_zenoh::{
+///     streamer::{ForwardingDirection, ForwardingRule, UStreamer},
+///     UPClientZenoh,
+/// };
+///
+/// let runtime = UPClientZenoh::new_loopback_runtime().await.unwrap();
+/// let zenoh = UPClientZenoh::new_with_runtime(runtime, String::from("gateway"))
+///     .await
+///     .unwrap();
+/// let other_runtime = UPClientZenoh::new_loopback_runtime().await.unwrap();
+/// let transport = UPClientZenoh::new_with_runtime(other_runtime, String::from("gateway"))
+///     .await
+///     .unwrap();
+/// let streamer = UStreamer::new(Arc::new(zenoh), Arc::new(transport));
+/// streamer
+///     .add_rule(ForwardingRule::messages_to(ForwardingDirection::ToTransport, "cloud"))
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+pub struct UStreamer {
+    zenoh: Arc<UPClientZenoh>,
+    transport: Arc<dyn UTransport>,
+    pending: Arc<PendingRequests>,
+    // The listener registered for each rule, able to remove the rule
+    forwarders: Mutex<HashMap<ForwardingRule, Arc<dyn UListener>>>,
+}
+
+impl UStreamer {
+    /// Create `UStreamer` between `zenoh` and `transport` without any forwarding rule.
+    #[must_use]
+    pub fn new(zenoh: Arc<UPClientZenoh>, transport: Arc<dyn UTransport>) -> Self {
+        UStreamer {
+            zenoh,
+            transport,
+            pending: Arc::new(PendingRequests::default()),
+            forwarders: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn transports(
+        &self,
+        direction: ForwardingDirection,
+    ) -> (Arc<dyn UTransport>, Arc<dyn UTransport>) {
+        let zenoh: Arc<dyn UTransport> = self.zenoh.clone();
+        match direction {
+            ForwardingDirection::ToTransport => (zenoh, self.transport.clone()),
+            ForwardingDirection::ToZenoh => (self.transport.clone(), zenoh),
+        }
+    }
+
+    /// Start forwarding the messages of `rule`.
+    ///
+    /// # Errors
+    /// Will return `Err` with `UCode::ALREADY_EXISTS` if the rule is added already,
+    /// `UCode::INVALID_ARGUMENT` if the same messages are forwarded in the reverse direction,
+    /// or the error of registering the listener on the input transport
+    ///
+    /// # Panics
+    /// Will panic if the lock is poisoned
+    pub async fn add_rule(&self, rule: ForwardingRule) -> Result<(), UStatus> {
+        let (input, output) = self.transports(rule.direction);
+        let forwarder: Arc<dyn UListener> = Arc::new(Forwarder {
+            direction: rule.direction,
+            output,
+            pending: self.pending.clone(),
+        });
+        // Reserve the rule before registering, so the same rule added concurrently is rejected
+        {
+            let mut forwarders = self.forwarders.lock().unwrap();
+            if forwarders.contains_key(&rule) {
+                let msg = format!("Forwarding rule {rule:?} already exists");
+                tracing::error!(code = ?UCode::ALREADY_EXISTS, "{msg}");
+                return Err(UStatus::fail_with_code(UCode::ALREADY_EXISTS, msg));
+            }
+            if forwarders.keys().any(|other| rule.loops_with(other)) {
+                let msg = format!("Forwarding rule {rule:?} loops with the reverse one");
+                tracing::error!(code = ?UCode::INVALID_ARGUMENT, "{msg}");
+                return Err(UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg));
+            }
+            forwarders.insert(rule.clone(), forwarder.clone());
+        }
+        let (source_filter, sink_filter) = rule.filters();
+        if let Err(e) = input
+            .register_listener(&source_filter, sink_filter.as_ref(), forwarder)
+            .await
+        {
+            self.forwarders.lock().unwrap().remove(&rule);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Stop forwarding the messages of `rule`.
+    ///
+    /// # Errors
+    /// Will return `Err` with `UCode::NOT_FOUND` if the rule is not added,
+    /// or the error of unregistering the listener on the input transport, which keeps the rule
+    ///
+    /// # Panics
+    /// Will panic if the lock is poisoned
+    pub async fn remove_rule(&self, rule: &ForwardingRule) -> Result<(), UStatus> {
+        let Some(forwarder) = self.forwarders.lock().unwrap().remove(rule) else {
+            let msg = format!("Forwarding rule {rule:?} doesn't exist");
+            tracing::warn!(code = ?UCode::NOT_FOUND, "{msg}");
+            return Err(UStatus::fail_with_code(UCode::NOT_FOUND, msg));
+        };
+        let (input, _) = self.transports(rule.direction);
+        let (source_filter, sink_filter) = rule.filters();
+        if let Err(e) = input
+            .unregister_listener(&source_filter, sink_filter.as_ref(), forwarder.clone())
+            .await
+        {
+            // The listener is still registered, so keep the rule able to be removed again
+            self.forwarders
+                .lock()
+                .unwrap()
+                .insert(rule.clone(), forwarder);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// The number of the forwarded requests waiting for the responses.
+    #[must_use]
+    pub fn pending_requests(&self) -> usize {
+        self.pending.len()
+    }
+}
@@ -0,0 +1,448 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+//! TLS and mTLS endpoints of the Zenoh session, configured without writing the Zenoh `Config` by hand.
+//!
+//! ```
+//! use up_transport_zenoh::{tls::TlsConfig, UPClientZenoh};
+//! let builder = UPClientZenoh::builder("vehicle").with_tls(
+//!     TlsConfig::new("ca.pem")
+//!         .with_certificate("vehicle.pem", "vehicle.key")
+//!         .with_mutual_auth(true)
+//!         .with_listen("0.0.0.0:7447")
+//!         .with_own_certificate_check(true)
+//!         .with_peer_certificate_check(true),
+//! );
+//! ```
+//!
+//! With the own certificate check, the subject common name of the local certificate must be the authority
+//! name of `UPClientZenoh`, so a misconfigured client fails to build instead of joining under another name.
+//!
+//! With the peer certificate check, the certificate of each peer is bound to the source authority of its
+//! messages. mTLS only guarantees the neighbour holds a certificate signed by the CA, and Zenoh doesn't tell
+//! which link a sample came through, so the binding is carried by the message itself: the sender signs the
+//! serialized `UAttributes` plus the payload with the private key of its certificate, and carries the
+//! certificate and the signature in the Zenoh attachment as the entries keyed by `cert` and `cert_sig`.
+//! The receiver rejects the message with `UCode::PERMISSION_DENIED` unless the certificate is issued by the CA
+//! to the source authority and the signature is valid. It's checked for the listeners of `register_listener`,
+//! the RPC requests and responses, the last values, the history and the recording.
+//!
+//! The certificate must be issued directly by the CA with an ECDSA P-256 / P-384 or Ed25519 key.
+//! Only the messages whose source authority is the local one are signed, so the messages forwarded by
+//! `UStreamer` on behalf of another authority are rejected by the receivers checking the peer certificates.
+use crate::{error::TransportError, Config};
+use ring::{
+    rand::SystemRandom,
+    signature::{
+        EcdsaKeyPair, Ed25519KeyPair, UnparsedPublicKey, VerificationAlgorithm,
+        ECDSA_P256_SHA256_ASN1, ECDSA_P256_SHA256_ASN1_SIGNING, ECDSA_P384_SHA384_ASN1,
+        ECDSA_P384_SHA384_ASN1_SIGNING, ED25519,
+    },
+};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use up_rust::{UAttributes, UCode, UStatus};
+use x509_parser::{certificate::X509Certificate, parse_x509_certificate, pem::parse_x509_pem};
+use zenoh::{
+    prelude::EndPoint,
+    sample::{Attachment, AttachmentBuilder},
+};
+
+const CERTIFICATE_KEY: &str = "cert";
+const CERTIFICATE_SIGNATURE_KEY: &str = "cert_sig";
+// Forget the verified certificates once there are too many of them, so the peers can't exhaust the memory
+const MAX_VERIFIED_CERTIFICATES: usize = 256;
+// The algorithms of the public keys in the peer certificates
+static VERIFICATION_ALGORITHMS: [&dyn VerificationAlgorithm; 3] =
+    [&ECDSA_P256_SHA256_ASN1, &ECDSA_P384_SHA384_ASN1, &ED25519];
+
+/// The TLS configuration applied to the Zenoh `Config`.
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    root_ca_certificate: PathBuf,
+    // The certificate and its private key
+    certificate: Option<(PathBuf, PathBuf)>,
+    mutual_auth: bool,
+    listen: Vec<String>,
+    connect: Vec<String>,
+    own_certificate_check: bool,
+    peer_certificate_check: bool,
+}
+
+fn invalid_argument(msg: String) -> UStatus {
+    tracing::error!(code = ?UCode::INVALID_ARGUMENT, "{msg}");
+    UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg)
+}
+
+fn path_string(path: &Path) -> Result<String, UStatus> {
+    path.to_str()
+        .map(ToString::to_string)
+        .ok_or_else(|| invalid_argument(format!("The path {path:?} isn't valid UTF-8")))
+}
+
+// Read the DER contents of the PEM file
+fn read_pem(path: &Path) -> Result<Vec<u8>, UStatus> {
+    let pem =
+        fs::read(path).map_err(|e| invalid_argument(format!("Unable to read {path:?}: {e:?}")))?;
+    parse_x509_pem(&pem)
+        .map(|(_, pem)| pem.contents)
+        .map_err(|e| invalid_argument(format!("Invalid PEM file {path:?}: {e:?}")))
+}
+
+fn common_name(certificate: &X509Certificate) -> Option<String> {
+    let common_name = certificate.subject().iter_common_name().next()?;
+    common_name.as_str().ok().map(ToString::to_string)
+}
+
+impl TlsConfig {
+    /// Trust the peers whose certificate is signed by the CA in the PEM file at `root_ca_certificate`.
+    #[must_use]
+    pub fn new(root_ca_certificate: impl Into<PathBuf>) -> Self {
+        TlsConfig {
+            root_ca_certificate: root_ca_certificate.into(),
+            certificate: None,
+            mutual_auth: false,
+            listen: vec![],
+            connect: vec![],
+            own_certificate_check: false,
+            peer_certificate_check: false,
+        }
+    }
+
+    /// Present the certificate and its private key in the PEM files.
+    /// It's required to listen, to use mTLS and to check the own certificate.
+    #[must_use]
+    pub fn with_certificate(
+        mut self,
+        certificate: impl Into<PathBuf>,
+        private_key: impl Into<PathBuf>,
+    ) -> Self {
+        self.certificate = Some((certificate.into(), private_key.into()));
+        self
+    }
+
+    /// Require the peers to present the certificate signed by the CA as well. Disabled by default.
+    #[must_use]
+    pub fn with_mutual_auth(mut self, enabled: bool) -> Self {
+        self.mutual_auth = enabled;
+        self
+    }
+
+    /// Listen on the TLS endpoint at `address`, e.g. `0.0.0.0:7447`.
+    #[must_use]
+    pub fn with_listen(mut self, address: impl Into<String>) -> Self {
+        self.listen.push(address.into());
+        self
+    }
+
+    /// Connect to the TLS endpoint at `address`, e.g. `router.example.com:7447`.
+    /// The host name must match the certificate of the peer.
+    #[must_use]
+    pub fn with_connect(mut self, address: impl Into<String>) -> Self {
+        self.connect.push(address.into());
+        self
+    }
+
+    /// Require the subject common name of the local certificate to be the authority name. Disabled by default.
+    #[must_use]
+    pub fn with_own_certificate_check(mut self, enabled: bool) -> Self {
+        self.own_certificate_check = enabled;
+        self
+    }
+
+    /// Sign the outgoing messages with the local certificate, and reject the inbound messages unless they are
+    /// signed with the certificate issued by the CA to their source authority. Disabled by default.
+    /// See the [module documentation](self) for how the certificate is bound to the authority.
+    #[must_use]
+    pub fn with_peer_certificate_check(mut self, enabled: bool) -> Self {
+        self.peer_certificate_check = enabled;
+        self
+    }
+
+    // Apply the TLS settings and the endpoints to the Zenoh config
+    pub(crate) fn apply(&self, config: &mut Config, authority_name: &str) -> Result<(), UStatus> {
+        if self.own_certificate_check {
+            self.check_own_certificate(authority_name)?;
+        }
+        let tls = &mut config.transport.link.tls;
+        let mut failed = tls
+            .set_root_ca_certificate(Some(path_string(&self.root_ca_certificate)?))
+            .is_err();
+        if let Some((certificate, private_key)) = &self.certificate {
+            let certificate = path_string(certificate)?;
+            let private_key = path_string(private_key)?;
+            // The same certificate is used to accept and to make the connections
+            failed = failed
+                || tls
+                    .set_server_certificate(Some(certificate.clone()))
+                    .is_err()
+                || tls
+                    .set_server_private_key(Some(private_key.clone()))
+                    .is_err()
+                || tls.set_client_certificate(Some(certificate)).is_err()
+                || tls.set_client_private_key(Some(private_key)).is_err();
+        } else if self.mutual_auth || !self.listen.is_empty() {
+            return Err(invalid_argument(
+                "The certificate is required to use mTLS or to listen".to_string(),
+            ));
+        }
+        if failed || tls.set_client_auth(Some(self.mutual_auth)).is_err() {
+            return Err(invalid_argument(
+                "Unable to set TLS in Zenoh config".to_string(),
+            ));
+        }
+        let to_endpoints = |addresses: &[String]| {
+            addresses
+                .iter()
+                .map(|address| {
+                    format!("tls/{address}").parse::<EndPoint>().map_err(|e| {
+                        invalid_argument(format!("Invalid TLS endpoint {address}: {e:?}"))
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        config.listen.endpoints.extend(to_endpoints(&self.listen)?);
+        config
+            .connect
+            .endpoints
+            .extend(to_endpoints(&self.connect)?);
+        Ok(())
+    }
+
+    // The subject common name of the local certificate must be the authority name
+    fn check_own_certificate(&self, authority_name: &str) -> Result<(), UStatus> {
+        let Some((certificate, _)) = &self.certificate else {
+            return Err(invalid_argument(
+                "The certificate is required to check it against the authority".to_string(),
+            ));
+        };
+        let der = read_pem(certificate)?;
+        let common_name = parse_x509_certificate(&der)
+            .ok()
+            .and_then(|(_, x509)| common_name(&x509))
+            .ok_or_else(|| {
+                invalid_argument(format!(
+                    "No subject common name in the certificate {certificate:?}"
+                ))
+            })?;
+        if common_name != authority_name {
+            let msg = format!(
+                "The certificate is issued to {common_name} instead of the authority {authority_name}"
+            );
+            tracing::error!(code = ?UCode::PERMISSION_DENIED, "{msg}");
+            return Err(UStatus::fail_with_code(UCode::PERMISSION_DENIED, msg));
+        }
+        Ok(())
+    }
+
+    // Load the CA, the certificate and its private key to sign and check the messages if enabled
+    pub(crate) fn peer_certificate_check(
+        &self,
+    ) -> Result<Option<Arc<PeerCertificateCheck>>, UStatus> {
+        if !self.peer_certificate_check {
+            return Ok(None);
+        }
+        let Some((certificate, private_key)) = &self.certificate else {
+            return Err(invalid_argument(
+                "The certificate is required to check the peer certificates".to_string(),
+            ));
+        };
+        let rng = SystemRandom::new();
+        let pkcs8 = read_pem(private_key)?;
+        let private_key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &pkcs8, &rng)
+            .or_else(|_| EcdsaKeyPair::from_pkcs8(&ECDSA_P384_SHA384_ASN1_SIGNING, &pkcs8, &rng))
+            .map(PrivateKey::Ecdsa)
+            .or_else(|_| {
+                Ed25519KeyPair::from_pkcs8_maybe_unchecked(&pkcs8).map(PrivateKey::Ed25519)
+            })
+            .map_err(|e| {
+                invalid_argument(format!(
+                    "The private key {private_key:?} isn't a PKCS#8 ECDSA or Ed25519 key: {e}"
+                ))
+            })?;
+        Ok(Some(Arc::new(PeerCertificateCheck {
+            ca: read_pem(&self.root_ca_certificate)?,
+            certificate: read_pem(certificate)?,
+            private_key,
+            rng,
+            verified: Mutex::new(HashMap::new()),
+        })))
+    }
+}
+
+// The private key of the local certificate
+enum PrivateKey {
+    Ecdsa(EcdsaKeyPair),
+    Ed25519(Ed25519KeyPair),
+}
+
+// Sign the outgoing messages with the local certificate, and check the inbound ones are signed with
+// the certificate issued by the CA to their source authority
+pub(crate) struct PeerCertificateCheck {
+    // The DER of the CA and the local certificates
+    ca: Vec<u8>,
+    certificate: Vec<u8>,
+    private_key: PrivateKey,
+    rng: SystemRandom,
+    // The common name and the expiry (seconds since UNIX epoch) of the peer certificates verified already
+    verified: Mutex<HashMap<Vec<u8>, (String, i64)>>,
+}
+
+// The signed bytes: the length of the serialized UAttributes, the serialized UAttributes and the payload
+fn signed_message(attributes: &[u8], payload: &[u8]) -> Option<Vec<u8>> {
+    let mut message = Vec::with_capacity(4 + attributes.len() + payload.len());
+    message.extend_from_slice(&u32::try_from(attributes.len()).ok()?.to_le_bytes());
+    message.extend_from_slice(attributes);
+    message.extend_from_slice(payload);
+    Some(message)
+}
+
+impl PeerCertificateCheck {
+    // Sign the message from the local authority with the local certificate.
+    // The message from another source authority, e.g. forwarded by `UStreamer`, is left unsigned.
+    pub(crate) fn sign(
+        &self,
+        attachment: &mut AttachmentBuilder,
+        authority: &str,
+        attributes: &UAttributes,
+        payload: &[u8],
+    ) -> Result<(), TransportError> {
+        let source_authority = &attributes.source.authority_name;
+        if !source_authority.is_empty() && source_authority != authority {
+            tracing::debug!(
+                "Leave the message from {source_authority} unsigned by the certificate"
+            );
+            return Ok(());
+        }
+        let attributes = protobuf::Message::write_to_bytes(attributes)
+            .map_err(|e| TransportError::AttachmentEncode(e.into()))?;
+        let message = signed_message(&attributes, payload).ok_or_else(|| {
+            TransportError::InvalidMessage("UAttributes is too large".to_string())
+        })?;
+        let signature = match &self.private_key {
+            PrivateKey::Ecdsa(key) => key
+                .sign(&self.rng, &message)
+                .map_err(|_| {
+                    TransportError::InvalidSignature(
+                        "Unable to sign with the certificate".to_string(),
+                    )
+                })?
+                .as_ref()
+                .to_vec(),
+            PrivateKey::Ed25519(key) => key.sign(&message).as_ref().to_vec(),
+        };
+        attachment.insert(CERTIFICATE_KEY, self.certificate.as_slice());
+        attachment.insert(CERTIFICATE_SIGNATURE_KEY, signature.as_slice());
+        Ok(())
+    }
+
+    // Check the message is signed with the certificate issued by the CA to its source authority
+    pub(crate) fn verify(
+        &self,
+        attachment: &Attachment,
+        attributes: &UAttributes,
+        payload: &[u8],
+    ) -> Result<(), TransportError> {
+        let mut raw_attributes = None;
+        let mut certificate = None;
+        let mut signature = None;
+        for (index, (key, value)) in attachment.iter().enumerate() {
+            match (index, key.as_slice()) {
+                (1, _) => raw_attributes = Some(value),
+                (_, key) if key == CERTIFICATE_KEY.as_bytes() => certificate = Some(value),
+                (_, key) if key == CERTIFICATE_SIGNATURE_KEY.as_bytes() => signature = Some(value),
+                _ => {}
+            }
+        }
+        let source_authority = &attributes.source.authority_name;
+        let (Some(raw_attributes), Some(certificate), Some(signature)) =
+            (raw_attributes, certificate, signature)
+        else {
+            return Err(TransportError::PermissionDenied(format!(
+                "The message from {source_authority} isn't signed with a certificate"
+            )));
+        };
+        let certificate = certificate.as_slice();
+        let common_name = self.verify_certificate(certificate)?;
+        if common_name != *source_authority {
+            return Err(TransportError::PermissionDenied(format!(
+                "The message from {source_authority} is signed with the certificate of {common_name}"
+            )));
+        }
+        let (_, x509) = parse_x509_certificate(certificate)
+            .map_err(|e| TransportError::PermissionDenied(format!("Invalid certificate: {e:?}")))?;
+        let public_key = x509.public_key().subject_public_key.data.as_ref();
+        let message = signed_message(raw_attributes.as_slice(), payload).ok_or_else(|| {
+            TransportError::InvalidMessage("UAttributes is too large".to_string())
+        })?;
+        let signature = signature.as_slice();
+        // Only the algorithm of the public key can verify the signature
+        if VERIFICATION_ALGORITHMS.iter().any(|algorithm| {
+            UnparsedPublicKey::new(*algorithm, public_key)
+                .verify(&message, signature)
+                .is_ok()
+        }) {
+            Ok(())
+        } else {
+            Err(TransportError::PermissionDenied(format!(
+                "Wrong certificate signature from {source_authority}"
+            )))
+        }
+    }
+
+    // Return the common name of the certificate if it's issued by the CA and valid now
+    fn verify_certificate(&self, certificate: &[u8]) -> Result<String, TransportError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| i64::try_from(d.as_secs()).unwrap_or(i64::MAX));
+        let mut verified = self.verified.lock().unwrap();
+        if let Some((common_name, not_after)) = verified.get(certificate) {
+            if now <= *not_after {
+                return Ok(common_name.clone());
+            }
+        }
+        let (_, ca) = parse_x509_certificate(&self.ca).map_err(|e| {
+            TransportError::PermissionDenied(format!("Invalid CA certificate: {e:?}"))
+        })?;
+        let (_, x509) = parse_x509_certificate(certificate).map_err(|e| {
+            TransportError::PermissionDenied(format!("Invalid peer certificate: {e:?}"))
+        })?;
+        x509.verify_signature(Some(ca.public_key())).map_err(|e| {
+            TransportError::PermissionDenied(format!(
+                "The certificate isn't issued by the CA: {e:?}"
+            ))
+        })?;
+        if !x509.validity().is_valid() {
+            return Err(TransportError::PermissionDenied(
+                "The certificate is expired or not yet valid".to_string(),
+            ));
+        }
+        let common_name = common_name(&x509).ok_or_else(|| {
+            TransportError::PermissionDenied(
+                "No subject common name in the certificate".to_string(),
+            )
+        })?;
+        if verified.len() >= MAX_VERIFIED_CERTIFICATES {
+            verified.clear();
+        }
+        verified.insert(
+            certificate.to_vec(),
+            (common_name.clone(), x509.validity().not_after.timestamp()),
+        );
+        Ok(common_name)
+    }
+}
@@ -0,0 +1,125 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+// The W3C trace context is carried in the Zenoh attachment after the UAttributes,
+// as the entries keyed by `traceparent` and `tracestate`.
+// The peers without the `tracing` feature only read the first two entries and ignore them.
+// Without the `tracing` feature, the spans are still created but never linked to the remote parent.
+use tracing::Span;
+use up_rust::UAttributes;
+use zenoh::sample::{Attachment, AttachmentBuilder};
+
+#[cfg(feature = "tracing")]
+use opentelemetry::{propagation::TextMapPropagator, Context};
+#[cfg(feature = "tracing")]
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+#[cfg(feature = "tracing")]
+use std::collections::HashMap;
+#[cfg(feature = "tracing")]
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+#[cfg(feature = "tracing")]
+const TRACE_CONTEXT_KEYS: [&str; 2] = ["traceparent", "tracestate"];
+
+// Append the trace context of the current span to the attachment
+#[cfg(feature = "tracing")]
+pub(crate) fn inject(attachment: &mut AttachmentBuilder) {
+    inject_context(&Span::current().context(), attachment);
+}
+
+#[cfg(feature = "tracing")]
+fn inject_context(cx: &Context, attachment: &mut AttachmentBuilder) {
+    let mut carrier = HashMap::new();
+    TraceContextPropagator::new().inject_context(cx, &mut carrier);
+    for key in TRACE_CONTEXT_KEYS {
+        if let Some(value) = carrier.get(key) {
+            attachment.insert(key, value.as_bytes());
+        }
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn inject(_attachment: &mut AttachmentBuilder) {}
+
+// Link the span to the trace context in the attachment, if any
+#[cfg(feature = "tracing")]
+fn set_remote_parent(span: &Span, attachment: &Attachment) {
+    if let Some(cx) = extract_context(attachment) {
+        span.set_parent(cx);
+    }
+}
+
+#[cfg(feature = "tracing")]
+fn extract_context(attachment: &Attachment) -> Option<Context> {
+    let carrier = attachment
+        .iter()
+        .filter_map(|(key, value)| {
+            let key = std::str::from_utf8(key.as_slice()).ok()?;
+            if !TRACE_CONTEXT_KEYS.contains(&key) {
+                return None;
+            }
+            let value = std::str::from_utf8(value.as_slice()).ok()?;
+            Some((key.to_string(), value.to_string()))
+        })
+        .collect::<HashMap<_, _>>();
+    (!carrier.is_empty()).then(|| TraceContextPropagator::new().extract(&carrier))
+}
+
+#[cfg(not(feature = "tracing"))]
+fn set_remote_parent(_span: &Span, _attachment: &Attachment) {}
+
+// The span covering the listener handling the received message
+pub(crate) fn receive_span(attributes: &UAttributes, attachment: &Attachment) -> Span {
+    let span = tracing::info_span!(
+        "up_receive",
+        message_type = ?attributes.type_.enum_value_or_default(),
+        source = %attributes.source.authority_name,
+        ue_id = attributes.source.ue_id,
+        resource_id = attributes.source.resource_id,
+    );
+    set_remote_parent(&span, attachment);
+    span
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{
+        SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState,
+    };
+
+    #[test]
+    fn test_trace_context_in_attachment() {
+        let span_context = SpanContext::new(
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+            SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        );
+        let mut attachment = AttachmentBuilder::new();
+        attachment.insert("", &[1u8]);
+        inject_context(
+            &Context::new().with_remote_span_context(span_context.clone()),
+            &mut attachment,
+        );
+        let cx = extract_context(&attachment.build()).unwrap();
+        assert_eq!(cx.span().span_context(), &span_context);
+    }
+
+    #[test]
+    fn test_no_trace_context_in_attachment() {
+        let mut attachment = AttachmentBuilder::new();
+        inject_context(&Context::new(), &mut attachment);
+        assert!(extract_context(&attachment.build()).is_none());
+    }
+}
@@ -10,17 +10,31 @@
  *
  * SPDX-License-Identifier: Apache-2.0
  ********************************************************************************/
-use crate::{MessageFlag, UPClientZenoh, CB_RUNTIME};
+use crate::{
+    access_control::Operation,
+    connectivity::has_route,
+    encryption::{self, EncryptionConfig},
+    error::TransportError,
+    key_mapping::{get_listener_message_type, MessageFlag},
+    metrics::Metrics,
+    offline_queue::QueuedPut,
+    rate_limit::{InFlight, ListenerLimiter},
+    signing::SigningConfig,
+    tls::PeerCertificateCheck,
+    trace_context, AuthorityPolicy, UPClientZenoh, CB_RUNTIME,
+};
 use async_trait::async_trait;
 use lazy_static::lazy_static;
 use std::{
+    borrow::Cow,
     sync::{Arc, Mutex},
-    time::Duration,
+    time::Instant,
 };
 use tokio::{
     runtime::{Handle, Runtime},
     task,
 };
+use tracing::{Instrument, Span};
 use up_rust::{
     ComparableListener, UAttributes, UAttributesValidators, UCode, UListener, UMessage,
     UMessageType, UStatus, UTransport, UUri,
@@ -29,6 +43,7 @@ use zenoh::{
     prelude::{r#async::*, Sample},
     query::Reply,
     queryable::Query,
+    sample::{Attachment, AttachmentBuilder},
 };
 
 lazy_static! {
@@ -36,7 +51,10 @@ lazy_static! {
 }
 
 #[inline]
-fn invoke_block_callback(listener: &Arc<dyn UListener>, resp_msg: Result<UMessage, &str>) {
+fn invoke_block_callback(
+    listener: &Arc<dyn UListener>,
+    resp_msg: Result<UMessage, TransportError>,
+) {
     match resp_msg {
         Ok(umsg) => match Handle::try_current() {
             Ok(handle) => {
@@ -51,20 +69,20 @@ fn invoke_block_callback(listener: &Arc<dyn UListener>, resp_msg: Result<UMessag
                     .block_on(listener.on_receive(umsg));
             }
         },
-        Err(err_msg) => {
-            log::error!("{err_msg}");
+        Err(e) => {
+            tracing::error!(code = ?e.code(), error = %e, "Unable to receive the response");
+            let status = UStatus::from(e);
             match Handle::try_current() {
                 Ok(handle) => {
                     task::block_in_place(|| {
-                        handle.block_on(
-                            listener.on_error(UStatus::fail_with_code(UCode::INTERNAL, err_msg)),
-                        );
+                        handle.block_on(listener.on_error(status));
                     });
                 }
                 Err(_) => {
-                    TOKIO_RUNTIME.lock().unwrap().block_on(
-                        listener.on_error(UStatus::fail_with_code(UCode::INTERNAL, err_msg)),
-                    );
+                    TOKIO_RUNTIME
+                        .lock()
+                        .unwrap()
+                        .block_on(listener.on_error(status));
                 }
             }
         }
@@ -72,58 +90,197 @@ fn invoke_block_callback(listener: &Arc<dyn UListener>, resp_msg: Result<UMessag
 }
 
 #[inline]
-fn spawn_nonblock_callback(listener: &Arc<dyn UListener>, listener_msg: Result<UMessage, &str>) {
+fn spawn_nonblock_callback(
+    metrics: &Arc<Metrics>,
+    listener: &Arc<dyn UListener>,
+    limiter: &ListenerLimiter,
+    listener_msg: Result<(UMessage, Span, InFlight), TransportError>,
+) {
     let listener = listener.clone();
+    let callback_guard = metrics.start_callback();
     match listener_msg {
-        Ok(umsg) => {
-            CB_RUNTIME.spawn(async move {
-                listener.on_receive(umsg).await;
-            });
+        Ok((umsg, span, in_flight)) => {
+            CB_RUNTIME.spawn(
+                async move {
+                    // Released when finished, even if the listener panics
+                    let _guards = (callback_guard, in_flight);
+                    listener.on_receive(umsg).await;
+                }
+                .instrument(span),
+            );
         }
-        Err(err_msg) => {
-            log::error!("{err_msg}");
-            let err_msg = err_msg.to_string();
+        Err(e) => {
+            tracing::error!(code = ?e.code(), error = %e, "Unable to receive the message");
+            // Too many errors are being reported to the listener
+            let Some(in_flight) = limiter.admit_error() else {
+                return;
+            };
             CB_RUNTIME.spawn(async move {
-                listener
-                    .on_error(UStatus::fail_with_code(UCode::INTERNAL, err_msg))
-                    .await;
+                let _guards = (callback_guard, in_flight);
+                listener.on_error(UStatus::from(e)).await;
             });
         }
     }
 }
 
+// The checks and the decryption of the inbound message before calling the listener
+pub(crate) struct InboundCheck {
+    authority_name: String,
+    strict_authority: bool,
+    signing: Option<Arc<SigningConfig>>,
+    encryption: Option<Arc<EncryptionConfig>>,
+    peer_certificates: Option<Arc<PeerCertificateCheck>>,
+}
+
+impl InboundCheck {
+    // Check the message is from its source authority and signed by it
+    fn verify(
+        &self,
+        zenoh_key: &str,
+        attachment: &Attachment,
+        attributes: &UAttributes,
+        payload: &[u8],
+    ) -> Result<(), TransportError> {
+        if self.strict_authority {
+            UPClientZenoh::check_inbound_authority(zenoh_key, attributes)?;
+        }
+        if let Some(signing) = &self.signing {
+            signing.verify(attachment, attributes, payload)?;
+        }
+        if let Some(peer_certificates) = &self.peer_certificates {
+            peer_certificates.verify(attachment, attributes, payload)?;
+        }
+        Ok(())
+    }
+
+    // Return the payload to deliver to the listener
+    pub(crate) fn check(
+        &self,
+        zenoh_key: &str,
+        attachment: &Attachment,
+        attributes: &UAttributes,
+        payload: &[u8],
+    ) -> Result<Vec<u8>, TransportError> {
+        self.verify(zenoh_key, attachment, attributes, payload)?;
+        encryption::decrypt_payload(
+            self.encryption.as_deref(),
+            &self.authority_name,
+            attachment,
+            attributes,
+            payload,
+        )
+    }
+
+    // Transform the Zenoh sample of Publish / Notification back to the verified and decrypted UMessage
+    pub(crate) fn sample_to_umessage(&self, sample: &Sample) -> Result<UMessage, TransportError> {
+        let attachment = sample
+            .attachment()
+            .ok_or_else(|| TransportError::AttachmentDecode("No attachment".to_string()))?;
+        let attributes = UPClientZenoh::attachment_to_uattributes(attachment)
+            .map_err(|e| TransportError::AttachmentDecode(format!("{e:?}")))?;
+        let payload = self.check(
+            sample.key_expr.as_str(),
+            attachment,
+            &attributes,
+            &sample.payload.contiguous(),
+        )?;
+        Ok(UMessage {
+            attributes: Some(attributes).into(),
+            payload: Some(payload.into()),
+            ..Default::default()
+        })
+    }
+}
+
 impl UPClientZenoh {
+    pub(crate) fn inbound_check(&self) -> InboundCheck {
+        InboundCheck {
+            authority_name: self.authority_name.clone(),
+            strict_authority: self.authority_policy != AuthorityPolicy::Trust,
+            signing: self.signing.clone(),
+            encryption: self.encryption.clone(),
+            peer_certificates: self.peer_certificates.clone(),
+        }
+    }
+
+    // Encrypt the payload if its topic is configured, and then sign the outgoing message
+    // if signing or the peer certificate check is enabled
+    pub(crate) fn seal_payload<'a>(
+        &self,
+        attachment: &mut AttachmentBuilder,
+        attributes: &UAttributes,
+        payload: &'a [u8],
+    ) -> Result<Cow<'a, [u8]>, TransportError> {
+        let mut payload = Cow::Borrowed(payload);
+        if let Some(encryption) = &self.encryption {
+            if let Some(ciphertext) =
+                encryption.encrypt(attachment, &self.authority_name, attributes, &payload)?
+            {
+                payload = Cow::Owned(ciphertext);
+            }
+        }
+        if let Some(signing) = &self.signing {
+            signing.sign(attachment, &self.authority_name, attributes, &payload)?;
+        }
+        if let Some(peer_certificates) = &self.peer_certificates {
+            peer_certificates.sign(attachment, &self.authority_name, attributes, &payload)?;
+        }
+        Ok(payload)
+    }
+
     async fn send_publish_notification(
         &self,
         zenoh_key: &str,
         payload: &[u8],
         attributes: UAttributes,
-    ) -> Result<(), UStatus> {
+    ) -> Result<(), TransportError> {
         // Transform UAttributes to user attachment in Zenoh
-        let Ok(attachment) = UPClientZenoh::uattributes_to_attachment(&attributes) else {
-            let msg = "Unable to transform UAttributes to attachment".to_string();
-            log::error!("{msg}");
-            return Err(UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg));
-        };
+        let mut attachment = UPClientZenoh::uattributes_to_attachment(&attributes)
+            .map_err(TransportError::AttachmentEncode)?;
+        let payload = self.seal_payload(&mut attachment, &attributes, payload)?;
+        let attachment = attachment.build();
 
         // Map the priority to Zenoh
-        let priority =
-            UPClientZenoh::map_zenoh_priority(attributes.priority.enum_value().map_err(|_| {
-                let msg = "Unable to map to Zenoh priority".to_string();
-                log::error!("{msg}");
-                UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg)
-            })?);
+        let priority = UPClientZenoh::map_zenoh_priority(
+            attributes
+                .priority
+                .enum_value()
+                .map_err(|v| TransportError::InvalidMessage(format!("Unknown priority {v}")))?,
+        );
+
+        // Hold it while nobody can receive it, or behind the queued ones to keep the order
+        if let Some(queue) = &self.offline_queue {
+            if !queue.is_empty()
+                || !has_route(
+                    &self.connectivity_status(),
+                    &self.local_listeners(),
+                    zenoh_key,
+                )
+            {
+                queue.push(QueuedPut::new(
+                    zenoh_key,
+                    &payload,
+                    &attachment,
+                    priority,
+                    &attributes,
+                ));
+                return Ok(());
+            }
+        }
+
+        // Fail fast if nobody can receive it
+        self.check_route(zenoh_key)?;
 
         // Send data
         let putbuilder = self
             .session
-            .put(zenoh_key, payload)
+            .put(zenoh_key, payload.as_ref())
             .priority(priority)
-            .with_attachment(attachment.build());
+            .with_attachment(attachment);
         putbuilder
             .res()
             .await
-            .map_err(|_| UStatus::fail_with_code(UCode::INTERNAL, "Unable to send with Zenoh"))?;
+            .map_err(|e| TransportError::zenoh("put", e))?;
 
         Ok(())
     }
@@ -133,13 +290,15 @@ impl UPClientZenoh {
         zenoh_key: &str,
         payload: &[u8],
         attributes: UAttributes,
-    ) -> Result<(), UStatus> {
+    ) -> Result<(), TransportError> {
         // Transform UAttributes to user attachment in Zenoh
-        let Ok(attachment) = UPClientZenoh::uattributes_to_attachment(&attributes) else {
-            let msg = "Unable to transform UAttributes to attachment".to_string();
-            log::error!("{msg}");
-            return Err(UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg));
-        };
+        let mut attachment = UPClientZenoh::uattributes_to_attachment(&attributes)
+            .map_err(TransportError::AttachmentEncode)?;
+        let payload = self.seal_payload(&mut attachment, &attributes, payload)?;
+
+        // Fail fast if nobody serves the method
+        self.check_method_available(attributes.sink.get_or_default())
+            .await?;
 
         // Retrieve the callback
         let zenoh_key = keyexpr::new(zenoh_key).unwrap();
@@ -152,45 +311,65 @@ impl UPClientZenoh {
             }
         }
         let Some(resp_callback) = resp_callback else {
-            let msg = "Unable to get callback".to_string();
-            log::error!("{msg}");
-            return Err(UStatus::fail_with_code(UCode::INTERNAL, msg));
+            return Err(TransportError::NoResponseListener);
         };
+        let metrics = self.metrics.clone();
+        let inbound_check = self.inbound_check();
+        let rate_limiter = self.rate_limiter.clone();
+        // Remember the deadline to tell the timeout from the replied error
+        let timeout = self.query_timeout(attributes.ttl);
+        let deadline = Instant::now() + timeout;
+        let mut rpc_guard = self.metrics.start_rpc(deadline);
         let zenoh_callback = move |reply: Reply| {
+            // The response callback is blocking, so it's in flight until this returns
+            let _in_flight = rate_limiter.track();
+            let mut respond = |result: Result<UMessage, TransportError>| {
+                rpc_guard.finish(&result);
+                invoke_block_callback(&resp_callback, result);
+            };
             match reply.sample {
                 Ok(sample) => {
                     // Get UAttribute from the attachment
                     let Some(attachment) = sample.attachment() else {
-                        invoke_block_callback(&resp_callback, Err("Unable to get the attachment"));
+                        metrics.on_attachment_error();
+                        respond(Err(TransportError::AttachmentDecode(
+                            "No attachment".to_string(),
+                        )));
                         return;
                     };
                     let u_attribute = match UPClientZenoh::attachment_to_uattributes(attachment) {
                         Ok(uattr) => uattr,
                         Err(e) => {
-                            invoke_block_callback(
-                                &resp_callback,
-                                Err(&format!(
-                                    "Transform attachment to UAttributes failed: {e:?}"
-                                )),
-                            );
+                            metrics.on_attachment_error();
+                            respond(Err(TransportError::AttachmentDecode(format!("{e:?}"))));
+                            return;
+                        }
+                    };
+                    let payload = match inbound_check.check(
+                        sample.key_expr.as_str(),
+                        attachment,
+                        &u_attribute,
+                        &sample.payload.contiguous(),
+                    ) {
+                        Ok(payload) => payload,
+                        Err(e) => {
+                            respond(Err(e));
                             return;
                         }
                     };
+                    metrics.on_receive(&u_attribute);
+                    // Handle the response within the span linked to the responder
+                    let span = trace_context::receive_span(&u_attribute, attachment);
+                    let _entered = span.enter();
                     // Create UMessage
-                    invoke_block_callback(
-                        &resp_callback,
-                        Ok(UMessage {
-                            attributes: Some(u_attribute).into(),
-                            payload: Some(sample.payload.contiguous().to_vec().into()),
-                            ..Default::default()
-                        }),
-                    );
+                    respond(Ok(UMessage {
+                        attributes: Some(u_attribute).into(),
+                        payload: Some(payload.into()),
+                        ..Default::default()
+                    }));
                 }
                 Err(e) => {
-                    invoke_block_callback(
-                        &resp_callback,
-                        Err(&format!("Error while parsing Zenoh reply: {e:?}")),
-                    );
+                    respond(Err(TransportError::from_reply_error(&e, deadline)));
                 }
             }
         };
@@ -203,40 +382,35 @@ impl UPClientZenoh {
             .with_value(value)
             .with_attachment(attachment.build())
             .target(QueryTarget::BestMatching)
-            .timeout(Duration::from_millis(u64::from(
-                attributes.ttl.unwrap_or(1000),
-            )))
-            .callback(zenoh_callback);
-        getbuilder.res().await.map_err(|e| {
-            let msg = format!("Unable to send get with Zenoh: {e:?}");
-            log::error!("{msg}");
-            UStatus::fail_with_code(UCode::INTERNAL, msg)
-        })?;
+            .timeout(timeout)
+            .callback_mut(zenoh_callback);
+        getbuilder
+            .res()
+            .await
+            .map_err(|e| TransportError::zenoh("get", e))?;
 
         Ok(())
     }
 
-    async fn send_response(&self, payload: &[u8], attributes: UAttributes) -> Result<(), UStatus> {
+    async fn send_response(
+        &self,
+        payload: &[u8],
+        attributes: UAttributes,
+    ) -> Result<(), TransportError> {
         // Transform UAttributes to user attachment in Zenoh
-        let Ok(attachment) = UPClientZenoh::uattributes_to_attachment(&attributes) else {
-            let msg = "Unable to transform UAttributes to attachment".to_string();
-            log::error!("{msg}");
-            return Err(UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg));
-        };
+        let mut attachment = UPClientZenoh::uattributes_to_attachment(&attributes)
+            .map_err(TransportError::AttachmentEncode)?;
+        let payload = self.seal_payload(&mut attachment, &attributes, payload)?;
 
         // Find out the corresponding query from HashMap
         let reqid = attributes.reqid.to_string();
-        let query = self
-            .query_map
-            .lock()
-            .unwrap()
-            .remove(&reqid)
-            .ok_or_else(|| {
-                let msg = "query doesn't exist".to_string();
-                log::error!("{msg}");
-                UStatus::fail_with_code(UCode::INTERNAL, msg)
-            })?
-            .clone();
+        let query = {
+            let mut query_map = self.query_map.lock().unwrap();
+            let query = query_map.remove(&reqid);
+            self.metrics.set_pending_queries(query_map.len());
+            query
+        }
+        .ok_or(TransportError::UnknownRequest(reqid))?;
 
         // Send back the query
         let value = Value::new(payload.to_vec().into());
@@ -245,17 +419,11 @@ impl UPClientZenoh {
             .reply(reply)
             .with_attachment(attachment.build())
             .map_err(|_| {
-                let msg = "Unable to add attachment";
-                log::error!("{msg}");
-                UStatus::fail_with_code(UCode::INTERNAL, msg)
+                TransportError::zenoh("add the attachment to reply", "attachment rejected")
             })?
             .res()
             .await
-            .map_err(|e| {
-                let msg = format!("Unable to reply with Zenoh: {e:?}");
-                log::error!("{msg}");
-                UStatus::fail_with_code(UCode::INTERNAL, msg)
-            })?;
+            .map_err(|e| TransportError::zenoh("reply", e))?;
 
         Ok(())
     }
@@ -264,53 +432,91 @@ impl UPClientZenoh {
         &self,
         zenoh_key: &String,
         listener: Arc<dyn UListener>,
-    ) -> Result<(), UStatus> {
+    ) -> Result<(), TransportError> {
         // Setup callback
         let listener_cloned = listener.clone();
+        let metrics = self.metrics.clone();
+        let inbound_check = self.inbound_check();
+        let limiter = ListenerLimiter::new(self.rate_limiter.clone(), self.metrics.clone());
         let callback = move |sample: Sample| {
             // Get the UAttribute from Zenoh user attachment
             let Some(attachment) = sample.attachment() else {
-                spawn_nonblock_callback(&listener_cloned, Err("Unable to get attachment"));
+                metrics.on_attachment_error();
+                spawn_nonblock_callback(
+                    &metrics,
+                    &listener_cloned,
+                    &limiter,
+                    Err(TransportError::AttachmentDecode(
+                        "No attachment".to_string(),
+                    )),
+                );
                 return;
             };
             let u_attribute = match UPClientZenoh::attachment_to_uattributes(attachment) {
                 Ok(uattributes) => uattributes,
                 Err(e) => {
+                    metrics.on_attachment_error();
                     spawn_nonblock_callback(
+                        &metrics,
                         &listener_cloned,
-                        Err(&format!(
-                            "Unable to transform attachment to UAttributes: {e:?}"
-                        )),
+                        &limiter,
+                        Err(TransportError::AttachmentDecode(format!("{e:?}"))),
                     );
                     return;
                 }
             };
+            // Admit the message before verifying and decrypting it
+            let in_flight = match limiter.admit(&u_attribute) {
+                Ok(in_flight) => in_flight,
+                Err(report) => {
+                    if let Some(e) = report {
+                        spawn_nonblock_callback(&metrics, &listener_cloned, &limiter, Err(e));
+                    }
+                    return;
+                }
+            };
+            let payload = match inbound_check.check(
+                sample.key_expr.as_str(),
+                attachment,
+                &u_attribute,
+                &sample.payload.contiguous(),
+            ) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    // The error callback counts as in-flight by itself
+                    drop(in_flight);
+                    spawn_nonblock_callback(&metrics, &listener_cloned, &limiter, Err(e));
+                    return;
+                }
+            };
+            metrics.on_receive(&u_attribute);
+            let span = trace_context::receive_span(&u_attribute, attachment);
             // Create UMessage
             let msg = UMessage {
                 attributes: Some(u_attribute).into(),
-                payload: Some(sample.payload.contiguous().to_vec().into()),
+                payload: Some(payload.into()),
                 ..Default::default()
             };
-            spawn_nonblock_callback(&listener_cloned, Ok(msg));
+            spawn_nonblock_callback(
+                &metrics,
+                &listener_cloned,
+                &limiter,
+                Ok((msg, span, in_flight)),
+            );
         };
 
         // Create Zenoh subscriber
-        if let Ok(subscriber) = self
+        let subscriber = self
             .session
             .declare_subscriber(zenoh_key)
             .callback_mut(callback)
             .res()
             .await
-        {
-            self.subscriber_map.lock().unwrap().insert(
-                (zenoh_key.clone(), ComparableListener::new(listener)),
-                subscriber,
-            );
-        } else {
-            let msg = "Unable to register callback with Zenoh";
-            log::error!("{msg}");
-            return Err(UStatus::fail_with_code(UCode::INTERNAL, msg));
-        }
+            .map_err(|e| TransportError::zenoh("declare subscriber", e))?;
+        self.subscriber_map.lock().unwrap().insert(
+            (zenoh_key.clone(), ComparableListener::new(listener)),
+            subscriber,
+        );
 
         Ok(())
     }
@@ -319,60 +525,99 @@ impl UPClientZenoh {
         &self,
         zenoh_key: &String,
         listener: Arc<dyn UListener>,
-    ) -> Result<(), UStatus> {
+    ) -> Result<(), TransportError> {
         // Setup callback
         let listener_cloned = listener.clone();
         let query_map = self.query_map.clone();
+        let metrics = self.metrics.clone();
+        let inbound_check = self.inbound_check();
+        let limiter = ListenerLimiter::new(self.rate_limiter.clone(), self.metrics.clone());
         let callback = move |query: Query| {
             // Create UAttribute from Zenoh user attachment
             let Some(attachment) = query.attachment() else {
-                spawn_nonblock_callback(&listener_cloned, Err("Unable to get attachment"));
+                metrics.on_attachment_error();
+                spawn_nonblock_callback(
+                    &metrics,
+                    &listener_cloned,
+                    &limiter,
+                    Err(TransportError::AttachmentDecode(
+                        "No attachment".to_string(),
+                    )),
+                );
                 return;
             };
             let u_attribute = match UPClientZenoh::attachment_to_uattributes(attachment) {
                 Ok(uattributes) => uattributes,
                 Err(e) => {
+                    metrics.on_attachment_error();
                     spawn_nonblock_callback(
+                        &metrics,
                         &listener_cloned,
-                        Err(&format!(
-                            "Unable to transform user attachment to UAttributes: {e:?}"
-                        )),
+                        &limiter,
+                        Err(TransportError::AttachmentDecode(format!("{e:?}"))),
                     );
                     return;
                 }
             };
+            // Admit the request before verifying and decrypting it
+            let in_flight = match limiter.admit(&u_attribute) {
+                Ok(in_flight) => in_flight,
+                Err(report) => {
+                    if let Some(e) = report {
+                        spawn_nonblock_callback(&metrics, &listener_cloned, &limiter, Err(e));
+                    }
+                    return;
+                }
+            };
+            let value = query.value().map(|value| value.payload.contiguous());
+            let payload = match inbound_check.check(
+                query.key_expr().as_str(),
+                attachment,
+                &u_attribute,
+                value.as_deref().unwrap_or_default(),
+            ) {
+                // The request without the value has no payload
+                Ok(payload) => value.is_some().then_some(payload),
+                Err(e) => {
+                    // The error callback counts as in-flight by itself
+                    drop(in_flight);
+                    spawn_nonblock_callback(&metrics, &listener_cloned, &limiter, Err(e));
+                    return;
+                }
+            };
+            metrics.on_receive(&u_attribute);
+            let span = trace_context::receive_span(&u_attribute, attachment);
             // Create UMessage and store the query into HashMap (Will be used in send_response)
             let msg = UMessage {
                 attributes: Some(u_attribute.clone()).into(),
-                payload: query
-                    .value()
-                    .map(|value| value.payload.contiguous().to_vec().into()),
+                payload: payload.map(Into::into),
                 ..Default::default()
             };
-            query_map
-                .lock()
-                .unwrap()
-                .insert(u_attribute.id.to_string(), query);
-            spawn_nonblock_callback(&listener_cloned, Ok(msg));
+            {
+                let mut query_map = query_map.lock().unwrap();
+                query_map.insert(u_attribute.id.to_string(), query);
+                metrics.set_pending_queries(query_map.len());
+            }
+            spawn_nonblock_callback(
+                &metrics,
+                &listener_cloned,
+                &limiter,
+                Ok((msg, span, in_flight)),
+            );
         };
 
         // Create Zenoh queryable
-        if let Ok(queryable) = self
+        let queryable = self
             .session
             .declare_queryable(zenoh_key)
             .callback_mut(callback)
             .res()
             .await
-        {
-            self.queryable_map.lock().unwrap().insert(
-                (zenoh_key.clone(), ComparableListener::new(listener)),
-                queryable,
-            );
-        } else {
-            let msg = "Unable to register callback with Zenoh".to_string();
-            log::error!("{msg}");
-            return Err(UStatus::fail_with_code(UCode::INTERNAL, msg));
-        }
+            .map_err(|e| TransportError::zenoh("declare queryable", e))?;
+        self.queryable_map.lock().unwrap().insert(
+            (zenoh_key.clone(), ComparableListener::new(listener)),
+            queryable,
+        );
 
         Ok(())
     }
@@ -387,20 +632,20 @@ impl UPClientZenoh {
     }
 }
 
-#[async_trait]
-impl UTransport for UPClientZenoh {
-    async fn send(&self, message: UMessage) -> Result<(), UStatus> {
-        let attributes = *message.attributes.0.ok_or_else(|| {
-            let msg = "Invalid UAttributes".to_string();
-            log::error!("{msg}");
-            UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg)
-        })?;
+impl UPClientZenoh {
+    async fn send_message(&self, message: UMessage) -> Result<(), TransportError> {
+        self.check_open()?;
+        let mut attributes = *message
+            .attributes
+            .0
+            .ok_or_else(|| TransportError::InvalidMessage("Empty UAttributes".to_string()))?;
+
+        // Only the local authority can be the source
+        self.check_outbound_authority(&mut attributes)?;
 
         // Get Zenoh key
         let source = *attributes.clone().source.0.ok_or_else(|| {
-            let msg = "attributes.source should not be empty".to_string();
-            log::error!("{msg}");
-            UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg)
+            TransportError::InvalidMessage("attributes.source should not be empty".to_string())
         })?;
         let zenoh_key = if let Some(sink) = attributes.sink.clone().0 {
             self.to_zenoh_key_string(&source, Some(&sink))
@@ -408,6 +653,14 @@ impl UTransport for UPClientZenoh {
             self.to_zenoh_key_string(&source, None)
         };
 
+        // Check the permission
+        self.authorize(
+            Operation::Send,
+            attributes.type_.enum_value_or_default(),
+            &source,
+            attributes.sink.as_ref(),
+        )?;
+
         // Get payload
         let payload = if let Some(payload) = message.payload {
             payload.to_vec()
@@ -419,16 +672,14 @@ impl UTransport for UPClientZenoh {
         match attributes
             .type_
             .enum_value()
-            .map_err(|_| UStatus::fail_with_code(UCode::INTERNAL, "Unable to parse type"))?
+            .map_err(|v| TransportError::InvalidMessage(format!("Unknown message type {v}")))?
         {
             UMessageType::UMESSAGE_TYPE_PUBLISH => {
                 UAttributesValidators::Publish
                     .validator()
                     .validate(&attributes)
                     .map_err(|e| {
-                        let msg = format!("Wrong Publish UAttributes: {e:?}");
-                        log::error!("{msg}");
-                        UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg)
+                        TransportError::InvalidMessage(format!("Wrong Publish UAttributes: {e:?}"))
                     })?;
                 // Send Publish
                 self.send_publish_notification(&zenoh_key, &payload, attributes)
@@ -439,9 +690,9 @@ impl UTransport for UPClientZenoh {
                     .validator()
                     .validate(&attributes)
                     .map_err(|e| {
-                        let msg = format!("Wrong Notification UAttributes: {e:?}");
-                        log::error!("{msg}");
-                        UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg)
+                        TransportError::InvalidMessage(format!(
+                            "Wrong Notification UAttributes: {e:?}"
+                        ))
                     })?;
                 // Send Publish
                 self.send_publish_notification(&zenoh_key, &payload, attributes)
@@ -452,9 +703,7 @@ impl UTransport for UPClientZenoh {
                     .validator()
                     .validate(&attributes)
                     .map_err(|e| {
-                        let msg = format!("Wrong Request UAttributes: {e:?}");
-                        log::error!("{msg}");
-                        UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg)
+                        TransportError::InvalidMessage(format!("Wrong Request UAttributes: {e:?}"))
                     })?;
                 // Send Request
                 self.send_request(&zenoh_key, &payload, attributes).await
@@ -464,20 +713,30 @@ impl UTransport for UPClientZenoh {
                     .validator()
                     .validate(&attributes)
                     .map_err(|e| {
-                        let msg = format!("Wrong Response UAttributes: {e:?}");
-                        log::error!("{msg}");
-                        UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg)
+                        TransportError::InvalidMessage(format!("Wrong Response UAttributes: {e:?}"))
                     })?;
                 // Send Response
                 self.send_response(&payload, attributes).await
             }
-            UMessageType::UMESSAGE_TYPE_UNSPECIFIED => {
-                let msg = "Wrong Message type in UAttributes".to_string();
-                log::error!("{msg}");
-                Err(UStatus::fail_with_code(UCode::INVALID_ARGUMENT, msg))
-            }
+            UMessageType::UMESSAGE_TYPE_UNSPECIFIED => Err(TransportError::InvalidMessage(
+                "Wrong Message type in UAttributes".to_string(),
+            )),
         }
     }
+}
+
+#[async_trait]
+impl UTransport for UPClientZenoh {
+    async fn send(&self, message: UMessage) -> Result<(), UStatus> {
+        let message_type = message.attributes.type_.enum_value_or_default();
+        let priority = message.attributes.priority.enum_value_or_default();
+        let result = self
+            .send_message(message)
+            .await
+            .map_err(TransportError::into_status);
+        self.metrics.on_send(message_type, priority, &result);
+        result
+    }
 
     async fn receive(
         &self,
@@ -485,7 +744,7 @@ impl UTransport for UPClientZenoh {
         _sink_filter: Option<&UUri>,
     ) -> Result<UMessage, UStatus> {
         let msg = "Not implemented".to_string();
-        log::error!("{msg}");
+        tracing::error!(code = ?UCode::UNIMPLEMENTED, "{msg}");
         Err(UStatus::fail_with_code(UCode::UNIMPLEMENTED, msg))
     }
 
@@ -495,20 +754,56 @@ impl UTransport for UPClientZenoh {
         sink_filter: Option<&UUri>,
         listener: Arc<dyn UListener>,
     ) -> Result<(), UStatus> {
-        let flag = UPClientZenoh::get_listener_message_type(source_filter, sink_filter)?;
+        self.check_open().map_err(TransportError::into_status)?;
+        let flag = get_listener_message_type(source_filter, sink_filter)?;
+        // Check the permission for each message type
+        for (message_flag, message_type) in [
+            (MessageFlag::Publish, UMessageType::UMESSAGE_TYPE_PUBLISH),
+            (
+                MessageFlag::Notification,
+                UMessageType::UMESSAGE_TYPE_NOTIFICATION,
+            ),
+            (MessageFlag::Request, UMessageType::UMESSAGE_TYPE_REQUEST),
+            (MessageFlag::Response, UMessageType::UMESSAGE_TYPE_RESPONSE),
+        ] {
+            if flag.contains(message_flag) {
+                self.authorize(
+                    Operation::RegisterListener,
+                    message_type,
+                    source_filter,
+                    sink_filter,
+                )
+                .map_err(TransportError::into_status)?;
+            }
+        }
         // Publish & Notification
         if flag.contains(MessageFlag::Publish) || flag.contains(MessageFlag::Notification) {
             // Get Zenoh key
             let zenoh_key = self.to_zenoh_key_string(source_filter, sink_filter);
             self.register_publish_notification_listener(&zenoh_key, listener.clone())
-                .await?;
+                .await
+                .map_err(TransportError::into_status)?;
         }
         // RPC request
         if flag.contains(MessageFlag::Request) {
             // Get Zenoh key
             let zenoh_key = self.to_zenoh_key_string(source_filter, sink_filter);
             self.register_request_listener(&zenoh_key, listener.clone())
-                .await?;
+                .await
+                .map_err(TransportError::into_status)?;
+            // Announce the RPC method is available. Undeclare the queryable if unable to.
+            if let Some(sink_filter) = sink_filter {
+                if let Err(e) = self
+                    .declare_method_liveliness(sink_filter, listener.clone())
+                    .await
+                {
+                    self.queryable_map
+                        .lock()
+                        .unwrap()
+                        .remove(&(zenoh_key, ComparableListener::new(listener.clone())));
+                    return Err(e);
+                }
+            }
         }
         // RPC response
         if flag.contains(MessageFlag::Response) {
@@ -533,20 +828,23 @@ impl UTransport for UPClientZenoh {
         sink_filter: Option<&UUri>,
         listener: Arc<dyn UListener>,
     ) -> Result<(), UStatus> {
-        let flag = UPClientZenoh::get_listener_message_type(source_filter, sink_filter)?;
+        let flag = get_listener_message_type(source_filter, sink_filter)?;
         // Publish & Notification
         if flag.contains(MessageFlag::Publish) || flag.contains(MessageFlag::Notification) {
             // Get Zenoh key
             let zenoh_key = self.to_zenoh_key_string(source_filter, sink_filter);
-            if self
-                .subscriber_map
-                .lock()
-                .unwrap()
-                .remove(&(zenoh_key.clone(), ComparableListener::new(listener.clone())))
-                .is_none()
+            // The listener might be registered by register_listener_with_last_value
+            let key = (zenoh_key.clone(), ComparableListener::new(listener.clone()));
+            if self.subscriber_map.lock().unwrap().remove(&key).is_none()
+                && self
+                    .querying_subscriber_map
+                    .lock()
+                    .unwrap()
+                    .remove(&key)
+                    .is_none()
             {
                 let msg = "Publish / Notifcation listener doesn't exist".to_string();
-                log::warn!("{msg}");
+                tracing::warn!(code = ?UCode::NOT_FOUND, "{msg}");
                 return Err(UStatus::fail_with_code(UCode::NOT_FOUND, msg));
             }
         }
@@ -562,9 +860,12 @@ impl UTransport for UPClientZenoh {
                 .is_none()
             {
                 let msg = "RPC request listener doesn't exist".to_string();
-                log::warn!("{msg}");
+                tracing::warn!(code = ?UCode::NOT_FOUND, "{msg}");
                 return Err(UStatus::fail_with_code(UCode::NOT_FOUND, msg));
             }
+            if let Some(sink_filter) = sink_filter {
+                self.undeclare_method_liveliness(sink_filter, listener.clone());
+            }
         }
         // RPC response
         if flag.contains(MessageFlag::Response) {
@@ -580,7 +881,7 @@ impl UTransport for UPClientZenoh {
                     .is_none()
                 {
                     let msg = "RPC response callback doesn't exist".to_string();
-                    log::warn!("{msg}");
+                    tracing::warn!(code = ?UCode::NOT_FOUND, "{msg}");
                     return Err(UStatus::fail_with_code(UCode::NOT_FOUND, msg));
                 }
             } else {
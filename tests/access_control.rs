@@ -0,0 +1,134 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+pub mod test_lib;
+
+use async_trait::async_trait;
+use std::sync::Arc;
+use test_case::test_case;
+use up_rust::{UCode, UListener, UMessage, UMessageBuilder, UPayloadFormat, UStatus, UTransport};
+use up_transport_zenoh::{access_control::RuleFileAccessControl, TransportError, UPClientZenoh};
+
+const RULES: &str = "
+# Only the resource 8000 can be published
+allow send publish vehicle //vehicle/1/1/8000
+# Any listener for Publish
+allow register publish * //*/FFFF/FF/FFFF
+# Only the method 2/1/1 can be served
+allow register request vehicle //*/FFFF/FF/FFFF //vehicle/2/1/1
+deny * * * //*/FFFF/FF/FFFF
+";
+
+struct DummyListener;
+#[async_trait]
+impl UListener for DummyListener {
+    async fn on_receive(&self, _msg: UMessage) {}
+    async fn on_error(&self, err: UStatus) {
+        panic!("Internal Error: {err:?}");
+    }
+}
+
+#[test_case(0x8000, true; "Allowed source")]
+#[test_case(0x8001, false; "Denied source")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_access_control_send(resource_id: u16, allowed: bool) {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient = UPClientZenoh::builder("vehicle")
+        .with_runtime(runtime)
+        .with_access_control(Arc::new(RuleFileAccessControl::parse(RULES).unwrap()))
+        .build()
+        .await
+        .unwrap();
+
+    let umessage = UMessageBuilder::publish(test_lib::new_uuri("vehicle", 1, 1, resource_id))
+        .build_with_payload("data".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+    let result = upclient.send(umessage).await;
+    if allowed {
+        assert!(result.is_ok());
+    } else {
+        assert_eq!(result.unwrap_err().get_code(), UCode::PERMISSION_DENIED);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_access_control_register_listener() {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient = UPClientZenoh::builder("vehicle")
+        .with_runtime(runtime)
+        .with_access_control(Arc::new(RuleFileAccessControl::parse(RULES).unwrap()))
+        .build()
+        .await
+        .unwrap();
+    let listener = Arc::new(DummyListener);
+    let any_uuri = test_lib::new_uuri("*", 0xFFFF, 0xFF, 0xFFFF);
+
+    // Publish listener is allowed
+    upclient
+        .register_listener(&any_uuri, None, listener.clone())
+        .await
+        .unwrap();
+
+    // Only the allowed method can be served
+    upclient
+        .register_listener(
+            &any_uuri,
+            Some(&test_lib::new_uuri("vehicle", 2, 1, 1)),
+            listener.clone(),
+        )
+        .await
+        .unwrap();
+    let result = upclient
+        .register_listener(
+            &any_uuri,
+            Some(&test_lib::new_uuri("vehicle", 2, 1, 2)),
+            listener.clone(),
+        )
+        .await;
+    assert_eq!(result.unwrap_err().get_code(), UCode::PERMISSION_DENIED);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_access_control_rpc() {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient = UPClientZenoh::builder("vehicle")
+        .with_runtime(runtime)
+        .with_access_control(Arc::new(RuleFileAccessControl::parse(RULES).unwrap()))
+        .build()
+        .await
+        .unwrap();
+    let method = test_lib::new_uuri("vehicle", 2, 1, 1);
+    let request = UMessageBuilder::request(method, test_lib::new_uuri("vehicle", 1, 1, 0), 1000)
+        .build_with_payload("data".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+
+    // No rule allows sending the request
+    let result = upclient.invoke_rpc(request).await;
+    assert!(matches!(result, Err(TransportError::PermissionDenied(_))));
+}
+
+#[test_case("allow send publish"; "Missing fields")]
+#[test_case("permit send publish * //vehicle/1/1/8000"; "Unknown action")]
+#[test_case("allow send event * //vehicle/1/1/8000"; "Unknown message type")]
+fn test_access_control_invalid_rule(rule: &str) {
+    let result = RuleFileAccessControl::parse(rule);
+    assert_eq!(result.unwrap_err().get_code(), UCode::INVALID_ARGUMENT);
+}
@@ -0,0 +1,135 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+pub mod test_lib;
+
+use protobuf::Message;
+use std::sync::Arc;
+use test_lib::{CheckListener, RecordListener};
+use up_rust::{UCode, UMessageBuilder, UPayloadFormat, UTransport};
+use up_transport_zenoh::{AuthorityPolicy, TransportError, UPClientZenoh};
+use zenoh::{prelude::r#async::*, sample::AttachmentBuilder};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_authority_policy_reject() {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient = UPClientZenoh::builder("vehicle")
+        .with_runtime(runtime)
+        .with_authority_policy(AuthorityPolicy::Reject)
+        .build()
+        .await
+        .unwrap();
+    let publish = |authority: &str| {
+        UMessageBuilder::publish(test_lib::new_uuri(authority, 1, 1, 0x8000))
+            .build_with_payload("data".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+            .unwrap()
+    };
+
+    // My authority and the omitted one are allowed
+    upclient.send(publish("vehicle")).await.unwrap();
+    upclient.send(publish("")).await.unwrap();
+    // Impersonating another authority is rejected
+    let result = upclient.send(publish("other")).await;
+    assert_eq!(result.unwrap_err().get_code(), UCode::PERMISSION_DENIED);
+    let request = UMessageBuilder::request(
+        test_lib::new_uuri("vehicle", 2, 1, 1),
+        test_lib::new_uuri("other", 1, 1, 0),
+        1000,
+    )
+    .build_with_payload("data".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+    .unwrap();
+    let result = upclient.invoke_rpc(request).await;
+    assert!(matches!(result, Err(TransportError::PermissionDenied(_))));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_authority_policy_rewrite() {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_send = UPClientZenoh::builder("vehicle")
+        .with_runtime(runtime.clone())
+        .with_authority_policy(AuthorityPolicy::Rewrite)
+        .build()
+        .await
+        .unwrap();
+    let upclient_recv = test_lib::create_up_client_zenoh(&runtime, "subscriber")
+        .await
+        .unwrap();
+    let listener = Arc::new(RecordListener::new());
+    upclient_recv
+        .register_listener(
+            &test_lib::new_uuri("vehicle", 1, 1, 0x8000),
+            None,
+            listener.clone(),
+        )
+        .await
+        .unwrap();
+
+    // The source authority is rewritten to mine, so the listener of my authority receives it
+    let umessage = UMessageBuilder::publish(test_lib::new_uuri("other", 1, 1, 0x8000))
+        .build_with_payload("data".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+    upclient_send.send(umessage).await.unwrap();
+    test_lib::wait_until(|| listener.received.lock().unwrap().len() == 1).await;
+    assert_eq!(*listener.received.lock().unwrap(), vec!["data"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_authority_mismatch_inbound() {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_recv = UPClientZenoh::builder("subscriber")
+        .with_runtime(runtime.clone())
+        .with_authority_policy(AuthorityPolicy::Reject)
+        .build()
+        .await
+        .unwrap();
+    let listener = Arc::new(CheckListener::new());
+    upclient_recv
+        .register_listener(
+            &test_lib::new_uuri("victim", 1, 1, 0x8000),
+            None,
+            listener.clone(),
+        )
+        .await
+        .unwrap();
+
+    // Forge the message whose Zenoh key claims another authority than its UAttributes
+    let umessage = UMessageBuilder::publish(test_lib::new_uuri("attacker", 1, 1, 0x8000))
+        .build_with_payload("data".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+    let mut attachment = AttachmentBuilder::new();
+    attachment.insert("", &[1u8]);
+    attachment.insert("", &umessage.attributes.write_to_bytes().unwrap());
+    let session = zenoh::init(runtime).res().await.unwrap();
+    session
+        .put("up/victim/1/1/8000/{}/{}/{}/{}", "data")
+        .with_attachment(attachment.build())
+        .res()
+        .await
+        .unwrap();
+
+    // The message is rejected
+    test_lib::wait_until(|| listener.errors.lock().unwrap().len() == 1).await;
+    assert_eq!(*listener.received.lock().unwrap(), 0);
+    assert_eq!(
+        *listener.errors.lock().unwrap(),
+        vec![UCode::PERMISSION_DENIED]
+    );
+}
@@ -54,10 +54,11 @@ async fn test_blocking_user_callback(pub_uuri: &UUri) {
     test_lib::before_test();
 
     // Initialization
-    let upclient_send = test_lib::create_up_client_zenoh("nonblock_pub")
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_send = test_lib::create_up_client_zenoh(&runtime, "nonblock_pub")
         .await
         .unwrap();
-    let upclient_recv = test_lib::create_up_client_zenoh("nonblock_sub")
+    let upclient_recv = test_lib::create_up_client_zenoh(&runtime, "nonblock_sub")
         .await
         .unwrap();
 
@@ -67,8 +68,6 @@ async fn test_blocking_user_callback(pub_uuri: &UUri) {
         .register_listener(pub_uuri, None, pub_listener.clone())
         .await
         .unwrap();
-    // Waiting for listener to take effect
-    sleep(Duration::from_millis(1000)).await;
 
     // Send 2 UMessage
     let umsg0 = UMessageBuilder::publish(pub_uuri.clone())
@@ -82,10 +81,10 @@ async fn test_blocking_user_callback(pub_uuri: &UUri) {
 
     // Receive the data in reverse order due to the delay time
     // Waiting for the subscriber to receive 2nd data
-    sleep(Duration::from_millis(1000)).await;
+    test_lib::wait_until(|| pub_listener.get_recv_data() == "Pub 1").await;
     assert_eq!(pub_listener.get_recv_data(), "Pub 1".to_string());
     // Waiting for the subscriber to receive 1st data
-    sleep(Duration::from_millis(3000)).await;
+    test_lib::wait_until(|| pub_listener.get_recv_data() == "Pub 0").await;
     assert_eq!(pub_listener.get_recv_data(), "Pub 0".to_string());
 
     // Cleanup
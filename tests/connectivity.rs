@@ -0,0 +1,143 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+pub mod test_lib;
+
+use async_trait::async_trait;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use up_rust::{UCode, UListener, UMessage, UMessageBuilder, UPayloadFormat, UStatus, UTransport};
+use up_transport_zenoh::{
+    connectivity::{ConnectivityEvent, ConnectivityListener},
+    TransportError, UPClientZenoh,
+};
+
+struct RecordConnectivityListener {
+    events: Mutex<Vec<ConnectivityEvent>>,
+}
+#[async_trait]
+impl ConnectivityListener for RecordConnectivityListener {
+    async fn on_event(&self, event: ConnectivityEvent) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+struct DummyListener;
+#[async_trait]
+impl UListener for DummyListener {
+    async fn on_receive(&self, _msg: UMessage) {}
+    async fn on_error(&self, _err: UStatus) {}
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_connectivity_events() {
+    test_lib::before_test();
+
+    // Initialization
+    let endpoint = format!("tcp/127.0.0.1:{}", test_lib::free_port());
+    let upclient = UPClientZenoh::builder("vehicle")
+        .with_config(test_lib::tcp_config(&[&endpoint], &[]))
+        .build()
+        .await
+        .unwrap();
+    assert!(!upclient.connectivity_status().is_connected());
+    let listener = Arc::new(RecordConnectivityListener {
+        events: Mutex::new(vec![]),
+    });
+    upclient.register_connectivity_listener(listener.clone());
+
+    // The peer joins
+    let upclient_peer = UPClientZenoh::builder("cloud")
+        .with_config(test_lib::tcp_config(&[], &[&endpoint]))
+        .build()
+        .await
+        .unwrap();
+    test_lib::wait_until(|| listener.events.lock().unwrap().len() == 2).await;
+    {
+        let events = listener.events.lock().unwrap();
+        assert_eq!(events[0], ConnectivityEvent::Connected);
+        assert!(matches!(&events[1], ConnectivityEvent::PeerJoined(peer) if !peer.is_router));
+    }
+    assert_eq!(upclient.connectivity_status().peers.len(), 1);
+
+    // The peer leaves
+    Arc::new(upclient_peer)
+        .close(Duration::from_secs(1))
+        .await
+        .unwrap();
+    test_lib::wait_until(|| listener.events.lock().unwrap().len() == 4).await;
+    {
+        let events = listener.events.lock().unwrap();
+        assert!(matches!(&events[2], ConnectivityEvent::PeerLeft(peer) if !peer.is_router));
+        assert_eq!(events[3], ConnectivityEvent::Disconnected);
+    }
+
+    upclient
+        .unregister_connectivity_listener(listener.clone())
+        .unwrap();
+    assert!(upclient.unregister_connectivity_listener(listener).is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_send_fail_fast() {
+    test_lib::before_test();
+
+    // Initialization: the loopback runtime has no router or peer
+    let upclient = UPClientZenoh::builder("vehicle")
+        .with_runtime(test_lib::create_loopback_runtime().await)
+        .with_send_fail_fast(true)
+        .build()
+        .await
+        .unwrap();
+    let uuri = test_lib::new_uuri("vehicle", 1, 1, 0x8000);
+    let publish = || {
+        UMessageBuilder::publish(uuri.clone())
+            .build_with_payload("data".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+            .unwrap()
+    };
+
+    // Nobody can receive it
+    let result = upclient.send(publish()).await;
+    assert_eq!(result.unwrap_err().get_code(), UCode::UNAVAILABLE);
+
+    // The local listener can receive it
+    upclient
+        .register_listener(&uuri, None, Arc::new(DummyListener))
+        .await
+        .unwrap();
+    assert!(upclient.send(publish()).await.is_ok());
+
+    // Nobody serves the method
+    let src_uuri = test_lib::new_uuri("vehicle", 1, 1, 0);
+    let sink_uuri = test_lib::new_uuri("vehicle", 2, 1, 1);
+    let request = || {
+        UMessageBuilder::request(sink_uuri.clone(), src_uuri.clone(), 100)
+            .build_with_payload("data".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+            .unwrap()
+    };
+    let result = upclient.invoke_rpc(request()).await;
+    assert!(matches!(result, Err(TransportError::Unavailable(_))));
+
+    // The local RPC server can receive it, though it never responds
+    upclient
+        .register_listener(
+            &test_lib::new_uuri("*", 0xFFFF, 0xFF, 0xFFFF),
+            Some(&sink_uuri),
+            Arc::new(DummyListener),
+        )
+        .await
+        .unwrap();
+    let result = upclient.invoke_rpc(request()).await;
+    assert!(matches!(result, Err(TransportError::Timeout)));
+}
@@ -0,0 +1,109 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+pub mod test_lib;
+
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use up_rust::{UListener, UMessage, UStatus, UTransport, UUri};
+use up_transport_zenoh::discovery::DiscoveryListener;
+
+struct FooListener;
+#[async_trait]
+impl UListener for FooListener {
+    async fn on_receive(&self, _msg: UMessage) {}
+    async fn on_error(&self, _err: UStatus) {}
+}
+
+struct RecordDiscoveryListener {
+    joined: Arc<Mutex<Vec<UUri>>>,
+    left: Arc<Mutex<Vec<UUri>>>,
+}
+impl RecordDiscoveryListener {
+    fn new() -> Self {
+        RecordDiscoveryListener {
+            joined: Arc::new(Mutex::new(Vec::new())),
+            left: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+    fn has_joined(&self, uri: &UUri) -> bool {
+        self.joined.lock().unwrap().contains(uri)
+    }
+    fn has_left(&self, uri: &UUri) -> bool {
+        self.left.lock().unwrap().contains(uri)
+    }
+}
+#[async_trait]
+impl DiscoveryListener for RecordDiscoveryListener {
+    async fn on_join(&self, uri: UUri) {
+        self.joined.lock().unwrap().push(uri);
+    }
+    async fn on_leave(&self, uri: UUri) {
+        self.left.lock().unwrap().push(uri);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_discover_rpc_method() {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_discovery = test_lib::create_up_client_zenoh(&runtime, "discovery")
+        .await
+        .unwrap();
+    let upclient_server = test_lib::create_up_client_zenoh(&runtime, "server")
+        .await
+        .unwrap();
+    let authority = UUri {
+        authority_name: "server".to_string(),
+        ..Default::default()
+    };
+    let method = test_lib::new_uuri("server", 2, 1, 1);
+    let src_filter = test_lib::new_uuri("*", 0xFFFF, 0xFF, 0xFFFF);
+
+    // The authority is alive after the creation
+    let entities = upclient_discovery.get_alive_entities().await.unwrap();
+    assert!(entities.contains(&authority));
+    assert!(!entities.contains(&method));
+
+    // Register the discovery listener
+    let discovery_listener = Arc::new(RecordDiscoveryListener::new());
+    upclient_discovery
+        .register_discovery_listener(discovery_listener.clone())
+        .await
+        .unwrap();
+
+    // The method joins after registering the request listener
+    let request_listener = Arc::new(FooListener);
+    upclient_server
+        .register_listener(&src_filter, Some(&method), request_listener.clone())
+        .await
+        .unwrap();
+    test_lib::wait_until(|| discovery_listener.has_joined(&method)).await;
+    assert!(discovery_listener.has_joined(&method));
+    let entities = upclient_discovery.get_alive_entities().await.unwrap();
+    assert!(entities.contains(&method));
+
+    // The method leaves after unregistering the request listener
+    upclient_server
+        .unregister_listener(&src_filter, Some(&method), request_listener)
+        .await
+        .unwrap();
+    test_lib::wait_until(|| discovery_listener.has_left(&method)).await;
+    assert!(discovery_listener.has_left(&method));
+
+    // Cleanup
+    upclient_discovery
+        .unregister_discovery_listener(discovery_listener)
+        .unwrap();
+}
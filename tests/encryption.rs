@@ -0,0 +1,243 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+pub mod test_lib;
+
+use async_trait::async_trait;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use test_case::test_case;
+use tokio::{runtime::Handle, task};
+use up_rust::{
+    RpcClient, UCode, UListener, UMessage, UMessageBuilder, UPayloadFormat, UStatus, UTransport,
+};
+use up_transport_zenoh::{encryption::EncryptionConfig, history::HistoryConfig, UPClientZenoh};
+
+const OLD_KEY: [u8; 32] = [1; 32];
+const NEW_KEY: [u8; 32] = [2; 32];
+
+struct RecordListener {
+    payloads: Arc<Mutex<Vec<Vec<u8>>>>,
+    errors: Arc<Mutex<Vec<UCode>>>,
+}
+impl RecordListener {
+    fn new() -> Self {
+        RecordListener {
+            payloads: Arc::new(Mutex::new(vec![])),
+            errors: Arc::new(Mutex::new(vec![])),
+        }
+    }
+}
+#[async_trait]
+impl UListener for RecordListener {
+    async fn on_receive(&self, msg: UMessage) {
+        self.payloads
+            .lock()
+            .unwrap()
+            .push(msg.payload.unwrap().to_vec());
+    }
+    async fn on_error(&self, err: UStatus) {
+        self.errors.lock().unwrap().push(err.get_code());
+    }
+}
+
+// EchoListener responds with the payload of the request
+struct EchoListener {
+    up_client: Arc<UPClientZenoh>,
+}
+#[async_trait]
+impl UListener for EchoListener {
+    async fn on_receive(&self, msg: UMessage) {
+        let umessage = UMessageBuilder::response_for_request(&msg.attributes)
+            .build_with_payload(msg.payload.unwrap(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+            .unwrap();
+        task::block_in_place(|| {
+            Handle::current()
+                .block_on(self.up_client.send(umessage))
+                .unwrap();
+        });
+    }
+    async fn on_error(&self, err: UStatus) {
+        panic!("Internal Error: {err:?}");
+    }
+}
+
+#[test_case(Some(("k1", OLD_KEY)), Some(vec![("k1", OLD_KEY)]), None; "Encrypted with the right key")]
+#[test_case(Some(("k2", NEW_KEY)), Some(vec![("k1", OLD_KEY), ("k2", NEW_KEY)]), None; "Encrypted with the rotated key")]
+#[test_case(Some(("k1", OLD_KEY)), Some(vec![("k1", NEW_KEY)]), Some(UCode::UNAUTHENTICATED); "Encrypted with the wrong key")]
+#[test_case(Some(("k1", OLD_KEY)), None, Some(UCode::UNAUTHENTICATED); "Encrypted without the receiver key")]
+#[test_case(None, Some(vec![("k1", OLD_KEY)]), Some(UCode::UNAUTHENTICATED); "Unencrypted but required")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_encryption(
+    sender_key: Option<(&str, [u8; 32])>,
+    receiver_keys: Option<Vec<(&str, [u8; 32])>>,
+    expected_error: Option<UCode>,
+) {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let uuri = test_lib::new_uuri("vehicle", 1, 1, 0x8000);
+    let mut builder_send = UPClientZenoh::builder("vehicle").with_runtime(runtime.clone());
+    if let Some((key_id, key)) = sender_key {
+        builder_send = builder_send.with_encryption(
+            EncryptionConfig::new()
+                .with_key(key_id, key)
+                .with_topic(uuri.clone(), key_id),
+        );
+    }
+    let upclient_send = builder_send.build().await.unwrap();
+    let mut builder_recv = UPClientZenoh::builder("subscriber").with_runtime(runtime);
+    if let Some(keys) = receiver_keys {
+        let config = keys
+            .into_iter()
+            .fold(EncryptionConfig::new(), |config, (key_id, key)| {
+                config.with_key(key_id, key)
+            });
+        builder_recv = builder_recv.with_encryption(config.with_topic(uuri.clone(), "k1"));
+    }
+    let upclient_recv = builder_recv.build().await.unwrap();
+
+    // Register the listener
+    let listener = Arc::new(RecordListener::new());
+    upclient_recv
+        .register_listener(&uuri, None, listener.clone())
+        .await
+        .unwrap();
+
+    // Publish
+    let umessage = UMessageBuilder::publish(uuri.clone())
+        .build_with_payload("command".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+    upclient_send.send(umessage).await.unwrap();
+    test_lib::wait_until(|| {
+        listener.payloads.lock().unwrap().len() + listener.errors.lock().unwrap().len() == 1
+    })
+    .await;
+
+    // Check the result
+    if let Some(code) = expected_error {
+        assert!(listener.payloads.lock().unwrap().is_empty());
+        assert_eq!(*listener.errors.lock().unwrap(), vec![code]);
+    } else {
+        assert_eq!(
+            *listener.payloads.lock().unwrap(),
+            vec![b"command".to_vec()]
+        );
+        assert!(listener.errors.lock().unwrap().is_empty());
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_encrypted_rpc() {
+    test_lib::before_test();
+
+    // Initialization: both sides encrypt the messages to and from the method
+    let runtime = test_lib::create_loopback_runtime().await;
+    let src_uuri = test_lib::new_uuri("requester", 1, 1, 0);
+    let sink_uuri = test_lib::new_uuri("responder", 2, 1, 1);
+    let config = EncryptionConfig::new()
+        .with_key("k1", OLD_KEY)
+        .with_topic(sink_uuri.clone(), "k1");
+    let upclient_client = UPClientZenoh::builder("requester")
+        .with_runtime(runtime.clone())
+        .with_encryption(config.clone())
+        .build()
+        .await
+        .unwrap();
+    let upclient_server = Arc::new(
+        UPClientZenoh::builder("responder")
+            .with_runtime(runtime)
+            .with_encryption(config)
+            .build()
+            .await
+            .unwrap(),
+    );
+    let request_listener = Arc::new(EchoListener {
+        up_client: upclient_server.clone(),
+    });
+    upclient_server
+        .register_listener(
+            &test_lib::new_uuri("*", 0xFFFF, 0xFF, 0xFFFF),
+            Some(&sink_uuri),
+            request_listener,
+        )
+        .await
+        .unwrap();
+
+    // Send the request and get back the decrypted payload
+    let result = upclient_client
+        .invoke_method(
+            sink_uuri.clone(),
+            UMessageBuilder::request(sink_uuri.clone(), src_uuri, 1000)
+                .build_with_payload("echo".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(result.payload.unwrap().to_vec(), b"echo".to_vec());
+}
+
+#[test_case(true; "Encrypted")]
+#[test_case(false; "Unencrypted but required")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_encryption_last_value_and_history(encrypted: bool) {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let uuri = test_lib::new_uuri("vehicle", 1, 1, 0x8000);
+    let encryption = || {
+        EncryptionConfig::new()
+            .with_key("k1", OLD_KEY)
+            .with_topic(uuri.clone(), "k1")
+    };
+    let mut builder_send = UPClientZenoh::builder("vehicle").with_runtime(runtime.clone());
+    if encrypted {
+        builder_send = builder_send.with_encryption(encryption());
+    }
+    let upclient_send = builder_send.build().await.unwrap();
+    let upclient_recv = UPClientZenoh::builder("subscriber")
+        .with_runtime(runtime)
+        .with_encryption(encryption())
+        .with_history(HistoryConfig::new(10))
+        .build()
+        .await
+        .unwrap();
+    upclient_send.enable_last_value_cache(&uuri).await.unwrap();
+
+    // Publish
+    let umessage = UMessageBuilder::publish(uuri.clone())
+        .build_with_payload("command".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+    upclient_send.send(umessage).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // The encrypted message is decrypted, and the unencrypted one is dropped
+    let last_values = upclient_recv.get_last_values(&uuri).await.unwrap();
+    let history = upclient_recv
+        .get_history(&uuri, None, UNIX_EPOCH..SystemTime::now())
+        .unwrap();
+    for messages in [last_values, history] {
+        let payloads = messages
+            .into_iter()
+            .map(|msg| msg.payload.unwrap().to_vec())
+            .collect::<Vec<_>>();
+        if encrypted {
+            assert_eq!(payloads, vec![b"command".to_vec()]);
+        } else {
+            assert!(payloads.is_empty());
+        }
+    }
+}
@@ -0,0 +1,114 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+pub mod test_lib;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use up_rust::{UMessage, UMessageBuilder, UPayloadFormat, UTransport};
+use up_transport_zenoh::{history::HistoryConfig, UPClientZenoh};
+
+fn payload_to_string(msg: &UMessage) -> String {
+    let data = msg.payload.clone().unwrap();
+    data.into_iter().map(|c| c as char).collect::<String>()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_history() {
+    test_lib::before_test();
+
+    // Initialization
+    let file_path = std::env::temp_dir().join(format!("up_history_{}.bin", std::process::id()));
+    let _ = std::fs::remove_file(&file_path);
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_send = test_lib::create_up_client_zenoh(&runtime, "publisher")
+        .await
+        .unwrap();
+    let upclient_history = UPClientZenoh::builder("history")
+        .with_runtime(runtime.clone())
+        .with_history(HistoryConfig::new(2).with_file(&file_path))
+        .build()
+        .await
+        .unwrap();
+    let topic = test_lib::new_uuri("publisher", 1, 1, 0x8000);
+    let other_topic = test_lib::new_uuri("publisher", 1, 1, 0x8001);
+    let source_filter = test_lib::new_uuri("publisher", 0xFFFF, 0xFF, 0xFFFF);
+    let all_time = UNIX_EPOCH..SystemTime::now() + Duration::from_secs(60);
+
+    // Send messages
+    for (uri, data) in [
+        (&topic, "First"),
+        (&topic, "Second"),
+        (&topic, "Third"),
+        (&other_topic, "Other"),
+    ] {
+        let umessage = UMessageBuilder::publish(uri.clone())
+            .build_with_payload(data.to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+            .unwrap();
+        upclient_send.send(umessage).await.unwrap();
+    }
+    test_lib::wait_until(|| {
+        upclient_history
+            .get_history(&source_filter, None, all_time.clone())
+            .unwrap()
+            .len()
+            == 3
+    })
+    .await;
+
+    // Only the latest 2 messages of each topic are kept
+    let messages = upclient_history
+        .get_history(&topic, None, all_time.clone())
+        .unwrap();
+    let values = messages.iter().map(payload_to_string).collect::<Vec<_>>();
+    assert_eq!(values, vec!["Second", "Third"]);
+
+    // Nothing is sent in the time range
+    let messages = upclient_history
+        .get_history(
+            &source_filter,
+            None,
+            UNIX_EPOCH..UNIX_EPOCH + Duration::from_secs(1),
+        )
+        .unwrap();
+    assert!(messages.is_empty());
+
+    // The history is loaded from the file after restart
+    drop(upclient_history);
+    let upclient_history = UPClientZenoh::builder("history")
+        .with_runtime(runtime.clone())
+        .with_history(HistoryConfig::new(2).with_file(&file_path))
+        .build()
+        .await
+        .unwrap();
+    let messages = upclient_history
+        .get_history(&source_filter, None, all_time)
+        .unwrap();
+    let values = messages.iter().map(payload_to_string).collect::<Vec<_>>();
+    assert_eq!(values, vec!["Second", "Third", "Other"]);
+
+    // Cleanup
+    let _ = std::fs::remove_file(&file_path);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_history_not_enabled() {
+    test_lib::before_test();
+
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient = test_lib::create_up_client_zenoh(&runtime, "history")
+        .await
+        .unwrap();
+    let topic = test_lib::new_uuri("publisher", 1, 1, 0x8000);
+    assert!(upclient
+        .get_history(&topic, None, UNIX_EPOCH..SystemTime::now())
+        .is_err());
+}
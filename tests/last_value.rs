@@ -0,0 +1,109 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+pub mod test_lib;
+
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use test_case::test_case;
+use up_rust::{UListener, UMessage, UMessageBuilder, UPayloadFormat, UStatus, UTransport, UUri};
+
+struct PublishListener {
+    recv_data: Arc<Mutex<String>>,
+}
+impl PublishListener {
+    fn new() -> Self {
+        PublishListener {
+            recv_data: Arc::new(Mutex::new(String::new())),
+        }
+    }
+    fn get_recv_data(&self) -> String {
+        self.recv_data.lock().unwrap().clone()
+    }
+}
+#[async_trait]
+impl UListener for PublishListener {
+    async fn on_receive(&self, msg: UMessage) {
+        let data = msg.payload.unwrap();
+        let value = data.into_iter().map(|c| c as char).collect::<String>();
+        *self.recv_data.lock().unwrap() = value;
+    }
+    async fn on_error(&self, err: UStatus) {
+        panic!("Internal Error: {err:?}");
+    }
+}
+
+#[test_case(&test_lib::new_uuri("publisher", 1, 1, 0x8000), &test_lib::new_uuri("publisher", 1, 1, 0x8000); "Normal UUri")]
+#[test_case(&test_lib::new_uuri("publisher", 2, 1, 0x8001), &test_lib::new_uuri("publisher", 0xFFFF, 0xFF, 0xFFFF); "Special UUri")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_last_value_for_late_joiner(publish_uuri: &UUri, listen_uuri: &UUri) {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_send = test_lib::create_up_client_zenoh(&runtime, "publisher")
+        .await
+        .unwrap();
+    let upclient_recv = test_lib::create_up_client_zenoh(&runtime, "subscriber")
+        .await
+        .unwrap();
+
+    // Publish with the last-value cache before anybody subscribes
+    upclient_send
+        .enable_last_value_cache(publish_uuri)
+        .await
+        .unwrap();
+    for data in ["Old value", "Latest value"] {
+        let umessage = UMessageBuilder::publish((*publish_uuri).clone())
+            .build_with_payload(data.to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+            .unwrap();
+        upclient_send.send(umessage).await.unwrap();
+    }
+
+    // Only the latest value is cached
+    let messages = upclient_recv.get_last_values(listen_uuri).await.unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].attributes.source.get_or_default(), publish_uuri);
+    let value = messages[0]
+        .payload
+        .clone()
+        .unwrap()
+        .into_iter()
+        .map(|c| c as char)
+        .collect::<String>();
+    assert_eq!(value, "Latest value");
+
+    // The late joiner gets the cached value without waiting for the next publish
+    let pub_listener = Arc::new(PublishListener::new());
+    upclient_recv
+        .register_listener_with_last_value(listen_uuri, pub_listener.clone())
+        .await
+        .unwrap();
+    test_lib::wait_until(|| pub_listener.get_recv_data() == "Latest value").await;
+    assert_eq!(pub_listener.get_recv_data(), "Latest value");
+
+    // Nothing is cached after disabling the last-value cache
+    upclient_send
+        .disable_last_value_cache(publish_uuri)
+        .unwrap();
+    assert!(upclient_recv
+        .get_last_values(listen_uuri)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Cleanup
+    upclient_recv
+        .unregister_listener(listen_uuri, None, pub_listener)
+        .await
+        .unwrap();
+}
@@ -0,0 +1,74 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+pub mod test_lib;
+
+use async_trait::async_trait;
+use std::sync::Arc;
+use test_case::test_case;
+use tokio::time::{timeout, Duration};
+use up_rust::{UListener, UMessage, UStatus, UTransport, UUri};
+
+struct FooListener;
+#[async_trait]
+impl UListener for FooListener {
+    async fn on_receive(&self, _msg: UMessage) {}
+    async fn on_error(&self, _err: UStatus) {}
+}
+
+#[test_case(&test_lib::new_uuri("matching_pub", 1, 1, 0x8000), None, &test_lib::new_uuri("matching_pub", 1, 1, 0x8000), None; "Publish")]
+#[test_case(&test_lib::new_uuri("matching_pub", 1, 1, 0x8000), None, &test_lib::new_uuri("*", 0xFFFF, 0xFF, 0xFFFF), None; "Publish with wildcard listener")]
+#[test_case(&test_lib::new_uuri("matching_pub", 1, 1, 0x8000), Some(&test_lib::new_uuri("matching_sub", 2, 1, 0)), &test_lib::new_uuri("*", 0xFFFF, 0xFF, 0xFFFF), Some(&test_lib::new_uuri("matching_sub", 2, 1, 0)); "Notification")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_wait_for_matching_listener(
+    src_uuri: &UUri,
+    sink_uuri: Option<&UUri>,
+    src_filter: &UUri,
+    sink_filter: Option<&UUri>,
+) {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_send = test_lib::create_up_client_zenoh(&runtime, "matching_pub")
+        .await
+        .unwrap();
+    let upclient_recv = test_lib::create_up_client_zenoh(&runtime, "matching_sub")
+        .await
+        .unwrap();
+
+    // Nobody listens yet
+    assert!(!upclient_send
+        .has_matching_listener(src_uuri, sink_uuri)
+        .await
+        .unwrap());
+
+    // Register the listener while waiting for it
+    let foo_listener = Arc::new(FooListener);
+    let waiting = upclient_send.wait_for_matching_listener(src_uuri, sink_uuri);
+    let registering =
+        upclient_recv.register_listener(src_filter, sink_filter, foo_listener.clone());
+    let (matched, registered) =
+        tokio::join!(timeout(Duration::from_millis(5000), waiting), registering);
+    registered.unwrap();
+    matched.unwrap().unwrap();
+    assert!(upclient_send
+        .has_matching_listener(src_uuri, sink_uuri)
+        .await
+        .unwrap());
+
+    // Cleanup
+    upclient_recv
+        .unregister_listener(src_filter, sink_filter, foo_listener)
+        .await
+        .unwrap();
+}
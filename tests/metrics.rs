@@ -0,0 +1,137 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+pub mod test_lib;
+
+use async_trait::async_trait;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use up_rust::{UListener, UMessage, UMessageBuilder, UPayloadFormat, UStatus, UTransport};
+
+// SilentListener never responds to the request
+struct SilentListener;
+#[async_trait]
+impl UListener for SilentListener {
+    async fn on_receive(&self, _msg: UMessage) {}
+    async fn on_error(&self, err: UStatus) {
+        panic!("Internal Error: {err:?}");
+    }
+}
+
+struct CountListener {
+    count: Arc<AtomicUsize>,
+}
+#[async_trait]
+impl UListener for CountListener {
+    async fn on_receive(&self, _msg: UMessage) {
+        self.count.fetch_add(1, Ordering::SeqCst);
+    }
+    async fn on_error(&self, err: UStatus) {
+        panic!("Internal Error: {err:?}");
+    }
+}
+
+// Whether the metric has the sample ending with `suffix`, regardless of the other labels
+fn has_sample(metrics: &str, name: &str, suffix: &str) -> bool {
+    metrics
+        .lines()
+        .any(|line| line.starts_with(name) && line.ends_with(suffix))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_metrics_of_publish() {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_send = test_lib::create_up_client_zenoh(&runtime, "publisher")
+        .await
+        .unwrap();
+    let upclient_recv = test_lib::create_up_client_zenoh(&runtime, "subscriber")
+        .await
+        .unwrap();
+    let uuri = test_lib::new_uuri("publisher", 1, 1, 0x8000);
+
+    // Register the listener
+    let count = Arc::new(AtomicUsize::new(0));
+    upclient_recv
+        .register_listener(
+            &uuri,
+            None,
+            Arc::new(CountListener {
+                count: count.clone(),
+            }),
+        )
+        .await
+        .unwrap();
+
+    // Publish
+    let umessage = UMessageBuilder::publish(uuri.clone())
+        .build_with_payload("metrics".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+    upclient_send.send(umessage).await.unwrap();
+    test_lib::wait_until(|| count.load(Ordering::SeqCst) == 1).await;
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+
+    // Check the metrics on both sides
+    let sent = upclient_send.gather_metrics();
+    assert!(has_sample(&sent, "up_zenoh_messages_sent_total", r#"type="publish"} 1"#));
+    let received = upclient_recv.gather_metrics();
+    assert!(has_sample(
+        &received,
+        "up_zenoh_messages_received_total",
+        r#"type="publish"} 1"#
+    ));
+    assert!(received.contains("up_zenoh_attachment_decode_errors_total 0"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_metrics_of_rpc_failures() {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_client = test_lib::create_up_client_zenoh(&runtime, "requester")
+        .await
+        .unwrap();
+    let upclient_server = test_lib::create_up_client_zenoh(&runtime, "responder")
+        .await
+        .unwrap();
+    let src_uuri = test_lib::new_uuri("requester", 1, 1, 0);
+    let sink_uuri = test_lib::new_uuri("responder", 2, 1, 1);
+    let request = || {
+        UMessageBuilder::request(sink_uuri.clone(), src_uuri.clone(), 500)
+            .build_with_payload("data".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+            .unwrap()
+    };
+
+    // Nobody serves the method, which fails before the TTL
+    assert!(upclient_client.invoke_rpc(request()).await.is_err());
+    let metrics = upclient_client.gather_metrics();
+    assert!(has_sample(
+        &metrics,
+        "up_zenoh_send_failures_total",
+        r#"code="UNAVAILABLE"} 1"#
+    ));
+    assert!(metrics.contains("up_zenoh_rpc_timeouts_total 0"));
+
+    // The server never responds, which times out at the TTL
+    upclient_server
+        .register_listener(&src_uuri, Some(&sink_uuri), Arc::new(SilentListener))
+        .await
+        .unwrap();
+    assert!(upclient_client.invoke_rpc(request()).await.is_err());
+    let metrics = upclient_client.gather_metrics();
+    assert!(metrics.contains("up_zenoh_rpc_timeouts_total 1"));
+}
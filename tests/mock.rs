@@ -0,0 +1,148 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+pub mod test_lib;
+
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+use test_case::test_case;
+use up_rust::{
+    RpcClient, UListener, UMessage, UMessageBuilder, UPayloadFormat, UStatus, UTransport, UUri,
+};
+use up_transport_zenoh::mock::MockTransport;
+
+struct RecordListener {
+    recv_data: Arc<Mutex<Vec<String>>>,
+}
+impl RecordListener {
+    fn new() -> Self {
+        RecordListener {
+            recv_data: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+    fn get_recv_data(&self) -> Vec<String> {
+        self.recv_data.lock().unwrap().clone()
+    }
+}
+#[async_trait]
+impl UListener for RecordListener {
+    async fn on_receive(&self, msg: UMessage) {
+        let data = msg.payload.unwrap();
+        let value = data.into_iter().map(|c| c as char).collect::<String>();
+        self.recv_data.lock().unwrap().push(value);
+    }
+    async fn on_error(&self, err: UStatus) {
+        panic!("Internal Error: {err:?}");
+    }
+}
+
+#[test_case(&test_lib::new_uuri("src", 1, 1, 0x8000), &test_lib::new_uuri("src", 1, 1, 0x8000), true; "Specific topic")]
+#[test_case(&test_lib::new_uuri("src", 1, 1, 0x8000), &test_lib::new_uuri("*", 0xFFFF, 0xFF, 0xFFFF), true; "Any topic")]
+#[test_case(&test_lib::new_uuri("src", 1, 1, 0x8000), &test_lib::new_uuri("src", 1, 1, 0x8001), false; "Other topic")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_inject_publish(publish_uuri: &UUri, listen_uuri: &UUri, expected_received: bool) {
+    let transport = MockTransport::new("mock".to_string());
+    let listener = Arc::new(RecordListener::new());
+    transport
+        .register_listener(listen_uuri, None, listener.clone())
+        .await
+        .unwrap();
+    let umessage = UMessageBuilder::publish(publish_uuri.clone())
+        .build_with_payload("Hello", UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+    transport.inject_message(umessage).await.unwrap();
+    assert_eq!(!listener.get_recv_data().is_empty(), expected_received);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_record_sent_messages() {
+    let transport = MockTransport::new("mock".to_string());
+    let src_uuri = test_lib::new_uuri("src", 1, 1, 0x8000);
+    let sink_uuri = test_lib::new_uuri("dst", 2, 1, 0);
+    let umessage = UMessageBuilder::notification(src_uuri, sink_uuri)
+        .build_with_payload("Notification", UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+    transport.send(umessage.clone()).await.unwrap();
+    assert_eq!(transport.sent_messages(), vec![umessage]);
+
+    // Invalid message is rejected and not recorded
+    transport.clear_sent_messages();
+    assert!(transport.send(UMessage::default()).await.is_err());
+    assert!(transport.sent_messages().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_register_and_unregister() {
+    let transport = MockTransport::new("mock".to_string());
+    let src_filter = test_lib::new_uuri("*", 0xFFFF, 0xFF, 0xFFFF);
+    let sink_filter = test_lib::new_uuri("dst", 2, 1, 0);
+    let listener: Arc<dyn UListener> = Arc::new(RecordListener::new());
+
+    transport
+        .register_listener(&src_filter, Some(&sink_filter), listener.clone())
+        .await
+        .unwrap();
+    assert!(transport.has_listener(&src_filter, Some(&sink_filter), &listener));
+    // Notification & Response
+    assert_eq!(transport.listener_count(), 2);
+
+    transport
+        .unregister_listener(&src_filter, Some(&sink_filter), listener.clone())
+        .await
+        .unwrap();
+    assert!(!transport.has_listener(&src_filter, Some(&sink_filter), &listener));
+    assert_eq!(transport.listener_count(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rpc_response() {
+    let transport = MockTransport::new("requester".to_string());
+    let src_uuri = test_lib::new_uuri("requester", 1, 1, 0);
+    let method_uuri = test_lib::new_uuri("responder", 2, 1, 1);
+    transport.set_rpc_response(
+        &method_uuri,
+        b"Response".to_vec(),
+        UPayloadFormat::UPAYLOAD_FORMAT_TEXT,
+    );
+
+    // invoke_method
+    let request = UMessageBuilder::request(method_uuri.clone(), src_uuri.clone(), 1000)
+        .build_with_payload("Request", UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+    let response = transport
+        .invoke_method(method_uuri.clone(), request)
+        .await
+        .unwrap();
+    let value = response
+        .payload
+        .unwrap()
+        .into_iter()
+        .map(|c| c as char)
+        .collect::<String>();
+    assert_eq!(value, "Response");
+
+    // send with response listener
+    let response_listener = Arc::new(RecordListener::new());
+    transport
+        .register_listener(&method_uuri, Some(&src_uuri), response_listener.clone())
+        .await
+        .unwrap();
+    let request = UMessageBuilder::request(method_uuri.clone(), src_uuri.clone(), 1000)
+        .build_with_payload("Request", UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+    transport.send(request).await.unwrap();
+    assert_eq!(
+        response_listener.get_recv_data(),
+        vec!["Response".to_string()]
+    );
+    assert_eq!(transport.sent_messages().len(), 2);
+}
@@ -0,0 +1,192 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+pub mod test_lib;
+
+use std::{sync::Arc, time::Duration};
+use test_lib::RecordListener;
+use up_rust::{UMessage, UMessageBuilder, UPayloadFormat, UTransport, UUri};
+use up_transport_zenoh::{
+    history::HistoryConfig, offline_queue::OfflineQueueConfig, UPClientZenoh,
+};
+
+fn publish(uuri: &UUri, data: &str, ttl: Option<u32>) -> UMessage {
+    let mut builder = UMessageBuilder::publish(uuri.clone());
+    if let Some(ttl) = ttl {
+        builder.with_ttl(ttl);
+    }
+    builder
+        .build_with_payload(data.to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap()
+}
+
+async fn create_sender(endpoint: &str, config: OfflineQueueConfig) -> UPClientZenoh {
+    UPClientZenoh::builder("vehicle")
+        .with_config(test_lib::tcp_config(&[endpoint], &[]))
+        .with_offline_queue(config)
+        .build()
+        .await
+        .unwrap()
+}
+
+// The local listener of the sender, which gets the queued messages once they are flushed
+async fn register_record_listener(upclient: &UPClientZenoh, uuri: &UUri) -> Arc<RecordListener> {
+    let listener = Arc::new(RecordListener::new());
+    upclient
+        .register_listener(uuri, None, listener.clone())
+        .await
+        .unwrap();
+    listener
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_offline_queue_flush() {
+    test_lib::before_test();
+
+    // Initialization: nobody is connected or listens
+    let endpoint = format!("tcp/127.0.0.1:{}", test_lib::free_port());
+    let upclient = create_sender(&endpoint, OfflineQueueConfig::new(4)).await;
+    let uuri = test_lib::new_uuri("vehicle", 1, 1, 0x8000);
+
+    // The messages are held while nobody can receive them. "0" overflows, and "1" expires before flushed.
+    upclient.send(publish(&uuri, "0", None)).await.unwrap();
+    upclient.send(publish(&uuri, "1", Some(100))).await.unwrap();
+    for data in ["2", "3", "4"] {
+        upclient.send(publish(&uuri, data, None)).await.unwrap();
+    }
+    assert_eq!(upclient.offline_queue_depth(), 4);
+    assert_eq!(upclient.offline_queue_dropped(), 1);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // The local listener is a route as well, and the queue is flushed to it in order
+    let listener = register_record_listener(&upclient, &uuri).await;
+    test_lib::wait_until(|| listener.received.lock().unwrap().len() == 3).await;
+    assert_eq!(*listener.received.lock().unwrap(), vec!["2", "3", "4"]);
+    assert_eq!(upclient.offline_queue_depth(), 0);
+    assert_eq!(upclient.offline_queue_dropped(), 2);
+
+    // The message to the local listener is sent immediately while offline
+    upclient.send(publish(&uuri, "5", None)).await.unwrap();
+    assert_eq!(upclient.offline_queue_depth(), 0);
+    test_lib::wait_until(|| listener.received.lock().unwrap().len() == 4).await;
+    assert_eq!(listener.received.lock().unwrap().len(), 4);
+
+    // Without the local listener, the message is held until the peer connects
+    upclient
+        .unregister_listener(&uuri, None, listener.clone())
+        .await
+        .unwrap();
+    upclient.send(publish(&uuri, "6", None)).await.unwrap();
+    assert_eq!(upclient.offline_queue_depth(), 1);
+    let _upclient_peer = UPClientZenoh::builder("cloud")
+        .with_config(test_lib::tcp_config(&[], &[&endpoint]))
+        .build()
+        .await
+        .unwrap();
+    test_lib::wait_until(|| upclient.offline_queue_depth() == 0).await;
+    assert_eq!(upclient.offline_queue_depth(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_offline_queue_file() {
+    test_lib::before_test();
+
+    // Initialization
+    let file_path =
+        std::env::temp_dir().join(format!("up_offline_queue_{}.bin", std::process::id()));
+    let _ = std::fs::remove_file(&file_path);
+    let endpoint = format!("tcp/127.0.0.1:{}", test_lib::free_port());
+    let config = OfflineQueueConfig::new(10).with_file(&file_path);
+    let uuri = test_lib::new_uuri("vehicle", 1, 1, 0x8000);
+
+    // Queue the messages and restart
+    let upclient = create_sender(&endpoint, config.clone()).await;
+    for data in ["First", "Second"] {
+        upclient.send(publish(&uuri, data, None)).await.unwrap();
+    }
+    Arc::new(upclient)
+        .close(Duration::from_secs(1))
+        .await
+        .unwrap();
+
+    // The queued messages are loaded from the file
+    let upclient = create_sender(&endpoint, config).await;
+    assert_eq!(upclient.offline_queue_depth(), 2);
+
+    // The listener is registered, and the loaded messages are flushed to it
+    let listener = register_record_listener(&upclient, &uuri).await;
+    test_lib::wait_until(|| listener.received.lock().unwrap().len() == 2).await;
+    assert_eq!(*listener.received.lock().unwrap(), vec!["First", "Second"]);
+    assert_eq!(upclient.offline_queue_depth(), 0);
+
+    // Cleanup
+    let _ = std::fs::remove_file(&file_path);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_offline_queue_with_history() {
+    test_lib::before_test();
+
+    // Initialization: the history subscribes to all the messages, but it's not a route
+    let endpoint = format!("tcp/127.0.0.1:{}", test_lib::free_port());
+    let upclient = UPClientZenoh::builder("vehicle")
+        .with_config(test_lib::tcp_config(&[&endpoint], &[]))
+        .with_history(HistoryConfig::new(10))
+        .with_offline_queue(OfflineQueueConfig::new(10))
+        .build()
+        .await
+        .unwrap();
+    let uuri = test_lib::new_uuri("vehicle", 1, 1, 0x8000);
+
+    // The message is held while nobody listens, and flushed to the listener registered later
+    upclient.send(publish(&uuri, "Held", None)).await.unwrap();
+    assert_eq!(upclient.offline_queue_depth(), 1);
+    let listener = register_record_listener(&upclient, &uuri).await;
+    test_lib::wait_until(|| listener.received.lock().unwrap().len() == 1).await;
+    assert_eq!(*listener.received.lock().unwrap(), vec!["Held"]);
+    assert_eq!(upclient.offline_queue_depth(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_offline_queue_order_across_topics() {
+    test_lib::before_test();
+
+    // Initialization: only the second topic has the local listener
+    let endpoint = format!("tcp/127.0.0.1:{}", test_lib::free_port());
+    let upclient = create_sender(&endpoint, OfflineQueueConfig::new(10)).await;
+    let uuri_remote = test_lib::new_uuri("vehicle", 1, 1, 0x8000);
+    let uuri_local = test_lib::new_uuri("vehicle", 1, 1, 0x8001);
+    let listener = register_record_listener(&upclient, &uuri_local).await;
+
+    // The message to the local listener is held behind the one nobody can receive
+    upclient
+        .send(publish(&uuri_remote, "Remote", None))
+        .await
+        .unwrap();
+    upclient
+        .send(publish(&uuri_local, "Local", None))
+        .await
+        .unwrap();
+    assert_eq!(upclient.offline_queue_depth(), 2);
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    assert!(listener.received.lock().unwrap().is_empty());
+
+    // Both are flushed in order once the peer connects
+    let _upclient_peer = UPClientZenoh::builder("cloud")
+        .with_config(test_lib::tcp_config(&[], &[&endpoint]))
+        .build()
+        .await
+        .unwrap();
+    test_lib::wait_until(|| upclient.offline_queue_depth() == 0).await;
+    test_lib::wait_until(|| listener.received.lock().unwrap().len() == 1).await;
+    assert_eq!(*listener.received.lock().unwrap(), vec!["Local"]);
+}
@@ -15,7 +15,6 @@ pub mod test_lib;
 use async_trait::async_trait;
 use std::sync::{Arc, Mutex};
 use test_case::test_case;
-use tokio::time::{sleep, Duration};
 use up_rust::{UListener, UMessage, UMessageBuilder, UPayloadFormat, UStatus, UTransport, UUri};
 
 struct PublishNotificationListener {
@@ -51,8 +50,11 @@ async fn test_publish_and_subscribe(publish_uuri: &UUri, listen_uuri: &UUri) {
 
     // Initialization
     let target_data = String::from("Hello World!");
-    let upclient_send = test_lib::create_up_client_zenoh("publisher").await.unwrap();
-    let upclient_recv = test_lib::create_up_client_zenoh("subscriber")
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_send = test_lib::create_up_client_zenoh(&runtime, "publisher")
+        .await
+        .unwrap();
+    let upclient_recv = test_lib::create_up_client_zenoh(&runtime, "subscriber")
         .await
         .unwrap();
 
@@ -62,8 +64,6 @@ async fn test_publish_and_subscribe(publish_uuri: &UUri, listen_uuri: &UUri) {
         .register_listener(listen_uuri, None, pub_listener.clone())
         .await
         .unwrap();
-    // Waiting for listener to take effect
-    sleep(Duration::from_millis(1000)).await;
 
     // Send UMessage
     let umessage = UMessageBuilder::publish((*publish_uuri).clone())
@@ -72,7 +72,7 @@ async fn test_publish_and_subscribe(publish_uuri: &UUri, listen_uuri: &UUri) {
     upclient_send.send(umessage).await.unwrap();
 
     // Waiting for the subscriber to receive data
-    sleep(Duration::from_millis(1000)).await;
+    test_lib::wait_until(|| pub_listener.get_recv_data() == target_data).await;
 
     // Compare the result
     assert_eq!(pub_listener.get_recv_data(), target_data);
@@ -98,8 +98,13 @@ async fn test_notification_and_subscribe(
 
     // Initialization
     let target_data = String::from("Hello World!");
-    let upclient_sender = test_lib::create_up_client_zenoh("sender").await.unwrap();
-    let upclient_receiver = test_lib::create_up_client_zenoh("receiver").await.unwrap();
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_sender = test_lib::create_up_client_zenoh(&runtime, "sender")
+        .await
+        .unwrap();
+    let upclient_receiver = test_lib::create_up_client_zenoh(&runtime, "receiver")
+        .await
+        .unwrap();
 
     // Register the listener
     let notification_listener = Arc::new(PublishNotificationListener::new());
@@ -107,8 +112,6 @@ async fn test_notification_and_subscribe(
         .register_listener(src_filter, sink_filter, notification_listener.clone())
         .await
         .unwrap();
-    // Waiting for listener to take effect
-    sleep(Duration::from_millis(1000)).await;
 
     // Send UMessage
     let umessage = UMessageBuilder::notification((*src_uuri).clone(), (*sink_uuri).clone())
@@ -117,7 +120,7 @@ async fn test_notification_and_subscribe(
     upclient_sender.send(umessage).await.unwrap();
 
     // Waiting for the subscriber to receive data
-    sleep(Duration::from_millis(1000)).await;
+    test_lib::wait_until(|| notification_listener.get_recv_data() == target_data).await;
 
     // Compare the result
     assert_eq!(notification_listener.get_recv_data(), target_data);
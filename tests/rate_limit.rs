@@ -0,0 +1,189 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+pub mod test_lib;
+
+use async_trait::async_trait;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use test_case::test_case;
+use up_rust::{
+    UCode, UListener, UMessage, UMessageBuilder, UPayloadFormat, UStatus, UTransport, UUri,
+};
+use up_transport_zenoh::{
+    rate_limit::{RateLimit, RateLimitConfig},
+    UPClientZenoh,
+};
+
+struct RecordListener {
+    received: Arc<Mutex<usize>>,
+    errors: Arc<Mutex<Vec<UCode>>>,
+    // Time to handle each message
+    delay: Duration,
+}
+impl RecordListener {
+    fn new(delay: Duration) -> Self {
+        RecordListener {
+            received: Arc::new(Mutex::new(0)),
+            errors: Arc::new(Mutex::new(vec![])),
+            delay,
+        }
+    }
+}
+#[async_trait]
+impl UListener for RecordListener {
+    async fn on_receive(&self, _msg: UMessage) {
+        *self.received.lock().unwrap() += 1;
+        tokio::time::sleep(self.delay).await;
+    }
+    async fn on_error(&self, err: UStatus) {
+        self.errors.lock().unwrap().push(err.get_code());
+    }
+}
+
+// The buckets are never refilled, so only the burst is delivered
+#[test_case(RateLimitConfig::new().with_per_source(RateLimit::new(0, 2)), Duration::ZERO; "Per source limit")]
+#[test_case(RateLimitConfig::new().with_per_listener(RateLimit::new(0, 2)), Duration::ZERO; "Per listener limit")]
+#[test_case(RateLimitConfig::new().with_max_in_flight(2), Duration::from_secs(2); "In-flight limit")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rate_limit(config: RateLimitConfig, delay: Duration) {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_send = test_lib::create_up_client_zenoh(&runtime, "vehicle")
+        .await
+        .unwrap();
+    let upclient_recv = UPClientZenoh::builder("subscriber")
+        .with_runtime(runtime)
+        .with_rate_limit(config)
+        .build()
+        .await
+        .unwrap();
+
+    // Register the listener
+    let uuri = test_lib::new_uuri("vehicle", 1, 1, 0x8000);
+    let listener = Arc::new(RecordListener::new(delay));
+    upclient_recv
+        .register_listener(&uuri, None, listener.clone())
+        .await
+        .unwrap();
+
+    // Flood the topic
+    for _ in 0..5 {
+        let umessage = UMessageBuilder::publish(uuri.clone())
+            .build_with_payload("data".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+            .unwrap();
+        upclient_send.send(umessage).await.unwrap();
+    }
+    test_lib::wait_until(|| upclient_recv.dropped_messages() == 3).await;
+    test_lib::wait_until(|| !listener.errors.lock().unwrap().is_empty()).await;
+
+    // Only the burst is delivered, and the drops are reported once
+    assert_eq!(upclient_recv.dropped_messages(), 3);
+    assert_eq!(*listener.received.lock().unwrap(), 2);
+    assert_eq!(
+        *listener.errors.lock().unwrap(),
+        vec![UCode::RESOURCE_EXHAUSTED]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rate_limit_per_source_shared_by_listeners() {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_send = test_lib::create_up_client_zenoh(&runtime, "vehicle")
+        .await
+        .unwrap();
+    let upclient_recv = UPClientZenoh::builder("subscriber")
+        .with_runtime(runtime)
+        .with_rate_limit(RateLimitConfig::new().with_per_source(RateLimit::new(0, 2)))
+        .build()
+        .await
+        .unwrap();
+
+    // Register two listeners matching the same messages
+    let uuri = test_lib::new_uuri("vehicle", 1, 1, 0x8000);
+    let listeners = [
+        Arc::new(RecordListener::new(Duration::ZERO)),
+        Arc::new(RecordListener::new(Duration::ZERO)),
+    ];
+    for listener in &listeners {
+        upclient_recv
+            .register_listener(&uuri, None, listener.clone())
+            .await
+            .unwrap();
+    }
+
+    // Each message takes one token of its source, however many listeners receive it
+    for _ in 0..2 {
+        let umessage = UMessageBuilder::publish(uuri.clone())
+            .build_with_payload("data".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+            .unwrap();
+        upclient_send.send(umessage).await.unwrap();
+    }
+    for listener in &listeners {
+        test_lib::wait_until(|| *listener.received.lock().unwrap() == 2).await;
+        assert!(listener.errors.lock().unwrap().is_empty());
+    }
+    assert_eq!(upclient_recv.dropped_messages(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rate_limit_per_source_with_same_id() {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_send = test_lib::create_up_client_zenoh(&runtime, "vehicle")
+        .await
+        .unwrap();
+    let upclient_recv = UPClientZenoh::builder("subscriber")
+        .with_runtime(runtime)
+        .with_rate_limit(RateLimitConfig::new().with_per_source(RateLimit::new(0, 1)))
+        .build()
+        .await
+        .unwrap();
+
+    // Register the listeners of two sources
+    let uuri_first = test_lib::new_uuri("vehicle", 1, 1, 0x8000);
+    let uuri_second = test_lib::new_uuri("vehicle", 2, 1, 0x8000);
+    let listener = Arc::new(RecordListener::new(Duration::ZERO));
+    for uuri in [&uuri_first, &uuri_second] {
+        upclient_recv
+            .register_listener(uuri, None, listener.clone())
+            .await
+            .unwrap();
+    }
+
+    // The first source runs out of its token, and its dropped message has the same ID
+    // as the message of the second source, which still takes its own token
+    let publish = |uuri: &UUri| {
+        UMessageBuilder::publish(uuri.clone())
+            .build_with_payload("data".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+            .unwrap()
+    };
+    upclient_send.send(publish(&uuri_first)).await.unwrap();
+    let dropped = publish(&uuri_first);
+    let mut same_id = publish(&uuri_second);
+    same_id.attributes.mut_or_insert_default().id = dropped.attributes.id.clone();
+    upclient_send.send(dropped).await.unwrap();
+    test_lib::wait_until(|| upclient_recv.dropped_messages() == 1).await;
+    upclient_send.send(same_id).await.unwrap();
+    test_lib::wait_until(|| *listener.received.lock().unwrap() == 2).await;
+    assert_eq!(*listener.received.lock().unwrap(), 2);
+    assert_eq!(upclient_recv.dropped_messages(), 1);
+}
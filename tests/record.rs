@@ -0,0 +1,136 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+pub mod test_lib;
+
+use async_trait::async_trait;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use up_rust::{UCode, UListener, UMessage, UMessageBuilder, UPayloadFormat, UStatus, UTransport};
+use up_transport_zenoh::record::{read_record_file, ReplayOptions};
+
+struct RecordListener {
+    recv_data: Arc<Mutex<Vec<String>>>,
+}
+impl RecordListener {
+    fn new() -> Self {
+        RecordListener {
+            recv_data: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+    fn get_recv_data(&self) -> Vec<String> {
+        self.recv_data.lock().unwrap().clone()
+    }
+}
+#[async_trait]
+impl UListener for RecordListener {
+    async fn on_receive(&self, msg: UMessage) {
+        let data = msg.payload.unwrap();
+        let value = data.into_iter().map(|c| c as char).collect::<String>();
+        self.recv_data.lock().unwrap().push(value);
+    }
+    async fn on_error(&self, err: UStatus) {
+        panic!("Internal Error: {err:?}");
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_record_and_replay() {
+    test_lib::before_test();
+
+    // Initialization
+    let file_path = std::env::temp_dir().join(format!("up_record_{}.uplog", std::process::id()));
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_send = test_lib::create_up_client_zenoh(&runtime, "publisher")
+        .await
+        .unwrap();
+    let upclient_record = Arc::new(
+        test_lib::create_up_client_zenoh(&runtime, "recorder")
+            .await
+            .unwrap(),
+    );
+    let upclient_recv = test_lib::create_up_client_zenoh(&runtime, "subscriber")
+        .await
+        .unwrap();
+    let topic = test_lib::new_uuri("publisher", 1, 1, 0x8000);
+    let other_topic = test_lib::new_uuri("publisher", 2, 1, 0x8000);
+
+    // Record the messages
+    let recorder = upclient_record.start_recording(&file_path).await.unwrap();
+    for (uri, data) in [
+        (&topic, "First"),
+        (&other_topic, "Other"),
+        (&topic, "Second"),
+    ] {
+        let umessage = UMessageBuilder::publish(uri.clone())
+            .build_with_payload(data.to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+            .unwrap();
+        upclient_send.send(umessage).await.unwrap();
+    }
+    test_lib::wait_until(|| read_record_file(&file_path).is_ok_and(|entries| entries.len() == 3))
+        .await;
+    drop(recorder);
+
+    // Replay only the messages of the topic
+    let listener = Arc::new(RecordListener::new());
+    upclient_recv
+        .register_listener(&topic, None, listener.clone())
+        .await
+        .unwrap();
+    let sent = upclient_record
+        .replay(
+            &file_path,
+            &ReplayOptions::default()
+                .with_speed(10.0)
+                .with_source_filter(topic.clone()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(sent, 2);
+    test_lib::wait_until(|| listener.get_recv_data().len() == 2).await;
+    assert_eq!(listener.get_recv_data(), vec!["First", "Second"]);
+
+    // The replay speed should be positive
+    let result = upclient_record
+        .replay(&file_path, &ReplayOptions::default().with_speed(0.0))
+        .await;
+    assert_eq!(result.unwrap_err().get_code(), UCode::INVALID_ARGUMENT);
+
+    // The tiny speed waits for the second message (nearly) forever instead of overflowing
+    let replay_client = upclient_record.clone();
+    let replay_path = file_path.clone();
+    let replay_topic = topic.clone();
+    let replay = tokio::spawn(async move {
+        replay_client
+            .replay(
+                &replay_path,
+                &ReplayOptions::default()
+                    .with_speed(f64::MIN_POSITIVE)
+                    .with_source_filter(replay_topic),
+            )
+            .await
+    });
+    test_lib::wait_until(|| listener.get_recv_data().len() == 3).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(!replay.is_finished());
+    replay.abort();
+    assert_eq!(listener.get_recv_data(), vec!["First", "Second", "First"]);
+
+    // Cleanup
+    upclient_recv
+        .unregister_listener(&topic, None, listener)
+        .await
+        .unwrap();
+    let _ = std::fs::remove_file(&file_path);
+}
@@ -39,7 +39,10 @@ async fn test_register_and_unregister(source_filter: &UUri, sink_filter: Option<
     test_lib::before_test();
 
     // Initialization
-    let upclient = test_lib::create_up_client_zenoh("myvehicle").await.unwrap();
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient = test_lib::create_up_client_zenoh(&runtime, "myvehicle")
+        .await
+        .unwrap();
     let foo_listener = Arc::new(FooListener);
 
     // Register the listener
@@ -15,15 +15,12 @@ pub mod test_lib;
 use async_trait::async_trait;
 use std::sync::{Arc, Mutex};
 use test_case::test_case;
-use tokio::{
-    runtime::Handle,
-    task,
-    time::{sleep, Duration},
-};
+use tokio::{runtime::Handle, task};
 use up_rust::{
-    RpcClient, UListener, UMessage, UMessageBuilder, UPayloadFormat, UStatus, UTransport, UUri,
+    RpcClient, UCode, UListener, UMessage, UMessageBuilder, UMessageType, UPayloadFormat, UStatus,
+    UTransport, UUri,
 };
-use up_transport_zenoh::UPClientZenoh;
+use up_transport_zenoh::{TransportError, UPClientZenoh};
 
 // RequestListener
 struct RequestListener {
@@ -73,6 +70,16 @@ impl UListener for RequestListener {
     }
 }
 
+// SilentListener never sends back the response
+struct SilentListener;
+#[async_trait]
+impl UListener for SilentListener {
+    async fn on_receive(&self, _msg: UMessage) {}
+    async fn on_error(&self, err: UStatus) {
+        panic!("Internal Error: {err:?}");
+    }
+}
+
 // ResponseListener
 struct ResponseListener {
     response_data: Arc<Mutex<String>>,
@@ -117,8 +124,15 @@ async fn test_rpc_server_client(
     test_lib::before_test();
 
     // Initialization
-    let upclient_client = test_lib::create_up_client_zenoh("requester").await.unwrap();
-    let upclient_server = Arc::new(test_lib::create_up_client_zenoh("responder").await.unwrap());
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_client = test_lib::create_up_client_zenoh(&runtime, "requester")
+        .await
+        .unwrap();
+    let upclient_server = Arc::new(
+        test_lib::create_up_client_zenoh(&runtime, "responder")
+            .await
+            .unwrap(),
+    );
     let request_data = String::from("This is the request data");
     let response_data = String::from("This is the response data");
 
@@ -132,8 +146,6 @@ async fn test_rpc_server_client(
         .register_listener(src_filter, sink_filter, request_listener.clone())
         .await
         .unwrap();
-    // Need some time for queryable to run
-    sleep(Duration::from_millis(1000)).await;
 
     // Send Request with invoke_method
     {
@@ -146,8 +158,13 @@ async fn test_rpc_server_client(
             )
             .await;
 
-        // Process the result
-        let payload = result.unwrap().payload.unwrap();
+        // Process the result, which carries the attributes of the response
+        let response = result.unwrap();
+        assert_eq!(
+            response.attributes.type_.enum_value_or_default(),
+            UMessageType::UMESSAGE_TYPE_RESPONSE
+        );
+        let payload = response.payload.unwrap();
         let value = payload.into_iter().map(|c| c as char).collect::<String>();
         assert_eq!(response_data.clone(), value);
     }
@@ -168,7 +185,7 @@ async fn test_rpc_server_client(
         upclient_client.send(umessage).await.unwrap();
 
         // Waiting for the callback to process data
-        sleep(Duration::from_millis(2000)).await;
+        test_lib::wait_until(|| response_listener.get_response_data() == response_data).await;
 
         // Compare the result
         assert_eq!(response_listener.get_response_data(), response_data);
@@ -186,3 +203,125 @@ async fn test_rpc_server_client(
         .await
         .unwrap();
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rpc_method_availability() {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_client = UPClientZenoh::builder("requester")
+        .with_runtime(runtime.clone())
+        .with_rpc_fail_fast(true)
+        .build()
+        .await
+        .unwrap();
+    let upclient_server = Arc::new(
+        test_lib::create_up_client_zenoh(&runtime, "responder")
+            .await
+            .unwrap(),
+    );
+    let src_uuri = test_lib::new_uuri("requester", 1, 1, 0);
+    let sink_uuri = test_lib::new_uuri("responder", 2, 1, 1);
+    let src_filter = test_lib::new_uuri("*", 0xFFFF, 0xFF, 0xFFFF);
+    let request = || {
+        UMessageBuilder::request(sink_uuri.clone(), src_uuri.clone(), 1000)
+            .build_with_payload("data".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+            .unwrap()
+    };
+
+    // Nobody serves the method
+    assert!(!upclient_client
+        .is_method_available(&sink_uuri)
+        .await
+        .unwrap());
+    let result = upclient_client.send(request()).await;
+    assert_eq!(result.unwrap_err().get_code(), UCode::UNAVAILABLE);
+    let result = upclient_client
+        .invoke_method(sink_uuri.clone(), request())
+        .await;
+    assert!(result.is_err());
+
+    // The method is available after the server registers the listener
+    let request_listener = Arc::new(RequestListener::new(
+        upclient_server.clone(),
+        "data".to_string(),
+        "response".to_string(),
+    ));
+    upclient_server
+        .register_listener(&src_filter, Some(&sink_uuri), request_listener.clone())
+        .await
+        .unwrap();
+    assert!(upclient_client
+        .is_method_available(&sink_uuri)
+        .await
+        .unwrap());
+    let result = upclient_client
+        .invoke_method(sink_uuri.clone(), request())
+        .await;
+    assert!(result.is_ok());
+
+    // Wildcard method can't be checked
+    assert!(upclient_client
+        .is_method_available(&src_filter)
+        .await
+        .is_err());
+
+    // Cleanup
+    upclient_server
+        .unregister_listener(&src_filter, Some(&sink_uuri), request_listener)
+        .await
+        .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rpc_failure_paths() {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_client = test_lib::create_up_client_zenoh(&runtime, "requester")
+        .await
+        .unwrap();
+    let upclient_server = test_lib::create_up_client_zenoh(&runtime, "responder")
+        .await
+        .unwrap();
+    let src_uuri = test_lib::new_uuri("requester", 1, 1, 0);
+    let sink_uuri = test_lib::new_uuri("responder", 2, 1, 1);
+    let request = || {
+        UMessageBuilder::request(sink_uuri.clone(), src_uuri.clone(), 500)
+            .build_with_payload("data".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+            .unwrap()
+    };
+
+    // Nobody serves the method
+    let result = upclient_client.invoke_rpc(request()).await;
+    assert!(matches!(result, Err(TransportError::Unavailable(_))));
+
+    // Sending the request without the response listener
+    let result = upclient_client.send(request()).await;
+    assert_eq!(result.unwrap_err().get_code(), UCode::FAILED_PRECONDITION);
+
+    // The server never responds
+    let silent_listener = Arc::new(SilentListener);
+    upclient_server
+        .register_listener(&src_uuri, Some(&sink_uuri), silent_listener.clone())
+        .await
+        .unwrap();
+    let error = upclient_client.invoke_rpc(request()).await.unwrap_err();
+    assert!(matches!(error, TransportError::Timeout));
+    assert_eq!(UStatus::from(error).get_code(), UCode::DEADLINE_EXCEEDED);
+
+    // Respond to the unknown request
+    let response = UMessageBuilder::response_for_request(&request().attributes)
+        .build_with_payload("response".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+    let result = upclient_server.send(response).await;
+    assert_eq!(result.unwrap_err().get_code(), UCode::NOT_FOUND);
+
+    // Cleanup
+    upclient_server
+        .unregister_listener(&src_uuri, Some(&sink_uuri), silent_listener)
+        .await
+        .unwrap();
+}
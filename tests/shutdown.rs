@@ -0,0 +1,270 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+pub mod test_lib;
+
+use async_trait::async_trait;
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use test_case::test_case;
+use up_rust::{
+    RpcClient, UCode, UListener, UMessage, UMessageBuilder, UPayloadFormat, UStatus, UTransport,
+    UUri,
+};
+use up_transport_zenoh::discovery::DiscoveryListener;
+
+// SlowListener takes `delay` to handle each message
+struct SlowListener {
+    started: AtomicUsize,
+    finished: AtomicUsize,
+    delay: Duration,
+}
+impl SlowListener {
+    fn new(delay: Duration) -> Self {
+        SlowListener {
+            started: AtomicUsize::new(0),
+            finished: AtomicUsize::new(0),
+            delay,
+        }
+    }
+}
+#[async_trait]
+impl UListener for SlowListener {
+    async fn on_receive(&self, _msg: UMessage) {
+        self.started.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(self.delay).await;
+        self.finished.fetch_add(1, Ordering::SeqCst);
+    }
+    async fn on_error(&self, err: UStatus) {
+        panic!("Internal Error: {err:?}");
+    }
+}
+#[async_trait]
+impl DiscoveryListener for SlowListener {
+    async fn on_join(&self, _uri: UUri) {
+        self.started.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(self.delay).await;
+        self.finished.fetch_add(1, Ordering::SeqCst);
+    }
+    async fn on_leave(&self, _uri: UUri) {}
+}
+
+#[test_case(Duration::from_secs(3), None; "Callback finished before the deadline")]
+#[test_case(Duration::from_millis(50), Some(UCode::DEADLINE_EXCEEDED); "Callback still running after the deadline")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_close_waits_for_callbacks(timeout: Duration, expected_error: Option<UCode>) {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_send = test_lib::create_up_client_zenoh(&runtime, "vehicle")
+        .await
+        .unwrap();
+    let upclient_recv = test_lib::create_up_client_zenoh(&runtime, "subscriber")
+        .await
+        .unwrap();
+    let uuri = test_lib::new_uuri("vehicle", 1, 1, 0x8000);
+    let listener = Arc::new(SlowListener::new(Duration::from_millis(500)));
+    upclient_recv
+        .register_listener(&uuri, None, listener.clone())
+        .await
+        .unwrap();
+
+    // Start the callback
+    let umessage = UMessageBuilder::publish(uuri.clone())
+        .build_with_payload("data".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+    upclient_send.send(umessage).await.unwrap();
+    test_lib::wait_until(|| listener.started.load(Ordering::SeqCst) == 1).await;
+
+    // Close while the callback is running
+    let result = Arc::new(upclient_recv).close(timeout).await;
+    if let Some(code) = expected_error {
+        assert_eq!(result.unwrap_err().get_code(), code);
+        assert_eq!(listener.finished.load(Ordering::SeqCst), 0);
+    } else {
+        assert!(result.is_ok());
+        assert_eq!(listener.finished.load(Ordering::SeqCst), 1);
+    }
+
+    // No more message is delivered after closed
+    let umessage = UMessageBuilder::publish(uuri.clone())
+        .build_with_payload("data".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+    upclient_send.send(umessage).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(listener.started.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_close_answers_pending_requests() {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_client = Arc::new(
+        test_lib::create_up_client_zenoh(&runtime, "requester")
+            .await
+            .unwrap(),
+    );
+    let upclient_server = test_lib::create_up_client_zenoh(&runtime, "responder")
+        .await
+        .unwrap();
+    let src_uuri = test_lib::new_uuri("requester", 1, 1, 0);
+    let sink_uuri = test_lib::new_uuri("responder", 2, 1, 1);
+    // The request listener never responds
+    let request_listener = Arc::new(SlowListener::new(Duration::ZERO));
+    upclient_server
+        .register_listener(
+            &test_lib::new_uuri("*", 0xFFFF, 0xFF, 0xFFFF),
+            Some(&sink_uuri),
+            request_listener.clone(),
+        )
+        .await
+        .unwrap();
+
+    // Send the request with the long TTL
+    let start = Instant::now();
+    let client = upclient_client.clone();
+    let request = UMessageBuilder::request(sink_uuri.clone(), src_uuri, 10000)
+        .build_with_payload("data".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+    let pending = tokio::spawn(async move { client.invoke_method(sink_uuri, request).await });
+    test_lib::wait_until(|| request_listener.finished.load(Ordering::SeqCst) == 1).await;
+
+    // The requester gets the error once the server is closed, instead of waiting until the TTL
+    Arc::new(upclient_server)
+        .close(Duration::from_secs(1))
+        .await
+        .unwrap();
+    assert!(pending.await.unwrap().is_err());
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_close_waits_for_discovery_callbacks() {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient = test_lib::create_up_client_zenoh(&runtime, "observer")
+        .await
+        .unwrap();
+    let listener = Arc::new(SlowListener::new(Duration::from_millis(500)));
+    upclient
+        .register_discovery_listener(listener.clone())
+        .await
+        .unwrap();
+
+    // Another authority joins
+    let _upclient_joined = test_lib::create_up_client_zenoh(&runtime, "joined")
+        .await
+        .unwrap();
+    test_lib::wait_until(|| listener.started.load(Ordering::SeqCst) > 0).await;
+
+    // Close while the discovery callback is running
+    Arc::new(upclient)
+        .close(Duration::from_secs(3))
+        .await
+        .unwrap();
+    assert_eq!(
+        listener.finished.load(Ordering::SeqCst),
+        listener.started.load(Ordering::SeqCst)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_close_shared_client() {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_send = test_lib::create_up_client_zenoh(&runtime, "vehicle")
+        .await
+        .unwrap();
+    let upclient_recv = Arc::new(
+        test_lib::create_up_client_zenoh(&runtime, "subscriber")
+            .await
+            .unwrap(),
+    );
+    let uuri = test_lib::new_uuri("vehicle", 1, 1, 0x8000);
+    let listener = Arc::new(SlowListener::new(Duration::ZERO));
+    upclient_recv
+        .register_listener(&uuri, None, listener.clone())
+        .await
+        .unwrap();
+
+    // The client is still held by another one, e.g. UStreamer
+    let holder = upclient_recv.clone();
+    let result = upclient_recv.close(Duration::from_millis(100)).await;
+    assert_eq!(result.unwrap_err().get_code(), UCode::FAILED_PRECONDITION);
+
+    // It stops receiving anyway
+    let umessage = UMessageBuilder::publish(uuri.clone())
+        .build_with_payload("data".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+    upclient_send.send(umessage).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(listener.started.load(Ordering::SeqCst), 0);
+
+    // The other holder can't send or register listeners either
+    let umessage = UMessageBuilder::publish(uuri.clone())
+        .build_with_payload("data".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+    let result = holder.send(umessage).await;
+    assert_eq!(result.unwrap_err().get_code(), UCode::FAILED_PRECONDITION);
+    let result = holder
+        .register_listener(&uuri, None, listener.clone())
+        .await;
+    assert_eq!(result.unwrap_err().get_code(), UCode::FAILED_PRECONDITION);
+    drop(holder);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_close_keeps_shared_runtime() {
+    test_lib::before_test();
+
+    // Initialization: the clients share the runtime
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_closed = test_lib::create_up_client_zenoh(&runtime, "closed")
+        .await
+        .unwrap();
+    let upclient_send = test_lib::create_up_client_zenoh(&runtime, "vehicle")
+        .await
+        .unwrap();
+    let upclient_recv = test_lib::create_up_client_zenoh(&runtime, "subscriber")
+        .await
+        .unwrap();
+    let uuri = test_lib::new_uuri("vehicle", 1, 1, 0x8000);
+    let listener = Arc::new(SlowListener::new(Duration::ZERO));
+    upclient_recv
+        .register_listener(&uuri, None, listener.clone())
+        .await
+        .unwrap();
+
+    // Closing one client doesn't close the runtime of the others
+    Arc::new(upclient_closed)
+        .close(Duration::from_secs(1))
+        .await
+        .unwrap();
+    let umessage = UMessageBuilder::publish(uuri.clone())
+        .build_with_payload("data".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+    upclient_send.send(umessage).await.unwrap();
+    test_lib::wait_until(|| listener.finished.load(Ordering::SeqCst) == 1).await;
+    assert_eq!(listener.finished.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,198 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+pub mod test_lib;
+
+use async_trait::async_trait;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use test_case::test_case;
+use up_rust::{UCode, UListener, UMessage, UMessageBuilder, UPayloadFormat, UStatus, UTransport};
+use up_transport_zenoh::{
+    history::HistoryConfig,
+    signing::{SignaturePolicy, SigningConfig},
+    UPClientZenoh,
+};
+
+struct RecordListener {
+    received: Arc<Mutex<usize>>,
+    errors: Arc<Mutex<Vec<UCode>>>,
+}
+impl RecordListener {
+    fn new() -> Self {
+        RecordListener {
+            received: Arc::new(Mutex::new(0)),
+            errors: Arc::new(Mutex::new(vec![])),
+        }
+    }
+}
+#[async_trait]
+impl UListener for RecordListener {
+    async fn on_receive(&self, _msg: UMessage) {
+        *self.received.lock().unwrap() += 1;
+    }
+    async fn on_error(&self, err: UStatus) {
+        self.errors.lock().unwrap().push(err.get_code());
+    }
+}
+
+#[test_case(Some(b"secret".as_slice()), Some(b"secret".as_slice()), SignaturePolicy::RequireSigned, None; "Signed with the right key")]
+#[test_case(Some(b"secret".as_slice()), Some(b"wrong".as_slice()), SignaturePolicy::AcceptUnsigned, Some(UCode::UNAUTHENTICATED); "Signed with the wrong key")]
+#[test_case(None, Some(b"secret".as_slice()), SignaturePolicy::RequireSigned, Some(UCode::UNAUTHENTICATED); "Unsigned and required")]
+#[test_case(None, Some(b"secret".as_slice()), SignaturePolicy::AcceptUnsigned, None; "Unsigned and accepted")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_signing(
+    sender_key: Option<&[u8]>,
+    receiver_key: Option<&[u8]>,
+    policy: SignaturePolicy,
+    expected_error: Option<UCode>,
+) {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let mut builder_send = UPClientZenoh::builder("vehicle").with_runtime(runtime.clone());
+    if let Some(key) = sender_key {
+        // Load the key from the file, which is unique for each test case running in parallel
+        let key_path = std::env::temp_dir().join(format!(
+            "up_signing_{}_{policy:?}_{expected_error:?}.key",
+            std::process::id()
+        ));
+        std::fs::write(&key_path, key).unwrap();
+        builder_send = builder_send.with_signing(
+            SigningConfig::new()
+                .with_key_file("vehicle", &key_path)
+                .unwrap(),
+        );
+        std::fs::remove_file(&key_path).unwrap();
+    }
+    let upclient_send = builder_send.build().await.unwrap();
+    let mut config = SigningConfig::new().with_policy(policy);
+    if let Some(key) = receiver_key {
+        config = config.with_key("vehicle", key);
+    }
+    let upclient_recv = UPClientZenoh::builder("subscriber")
+        .with_runtime(runtime)
+        .with_signing(config)
+        .build()
+        .await
+        .unwrap();
+
+    // Register the listener
+    let uuri = test_lib::new_uuri("vehicle", 1, 1, 0x8000);
+    let listener = Arc::new(RecordListener::new());
+    upclient_recv
+        .register_listener(&uuri, None, listener.clone())
+        .await
+        .unwrap();
+
+    // Publish
+    let umessage = UMessageBuilder::publish(uuri.clone())
+        .build_with_payload("command".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+    upclient_send.send(umessage).await.unwrap();
+    test_lib::wait_until(|| {
+        *listener.received.lock().unwrap() + listener.errors.lock().unwrap().len() == 1
+    })
+    .await;
+
+    // Check the result
+    if let Some(code) = expected_error {
+        assert_eq!(*listener.received.lock().unwrap(), 0);
+        assert_eq!(*listener.errors.lock().unwrap(), vec![code]);
+    } else {
+        assert_eq!(*listener.received.lock().unwrap(), 1);
+        assert!(listener.errors.lock().unwrap().is_empty());
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_signing_last_value_and_history() {
+    test_lib::before_test();
+
+    // Initialization: the publisher doesn't sign
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_send = test_lib::create_up_client_zenoh(&runtime, "vehicle")
+        .await
+        .unwrap();
+    let upclient_recv = UPClientZenoh::builder("subscriber")
+        .with_runtime(runtime)
+        .with_signing(SigningConfig::new().with_key("vehicle", b"secret".to_vec()))
+        .with_history(HistoryConfig::new(10))
+        .build()
+        .await
+        .unwrap();
+    let uuri = test_lib::new_uuri("vehicle", 1, 1, 0x8000);
+    upclient_send.enable_last_value_cache(&uuri).await.unwrap();
+
+    // Publish
+    let umessage = UMessageBuilder::publish(uuri.clone())
+        .build_with_payload("command".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+    upclient_send.send(umessage).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // The unsigned message is neither the last value nor recorded in the history
+    assert!(upclient_recv
+        .get_last_values(&uuri)
+        .await
+        .unwrap()
+        .is_empty());
+    let history = upclient_recv
+        .get_history(&uuri, None, UNIX_EPOCH..SystemTime::now())
+        .unwrap();
+    assert!(history.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_signing_foreign_source() {
+    test_lib::before_test();
+
+    // Initialization: the relay signs its own messages only
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_send = UPClientZenoh::builder("gateway")
+        .with_runtime(runtime.clone())
+        .with_signing(SigningConfig::new().with_key("gateway", b"secret".to_vec()))
+        .build()
+        .await
+        .unwrap();
+    let upclient_recv = UPClientZenoh::builder("subscriber")
+        .with_runtime(runtime)
+        .with_signing(
+            SigningConfig::new()
+                .with_key("gateway", b"secret".to_vec())
+                .with_policy(SignaturePolicy::AcceptUnsigned),
+        )
+        .build()
+        .await
+        .unwrap();
+    let uuri = test_lib::new_uuri("vehicle", 1, 1, 0x8000);
+    let listener = Arc::new(RecordListener::new());
+    upclient_recv
+        .register_listener(&uuri, None, listener.clone())
+        .await
+        .unwrap();
+
+    // The message on behalf of another authority is sent unsigned, not signed by the relay
+    let umessage = UMessageBuilder::publish(uuri.clone())
+        .build_with_payload("command".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+    upclient_send.send(umessage).await.unwrap();
+    test_lib::wait_until(|| {
+        *listener.received.lock().unwrap() + listener.errors.lock().unwrap().len() == 1
+    })
+    .await;
+    assert_eq!(*listener.received.lock().unwrap(), 1);
+    assert!(listener.errors.lock().unwrap().is_empty());
+}
@@ -0,0 +1,69 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+pub mod test_lib;
+
+use std::sync::{Arc, Mutex};
+use up_rust::{UMessageBuilder, UMessageType, UPayloadFormat, UTransport};
+use up_transport_zenoh::sniffer::SniffedMessage;
+use zenoh::prelude::r#async::*;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_sniffer() {
+    test_lib::before_test();
+
+    // Initialization
+    let runtime = test_lib::create_loopback_runtime().await;
+    let upclient_send = test_lib::create_up_client_zenoh(&runtime, "publisher")
+        .await
+        .unwrap();
+    let upclient_sniff = test_lib::create_up_client_zenoh(&runtime, "sniffer")
+        .await
+        .unwrap();
+    let sniffed = Arc::new(Mutex::new(Vec::<SniffedMessage>::new()));
+    let sniffed_cloned = sniffed.clone();
+    let _sniffer = upclient_sniff
+        .start_sniffing("up/**", move |msg| sniffed_cloned.lock().unwrap().push(msg))
+        .await
+        .unwrap();
+
+    // Send the uProtocol message
+    let topic = test_lib::new_uuri("publisher", 0x10AB, 3, 0x80CD);
+    let umessage = UMessageBuilder::publish(topic.clone())
+        .build_with_payload("Hello".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+    upclient_send.send(umessage).await.unwrap();
+    test_lib::wait_until(|| sniffed.lock().unwrap().len() == 1).await;
+    {
+        let msg = sniffed.lock().unwrap()[0].clone();
+        assert_eq!(msg.key, "up/publisher/10AB/3/80CD/{}/{}/{}/{}");
+        assert_eq!(msg.uris.unwrap(), (topic, None));
+        assert_eq!(
+            msg.attributes.unwrap().type_.enum_value_or_default(),
+            UMessageType::UMESSAGE_TYPE_PUBLISH
+        );
+        assert_eq!(msg.payload_size, 5);
+    }
+
+    // Send the raw Zenoh message without attachment
+    let session = zenoh::init(runtime).res().await.unwrap();
+    session
+        .put("up/raw/1/1/8000/{}/{}/{}/{}", "raw")
+        .res()
+        .await
+        .unwrap();
+    test_lib::wait_until(|| sniffed.lock().unwrap().len() == 2).await;
+    let msg = sniffed.lock().unwrap()[1].clone();
+    assert!(msg.uris.is_ok());
+    assert!(msg.attributes.is_err());
+    assert!(msg.to_string().contains("malformed attachment"));
+}
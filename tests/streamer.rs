@@ -0,0 +1,193 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+pub mod test_lib;
+
+use async_trait::async_trait;
+use std::sync::Arc;
+use test_case::test_case;
+use test_lib::{payload_to_string, RecordListener};
+use up_rust::{
+    RpcClient, UCode, UListener, UMessage, UMessageBuilder, UPayloadFormat, UStatus, UTransport,
+};
+use up_transport_zenoh::{
+    streamer::{ForwardingDirection, ForwardingRule, UStreamer},
+    UPClientZenoh,
+};
+
+// Respond to the request with the payload prefixed by the server name
+struct EchoListener {
+    up_client: Arc<UPClientZenoh>,
+    name: String,
+}
+#[async_trait]
+impl UListener for EchoListener {
+    async fn on_receive(&self, msg: UMessage) {
+        let data = format!("{}: {}", self.name, payload_to_string(&msg));
+        let umessage = UMessageBuilder::response_for_request(&msg.attributes)
+            .build_with_payload(data, UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+            .unwrap();
+        self.up_client.send(umessage).await.unwrap();
+    }
+    async fn on_error(&self, err: UStatus) {
+        panic!("Internal Error: {err:?}");
+    }
+}
+
+// The vehicle and the streamer share one Zenoh network, and the cloud is in another one
+struct Network {
+    vehicle: Arc<UPClientZenoh>,
+    cloud: Arc<UPClientZenoh>,
+    streamer: UStreamer,
+}
+
+async fn create_network() -> Network {
+    let vehicle_runtime = test_lib::create_loopback_runtime().await;
+    let cloud_runtime = test_lib::create_loopback_runtime().await;
+    let vehicle = test_lib::create_up_client_zenoh(&vehicle_runtime, "vehicle")
+        .await
+        .unwrap();
+    let cloud = test_lib::create_up_client_zenoh(&cloud_runtime, "cloud")
+        .await
+        .unwrap();
+    let streamer_zenoh = test_lib::create_up_client_zenoh(&vehicle_runtime, "gateway")
+        .await
+        .unwrap();
+    let streamer_transport = test_lib::create_up_client_zenoh(&cloud_runtime, "gateway")
+        .await
+        .unwrap();
+    let streamer = UStreamer::new(Arc::new(streamer_zenoh), Arc::new(streamer_transport));
+    for rule in [
+        ForwardingRule::messages_to(ForwardingDirection::ToTransport, "cloud"),
+        ForwardingRule::messages_to(ForwardingDirection::ToZenoh, "vehicle"),
+        ForwardingRule::publish_from(ForwardingDirection::ToTransport, "vehicle"),
+    ] {
+        streamer.add_rule(rule).await.unwrap();
+    }
+    Network {
+        vehicle: Arc::new(vehicle),
+        cloud: Arc::new(cloud),
+        streamer,
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_streamer_publish_notification() {
+    test_lib::before_test();
+
+    // Initialization
+    let network = create_network().await;
+    let topic = test_lib::new_uuri("vehicle", 1, 1, 0x8000);
+    let publish_listener = Arc::new(RecordListener::new());
+    network
+        .cloud
+        .register_listener(&topic, None, publish_listener.clone())
+        .await
+        .unwrap();
+    let sink = test_lib::new_uuri("cloud", 2, 1, 0);
+    let notification_listener = Arc::new(RecordListener::new());
+    network
+        .cloud
+        .register_listener(&topic, Some(&sink), notification_listener.clone())
+        .await
+        .unwrap();
+
+    // The vehicle sends, and the cloud receives through the streamer
+    let umessage = UMessageBuilder::publish(topic.clone())
+        .build_with_payload("Publish".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+    network.vehicle.send(umessage).await.unwrap();
+    let umessage = UMessageBuilder::notification(topic.clone(), sink.clone())
+        .build_with_payload(
+            "Notification".to_string(),
+            UPayloadFormat::UPAYLOAD_FORMAT_TEXT,
+        )
+        .unwrap();
+    network.vehicle.send(umessage).await.unwrap();
+    test_lib::wait_until(|| !publish_listener.received.lock().unwrap().is_empty()).await;
+    test_lib::wait_until(|| !notification_listener.received.lock().unwrap().is_empty()).await;
+    assert_eq!(*publish_listener.received.lock().unwrap(), vec!["Publish"]);
+    assert_eq!(
+        *notification_listener.received.lock().unwrap(),
+        vec!["Notification"]
+    );
+}
+
+#[test_case("vehicle", "cloud"; "Zenoh to the other transport")]
+#[test_case("cloud", "vehicle"; "The other transport to Zenoh")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_streamer_rpc(client_authority: &str, server_authority: &str) {
+    test_lib::before_test();
+
+    // Initialization
+    let network = create_network().await;
+    let (client, server) = if client_authority == "vehicle" {
+        (network.vehicle.clone(), network.cloud.clone())
+    } else {
+        (network.cloud.clone(), network.vehicle.clone())
+    };
+    let method = test_lib::new_uuri(server_authority, 2, 1, 1);
+    server
+        .register_listener(
+            &test_lib::new_uuri("*", 0xFFFF, 0xFF, 0xFFFF),
+            Some(&method),
+            Arc::new(EchoListener {
+                up_client: server.clone(),
+                name: server_authority.to_string(),
+            }),
+        )
+        .await
+        .unwrap();
+
+    // The response is correlated with the original request through the streamer
+    let src_uuri = test_lib::new_uuri(client_authority, 1, 1, 0);
+    let request = UMessageBuilder::request(method.clone(), src_uuri, 1000)
+        .build_with_payload("Request".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+        .unwrap();
+    let response = client.invoke_method(method, request).await.unwrap();
+    assert_eq!(
+        payload_to_string(&response),
+        format!("{server_authority}: Request")
+    );
+    assert_eq!(network.streamer.pending_requests(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_streamer_rules() {
+    test_lib::before_test();
+
+    let network = create_network().await;
+    let rule = ForwardingRule::messages_to(ForwardingDirection::ToTransport, "cloud");
+
+    // The same rule can't be added twice, and the reverse one would loop
+    let result = network.streamer.add_rule(rule.clone()).await;
+    assert_eq!(result.unwrap_err().get_code(), UCode::ALREADY_EXISTS);
+    let reverse = ForwardingRule::messages_to(ForwardingDirection::ToZenoh, "cloud");
+    let result = network.streamer.add_rule(reverse).await;
+    assert_eq!(result.unwrap_err().get_code(), UCode::INVALID_ARGUMENT);
+
+    // Only one of the same rules added concurrently succeeds
+    let other = ForwardingRule::messages_to(ForwardingDirection::ToTransport, "other");
+    let (first, second) = tokio::join!(
+        network.streamer.add_rule(other.clone()),
+        network.streamer.add_rule(other.clone())
+    );
+    assert!(first.is_ok() != second.is_ok());
+    let result = first.and(second);
+    assert_eq!(result.unwrap_err().get_code(), UCode::ALREADY_EXISTS);
+    network.streamer.remove_rule(&other).await.unwrap();
+
+    // Remove the rule
+    network.streamer.remove_rule(&rule).await.unwrap();
+    let result = network.streamer.remove_rule(&rule).await;
+    assert_eq!(result.unwrap_err().get_code(), UCode::NOT_FOUND);
+}
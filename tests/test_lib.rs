@@ -10,9 +10,19 @@
  *
  * SPDX-License-Identifier: Apache-2.0
  ********************************************************************************/
-use std::sync::Once;
-use up_rust::{UStatus, UUri};
+use async_trait::async_trait;
+use std::{
+    net::TcpListener,
+    sync::{Mutex, Once},
+};
+use tokio::time::{sleep, Duration, Instant};
+use up_rust::{UCode, UListener, UMessage, UStatus, UUri};
 use up_transport_zenoh::{Config, UPClientZenoh};
+use zenoh::runtime::Runtime;
+
+// The maximum time to wait for a condition in the tests
+const WAIT_TIMEOUT: Duration = Duration::from_millis(5000);
+const WAIT_INTERVAL: Duration = Duration::from_millis(10);
 
 static INIT: Once = Once::new();
 
@@ -20,10 +30,27 @@ pub fn before_test() {
     INIT.call_once(env_logger::init);
 }
 
+/// # Panics
+/// Will panic if unable to create the loopback runtime
+pub async fn create_loopback_runtime() -> Runtime {
+    UPClientZenoh::new_loopback_runtime().await.unwrap()
+}
+
 /// # Errors
 /// Will return `Err` if unable to create `UPClientZenoh`
-pub async fn create_up_client_zenoh(uauthority: &str) -> Result<UPClientZenoh, UStatus> {
-    UPClientZenoh::new(Config::default(), uauthority.to_string()).await
+pub async fn create_up_client_zenoh(
+    runtime: &Runtime,
+    uauthority: &str,
+) -> Result<UPClientZenoh, UStatus> {
+    UPClientZenoh::new_with_runtime(runtime.clone(), uauthority.to_string()).await
+}
+
+// Wait until the condition is met or the timeout is reached
+pub async fn wait_until<F: Fn() -> bool>(condition: F) {
+    let deadline = Instant::now() + WAIT_TIMEOUT;
+    while !condition() && Instant::now() < deadline {
+        sleep(WAIT_INTERVAL).await;
+    }
 }
 
 #[allow(clippy::must_use_candidate)]
@@ -36,3 +63,81 @@ pub fn new_uuri(authority: &str, ue_id: u32, ue_version_major: u8, resource_id:
         ..Default::default()
     }
 }
+
+/// Get a free TCP port on localhost, so the tests running in parallel don't conflict
+///
+/// # Panics
+/// Will panic if unable to bind any port
+#[must_use]
+pub fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// The config without scouting, listening on or connecting to the endpoints
+///
+/// # Panics
+/// Will panic if the endpoints are invalid
+#[must_use]
+pub fn tcp_config(listen: &[&str], connect: &[&str]) -> Config {
+    let mut config = Config::default();
+    config.scouting.multicast.set_enabled(Some(false)).unwrap();
+    config.scouting.gossip.set_enabled(Some(false)).unwrap();
+    config.listen.endpoints = listen.iter().map(|e| e.parse().unwrap()).collect();
+    config.connect.endpoints = connect.iter().map(|e| e.parse().unwrap()).collect();
+    config
+}
+
+/// # Panics
+/// Will panic if the message has no payload
+#[must_use]
+pub fn payload_to_string(msg: &UMessage) -> String {
+    let data = msg.payload.clone().unwrap();
+    data.into_iter().map(|c| c as char).collect::<String>()
+}
+
+/// The listener recording the payloads of the received messages as text
+#[derive(Default)]
+pub struct RecordListener {
+    pub received: Mutex<Vec<String>>,
+}
+impl RecordListener {
+    #[must_use]
+    pub fn new() -> Self {
+        RecordListener::default()
+    }
+}
+#[async_trait]
+impl UListener for RecordListener {
+    async fn on_receive(&self, msg: UMessage) {
+        self.received.lock().unwrap().push(payload_to_string(&msg));
+    }
+    async fn on_error(&self, err: UStatus) {
+        panic!("Internal Error: {err:?}");
+    }
+}
+
+/// The listener counting the received messages and recording the codes of the errors
+#[derive(Default)]
+pub struct CheckListener {
+    pub received: Mutex<usize>,
+    pub errors: Mutex<Vec<UCode>>,
+}
+impl CheckListener {
+    #[must_use]
+    pub fn new() -> Self {
+        CheckListener::default()
+    }
+}
+#[async_trait]
+impl UListener for CheckListener {
+    async fn on_receive(&self, _msg: UMessage) {
+        *self.received.lock().unwrap() += 1;
+    }
+    async fn on_error(&self, err: UStatus) {
+        self.errors.lock().unwrap().push(err.get_code());
+    }
+}
@@ -0,0 +1,249 @@
+/********************************************************************************
+ * Copyright (c) 2024 Contributors to the Eclipse Foundation
+ *
+ * See the NOTICE file(s) distributed with this work for additional
+ * information regarding copyright ownership.
+ *
+ * This program and the accompanying materials are made available under the
+ * terms of the Apache License Version 2.0 which is available at
+ * https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * SPDX-License-Identifier: Apache-2.0
+ ********************************************************************************/
+pub mod test_lib;
+
+use rcgen::{BasicConstraints, Certificate, CertificateParams, DnType, IsCa};
+use std::{path::PathBuf, sync::Arc};
+use test_lib::CheckListener;
+use tokio::time::{sleep, Duration};
+use up_rust::{UCode, UMessageBuilder, UPayloadFormat, UTransport};
+use up_transport_zenoh::{tls::TlsConfig, Config, UPClientZenoh};
+
+// The self-signed CA and the certificates issued to the authorities, written to a unique directory
+struct TestPki {
+    dir: PathBuf,
+    ca: Certificate,
+}
+impl TestPki {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("up_tls_{name}_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut params = CertificateParams::new(vec![]);
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "uProtocol test CA");
+        let ca = Certificate::from_params(params).unwrap();
+        std::fs::write(dir.join("ca.pem"), ca.serialize_pem().unwrap()).unwrap();
+        TestPki { dir, ca }
+    }
+
+    fn ca_path(&self) -> PathBuf {
+        self.dir.join("ca.pem")
+    }
+
+    // Issue the certificate for localhost with the common name and return the certificate and key paths
+    fn issue(&self, common_name: &str) -> (PathBuf, PathBuf) {
+        let mut params = CertificateParams::new(vec!["localhost".to_string()]);
+        params
+            .distinguished_name
+            .push(DnType::CommonName, common_name);
+        let cert = Certificate::from_params(params).unwrap();
+        let cert_path = self.dir.join(format!("{common_name}.pem"));
+        let key_path = self.dir.join(format!("{common_name}.key"));
+        std::fs::write(
+            &cert_path,
+            cert.serialize_pem_with_signer(&self.ca).unwrap(),
+        )
+        .unwrap();
+        std::fs::write(&key_path, cert.serialize_private_key_pem()).unwrap();
+        (cert_path, key_path)
+    }
+}
+impl Drop for TestPki {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+// The config without scouting, so only the TLS endpoints are used
+fn isolated_config() -> Config {
+    let mut config = Config::default();
+    config.scouting.multicast.set_enabled(Some(false)).unwrap();
+    config.scouting.gossip.set_enabled(Some(false)).unwrap();
+    config.listen.endpoints = vec![];
+    config.connect.endpoints = vec![];
+    config
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_mtls_publish() {
+    test_lib::before_test();
+
+    // Initialization
+    let pki = TestPki::new("mtls");
+    let (server_cert, server_key) = pki.issue("vehicle");
+    let (client_cert, client_key) = pki.issue("cloud");
+    let endpoint = format!("localhost:{}", test_lib::free_port());
+    let upclient_server = UPClientZenoh::builder("vehicle")
+        .with_config(isolated_config())
+        .with_tls(
+            TlsConfig::new(pki.ca_path())
+                .with_certificate(server_cert, server_key)
+                .with_mutual_auth(true)
+                .with_listen(&endpoint)
+                .with_own_certificate_check(true),
+        )
+        .build()
+        .await
+        .unwrap();
+    let upclient_client = UPClientZenoh::builder("cloud")
+        .with_config(isolated_config())
+        .with_tls(
+            TlsConfig::new(pki.ca_path())
+                .with_certificate(client_cert, client_key)
+                .with_mutual_auth(true)
+                .with_connect(&endpoint)
+                .with_own_certificate_check(true),
+        )
+        .build()
+        .await
+        .unwrap();
+
+    // Register the listener
+    let uuri = test_lib::new_uuri("vehicle", 1, 1, 0x8000);
+    let listener = Arc::new(CheckListener::new());
+    upclient_client
+        .register_listener(&uuri, None, listener.clone())
+        .await
+        .unwrap();
+
+    // Publish through the TLS link until the connection is established
+    for _ in 0..50 {
+        let umessage = UMessageBuilder::publish(uuri.clone())
+            .build_with_payload("data".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+            .unwrap();
+        upclient_server.send(umessage).await.unwrap();
+        if *listener.received.lock().unwrap() > 0 {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    assert!(*listener.received.lock().unwrap() > 0);
+    assert!(listener.errors.lock().unwrap().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tls_peer_certificate_check() {
+    test_lib::before_test();
+
+    // Initialization: the impostor claims the authority of the client with the certificate of another one
+    let pki = TestPki::new("peer");
+    let (server_cert, server_key) = pki.issue("vehicle");
+    let (client_cert, client_key) = pki.issue("cloud");
+    let (impostor_cert, impostor_key) = pki.issue("impostor");
+    let endpoint = format!("localhost:{}", test_lib::free_port());
+    let tls = |cert: PathBuf, key: PathBuf| {
+        TlsConfig::new(pki.ca_path())
+            .with_certificate(cert, key)
+            .with_mutual_auth(true)
+            .with_peer_certificate_check(true)
+    };
+    let upclient_server = UPClientZenoh::builder("vehicle")
+        .with_config(isolated_config())
+        .with_tls(tls(server_cert, server_key).with_listen(&endpoint))
+        .build()
+        .await
+        .unwrap();
+    let upclient_client = UPClientZenoh::builder("cloud")
+        .with_config(isolated_config())
+        .with_tls(tls(client_cert, client_key).with_connect(&endpoint))
+        .build()
+        .await
+        .unwrap();
+    let upclient_impostor = UPClientZenoh::builder("cloud")
+        .with_config(isolated_config())
+        .with_tls(tls(impostor_cert, impostor_key).with_connect(&endpoint))
+        .build()
+        .await
+        .unwrap();
+
+    // Register the listener
+    let uuri = test_lib::new_uuri("cloud", 1, 1, 0x8000);
+    let listener = Arc::new(CheckListener::new());
+    upclient_server
+        .register_listener(&uuri, None, listener.clone())
+        .await
+        .unwrap();
+    let publish = || {
+        UMessageBuilder::publish(uuri.clone())
+            .build_with_payload("data".to_string(), UPayloadFormat::UPAYLOAD_FORMAT_TEXT)
+            .unwrap()
+    };
+
+    // The message signed with the certificate of its source authority is accepted
+    for _ in 0..50 {
+        upclient_client.send(publish()).await.unwrap();
+        if *listener.received.lock().unwrap() > 0 {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    assert!(*listener.received.lock().unwrap() > 0);
+    assert!(listener.errors.lock().unwrap().is_empty());
+
+    // The message signed with the certificate of another authority is rejected
+    for _ in 0..50 {
+        upclient_impostor.send(publish()).await.unwrap();
+        if !listener.errors.lock().unwrap().is_empty() {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(
+        listener.errors.lock().unwrap().first(),
+        Some(&UCode::PERMISSION_DENIED)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tls_own_certificate_mismatch() {
+    test_lib::before_test();
+
+    let pki = TestPki::new("mismatch");
+    let (cert, key) = pki.issue("another");
+    let result = UPClientZenoh::builder("vehicle")
+        .with_config(isolated_config())
+        .with_tls(
+            TlsConfig::new(pki.ca_path())
+                .with_certificate(cert, key)
+                .with_own_certificate_check(true),
+        )
+        .build()
+        .await;
+    assert_eq!(result.err().unwrap().get_code(), UCode::PERMISSION_DENIED);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tls_invalid_config() {
+    test_lib::before_test();
+
+    let pki = TestPki::new("invalid");
+    // Listening requires the certificate
+    let result = UPClientZenoh::builder("vehicle")
+        .with_config(isolated_config())
+        .with_tls(
+            TlsConfig::new(pki.ca_path())
+                .with_listen(format!("localhost:{}", test_lib::free_port())),
+        )
+        .build()
+        .await;
+    assert_eq!(result.err().unwrap().get_code(), UCode::INVALID_ARGUMENT);
+    // The existing runtime can't be reconfigured
+    let result = UPClientZenoh::builder("vehicle")
+        .with_runtime(test_lib::create_loopback_runtime().await)
+        .with_tls(TlsConfig::new(pki.ca_path()))
+        .build()
+        .await;
+    assert_eq!(result.err().unwrap().get_code(), UCode::INVALID_ARGUMENT);
+}
@@ -17,11 +17,7 @@ use std::{
     str::FromStr,
     sync::{Arc, Mutex},
 };
-use tokio::{
-    runtime::Handle,
-    task,
-    time::{sleep, Duration},
-};
+use tokio::{runtime::Handle, task};
 use up_rust::{
     UListener, UMessage, UMessageBuilder, UMessageType, UPayloadFormat, UStatus, UTransport, UUri,
 };
@@ -166,8 +162,17 @@ async fn test_ustreamer() {
     test_lib::before_test();
 
     // Initialization
-    let uclient = Arc::new(test_lib::create_up_client_zenoh("uclient").await.unwrap());
-    let ustreamer = Arc::new(test_lib::create_up_client_zenoh("ustreamer").await.unwrap());
+    let runtime = test_lib::create_loopback_runtime().await;
+    let uclient = Arc::new(
+        test_lib::create_up_client_zenoh(&runtime, "uclient")
+            .await
+            .unwrap(),
+    );
+    let ustreamer = Arc::new(
+        test_lib::create_up_client_zenoh(&runtime, "ustreamer")
+            .await
+            .unwrap(),
+    );
     let client_auth = "uclient";
     let streamer_auth = "ustreamer";
 
@@ -179,8 +184,6 @@ async fn test_ustreamer() {
         .register_listener(&src_filter, Some(&sink_filter), ustreamer_listener.clone())
         .await
         .unwrap();
-    // Need some time for queryable to run
-    sleep(Duration::from_millis(1000)).await;
 
     // Send Notification (uclient => uStreamer)
     {
@@ -193,7 +196,8 @@ async fn test_ustreamer() {
         uclient.send(umessage).await.unwrap();
 
         // Waiting for the subscriber to receive data
-        sleep(Duration::from_millis(1000)).await;
+        test_lib::wait_until(|| ustreamer_listener.get_recv_notification_data() == target_data)
+            .await;
 
         // Compare the result
         assert_eq!(ustreamer_listener.get_recv_notification_data(), target_data);
@@ -221,7 +225,10 @@ async fn test_ustreamer() {
         uclient.send(umessage).await.unwrap();
 
         // Waiting for the callback to process data
-        sleep(Duration::from_millis(2000)).await;
+        test_lib::wait_until(|| {
+            response_listener.get_response_data() == ustreamer_listener.predefined_resp_data
+        })
+        .await;
 
         // Compare the result
         assert_eq!(
@@ -249,9 +256,6 @@ async fn test_ustreamer() {
             .await
             .unwrap();
 
-        // Waiting for the callback to be ready
-        sleep(Duration::from_millis(1000)).await;
-
         // Send Request (uStreamer => uclient)
         let umessage = UMessageBuilder::request(sink_uuri.clone(), src_uuri.clone(), 1000)
             .build_with_payload(
@@ -262,7 +266,10 @@ async fn test_ustreamer() {
         ustreamer.send(umessage).await.unwrap();
 
         // Waiting for the callback to process data
-        sleep(Duration::from_millis(2000)).await;
+        test_lib::wait_until(|| {
+            ustreamer_listener.get_recv_response_data() == rpc_listener.predefined_resp_data
+        })
+        .await;
 
         // Compare the result
         assert_eq!(